@@ -0,0 +1,42 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// Collect build information shown by --version, since it is only known when
+// building (e.g. the csv version resolved in Cargo.lock)
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let lock_file = Path::new(&manifest_dir).join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock_file.display());
+    let csv_version = fs::read_to_string(&lock_file)
+        .ok()
+        .and_then(|s| locked_version(&s, "csv"))
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=CSVLENS_CSV_VERSION={}", csv_version);
+
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(k, _)| k.strip_prefix("CARGO_FEATURE_").map(|f| f.to_owned()))
+        .map(|f| f.to_lowercase().replace('_', "-"))
+        .filter(|f| f != "default")
+        .collect();
+    features.sort();
+    let features = if features.is_empty() {
+        "none".to_owned()
+    } else {
+        features.join(", ")
+    };
+    println!("cargo:rustc-env=CSVLENS_FEATURES={}", features);
+
+    println!(
+        "cargo:rustc-env=CSVLENS_TARGET={}",
+        env::var("TARGET").unwrap_or_default()
+    );
+}
+
+fn locked_version(lock: &str, package: &str) -> Option<String> {
+    let name_line = format!("name = \"{}\"", package);
+    let mut lines = lock.lines();
+    lines.find(|line| *line == name_line)?;
+    let version = lines.next()?.strip_prefix("version = \"")?;
+    Some(version.trim_end_matches('"').to_owned())
+}
@@ -1,17 +1,32 @@
+use crate::clipboard;
+use crate::column_groups::{self, ColumnGroup};
 use crate::csv;
+use crate::display_options::DisplayOptions;
+use crate::edit;
+use crate::export;
 use crate::find;
-use crate::input::{Control, InputHandler};
-use crate::ui::{CsvTable, CsvTableState, FilterColumnsState, FinderState};
+use crate::input::{Control, InputHandler, InputMode, Key};
+use crate::pipe::Pipe;
+use crate::sort;
+use crate::stats;
+use crate::ui::{
+    CountState, CsvTable, CsvTableState, DetailFindState, FileStats, FilterColumnsState,
+    FinderState, HeaderFindState, HeaderMode, SortState, TruncateSide,
+};
 use crate::view;
 
 use tui::backend::Backend;
+use tui::layout::Alignment;
 use tui::{Frame, Terminal};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use regex::Regex;
-use std::cmp::min;
+use std::cmp::{max, min};
+use std::collections::{HashSet, VecDeque};
+use std::mem;
 use std::sync::Arc;
-use std::usize;
+use std::time::{Duration, Instant};
+use tempfile::NamedTempFile;
 
 fn get_offsets_to_make_visible(
     found_record: find::FoundRecord,
@@ -22,16 +37,20 @@ fn get_offsets_to_make_visible(
     let new_rows_offset = if rows_view.in_view(found_record.row_index() as u64) {
         None
     } else {
-        Some(found_record.row_index() as u64)
+        Some(rows_view.position_of(found_record.row_index() as u64))
     };
 
     let cols_offset = csv_table_state.cols_offset;
     let last_rendered_col = cols_offset.saturating_add(csv_table_state.num_cols_rendered);
-    let column_index = found_record.first_column() as u64;
-    let new_cols_offset = if column_index >= cols_offset && column_index < last_rendered_col {
-        None
-    } else {
-        Some(column_index)
+    // Matches may only be in columns hidden by the columns filter
+    let column_index = found_record
+        .column_indices()
+        .iter()
+        .find_map(|&i| rows_view.shown_column_index(i))
+        .map(|i| i as u64);
+    let new_cols_offset = match column_index {
+        Some(i) if i < cols_offset || i >= last_rendered_col => Some(i),
+        _ => None,
     };
 
     (new_rows_offset, new_cols_offset)
@@ -55,6 +74,24 @@ fn scroll_to_found_record(
     }
 }
 
+/// Delimiter as given to -d
+fn delimiter_name(delimiter: u8) -> String {
+    match delimiter {
+        b'\t' => "tab".to_owned(),
+        d => (d as char).to_string(),
+    }
+}
+
+/// Quote the argument for POSIX shells if needed
+fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-+=./,:@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
 fn get_page_left_cols_offset(frame_width: u16, csv_table_state: &CsvTableState) -> Option<u64> {
     let columns_widths = csv_table_state.column_widths.as_ref();
     let cols_offset = csv_table_state.cols_offset;
@@ -79,23 +116,147 @@ fn get_page_left_cols_offset(frame_width: u16, csv_table_state: &CsvTableState)
     }
 }
 
+/// Sum and number of numeric values in the column within the selection range
+struct SelectionSum {
+    range: (u64, u64),
+    column: usize,
+    sum: Option<(f64, usize)>,
+}
+
+/// How long each step reads rows when looking for a value in a column, so
+/// that the keys are still handled on large files
+const VALUE_SCAN_STEP: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy)]
+enum ValueScanKind {
+    NextChange,
+    PrevChange,
+    FirstSame,
+    LastSame,
+}
+
+/// Rows shown read a chunk at a time over several steps, looking for where
+/// the value of the selected row in the column changes or for all rows with
+/// the same value
+struct ValueScan {
+    kind: ValueScanKind,
+    column: usize,
+    value: Option<String>,
+    // First offset of the next chunk, or the one after its last offset when
+    // reading backward
+    offset: u64,
+    rows_read: u64,
+    // First and last offsets found and how many were found
+    found: Option<(u64, u64)>,
+    count: usize,
+}
+
+/// State of a file opened in a tab other than the active one, whose state is
+/// kept directly in App
+struct Tab {
+    name: String,
+    shared_config: Arc<csv::CsvConfig>,
+    rows_view: view::RowsView,
+    csv_table_state: CsvTableState,
+    finder: Option<find::Finder>,
+    first_found_scrolled: bool,
+    select_first_found: bool,
+    sorter: Option<sort::Sorter>,
+    counter: Option<find::Finder>,
+    suggested_delimiter: Option<u8>,
+    delimiter_warned: bool,
+    fixed_width_file: Option<NamedTempFile>,
+    fixed_width_converter: Option<csv::FixedWidthConverter>,
+}
+
+impl Tab {
+    fn new(
+        config: csv::CsvConfig,
+        original_filename: Option<String>,
+        num_rows: u64,
+    ) -> Result<Self> {
+        let shared_config = Arc::new(config);
+
+        let csvlens_reader = csv::CsvLensReader::new(shared_config.clone())
+            .context(format!("Failed to open file: {}", shared_config.filename()))?;
+        let rows_view = view::RowsView::new(csvlens_reader, num_rows)?;
+
+        let name = original_filename
+            .clone()
+            .unwrap_or_else(|| "stdin".to_owned());
+        let mut csv_table_state = CsvTableState::new(original_filename, rows_view.headers().len());
+        csv_table_state.null_tokens = shared_config.null_tokens.clone();
+        // Only a hint, so files that can't be sampled are just not warned about
+        let suggested_delimiter = csv::suggest_delimiter(&shared_config).unwrap_or(None);
+
+        Ok(Tab {
+            name,
+            shared_config,
+            rows_view,
+            csv_table_state,
+            finder: None,
+            first_found_scrolled: false,
+            select_first_found: false,
+            sorter: None,
+            counter: None,
+            suggested_delimiter,
+            delimiter_warned: false,
+            fixed_width_file: None,
+            fixed_width_converter: None,
+        })
+    }
+}
+
 pub struct App {
     input_handler: InputHandler,
     num_rows_not_visible: u16,
+    tab_name: String,
     shared_config: Arc<csv::CsvConfig>,
     rows_view: view::RowsView,
     csv_table_state: CsvTableState,
     finder: Option<find::Finder>,
     first_found_scrolled: bool,
+    // Opened with --find: select the first row found, or warn if none is
+    select_first_found: bool,
+    sorter: Option<sort::Sorter>,
+    // Counts matching rows without changing what is shown
+    counter: Option<find::Finder>,
+    // Delimiter the values seem to be delimited by instead, warned about once
+    suggested_delimiter: Option<u8>,
+    delimiter_warned: bool,
+    // Fixed-width text converted again in the app, e.g. with other columns
+    fixed_width_file: Option<NamedTempFile>,
+    // Converts the fixed-width text again in the background, shown once done
+    fixed_width_converter: Option<csv::FixedWidthConverter>,
+    // Other tabs, starting from the one after the active tab and wrapping around
+    tabs: VecDeque<Tab>,
+    active_tab: usize,
     frame_width: Option<u16>,
     user_error: Option<String>,
+    user_message: Option<String>,
+    export_options: export::ExportOptions,
+    cell_format: export::CellFormat,
+    find_all_columns: bool,
+    pending_goto: Option<usize>,
     show_stats: bool,
+    editable: bool,
+    wrap_columns: bool,
+    column_groups: Vec<ColumnGroup>,
+    pipe: Option<Pipe>,
+    // Input for the pipe command, run once back in the main loop
+    pending_pipe: Option<String>,
+    // Selection sum last read, kept until the range or column changes
+    selection_sum: Option<SelectionSum>,
+    // Looking for a value in the column until another key is pressed
+    value_scan: Option<ValueScan>,
+    // Options for how the file was read that the config doesn't keep, e.g.
+    // --skip-rows, for the command line recreating the view
+    source_args: Vec<String>,
 }
 
 impl App {
-    pub fn new(
-        filename: &str,
-        delimiter: Option<u8>,
+    pub fn with_config(
+        config: csv::CsvConfig,
         original_filename: Option<String>,
         show_stats: bool,
     ) -> Result<Self> {
@@ -107,74 +268,708 @@ impl App {
         // Number of rows that are visible in the current frame
         let num_rows = 50 - num_rows_not_visible;
 
-        let mut config = csv::CsvConfig::new(filename);
-        if let Some(d) = delimiter {
-            config.delimiter = d;
-        }
-        let shared_config = Arc::new(config);
-
-        let csvlens_reader = csv::CsvLensReader::new(shared_config.clone())
-            .context(format!("Failed to open file: {}", filename))?;
-        let rows_view = view::RowsView::new(csvlens_reader, num_rows as u64)?;
-
-        let csv_table_state = CsvTableState::new(original_filename, rows_view.headers().len());
+        let tab = Tab::new(config, original_filename, num_rows as u64)?;
 
-        let finder: Option<find::Finder> = None;
-        let first_found_scrolled = false;
         let frame_width = None;
 
         let user_error: Option<String> = None;
+        let user_message: Option<String> = None;
 
-        let app = App {
+        let mut app = App {
             input_handler,
-            shared_config,
             num_rows_not_visible,
-            rows_view,
-            csv_table_state,
-            finder,
-            first_found_scrolled,
+            tab_name: tab.name,
+            shared_config: tab.shared_config,
+            rows_view: tab.rows_view,
+            csv_table_state: tab.csv_table_state,
+            finder: tab.finder,
+            first_found_scrolled: tab.first_found_scrolled,
+            select_first_found: tab.select_first_found,
+            sorter: tab.sorter,
+            counter: tab.counter,
+            suggested_delimiter: tab.suggested_delimiter,
+            delimiter_warned: tab.delimiter_warned,
+            fixed_width_file: tab.fixed_width_file,
+            fixed_width_converter: tab.fixed_width_converter,
+            tabs: VecDeque::new(),
+            active_tab: 0,
             frame_width,
             user_error,
+            user_message,
+            export_options: export::ExportOptions::default(),
+            cell_format: export::CellFormat::KeyValue,
+            find_all_columns: false,
+            pending_goto: None,
             show_stats,
+            editable: false,
+            wrap_columns: false,
+            column_groups: vec![],
+            pipe: None,
+            pending_pipe: None,
+            selection_sum: None,
+            value_scan: None,
+            source_args: vec![],
         };
+        app.warn_about_delimiter();
 
         Ok(app)
     }
 
+    /// Open another file in a new tab after the existing ones
+    pub fn add_tab(
+        &mut self,
+        config: csv::CsvConfig,
+        original_filename: Option<String>,
+    ) -> Result<()> {
+        let num_rows = self.rows_view.num_rows();
+        let tab = Tab::new(config, original_filename, num_rows)?;
+        // the last tab is right before the active one
+        let num_after_active = self.tabs.len() - self.active_tab;
+        self.tabs.insert(num_after_active, tab);
+        Ok(())
+    }
+
+    fn num_tabs(&self) -> usize {
+        self.tabs.len() + 1
+    }
+
+    fn swap_active_tab(&mut self, tab: &mut Tab) {
+        mem::swap(&mut self.tab_name, &mut tab.name);
+        mem::swap(&mut self.shared_config, &mut tab.shared_config);
+        mem::swap(&mut self.rows_view, &mut tab.rows_view);
+        mem::swap(&mut self.csv_table_state, &mut tab.csv_table_state);
+        mem::swap(&mut self.finder, &mut tab.finder);
+        mem::swap(
+            &mut self.first_found_scrolled,
+            &mut tab.first_found_scrolled,
+        );
+        mem::swap(&mut self.select_first_found, &mut tab.select_first_found);
+        mem::swap(&mut self.sorter, &mut tab.sorter);
+        mem::swap(&mut self.counter, &mut tab.counter);
+        mem::swap(&mut self.suggested_delimiter, &mut tab.suggested_delimiter);
+        mem::swap(&mut self.delimiter_warned, &mut tab.delimiter_warned);
+        mem::swap(&mut self.fixed_width_file, &mut tab.fixed_width_file);
+        mem::swap(
+            &mut self.fixed_width_converter,
+            &mut tab.fixed_width_converter,
+        );
+    }
+
+    /// Warn the first time the tab is shown if the values seem to be
+    /// delimited by another delimiter, or to be fixed-width text
+    fn warn_about_delimiter(&mut self) {
+        if self.delimiter_warned {
+            return;
+        }
+        self.delimiter_warned = true;
+        if let Some(d) = self.suggested_delimiter {
+            self.user_error = Some(format!(
+                "Many values contain {:?}, reopen with it as the delimiter from the palette (:)?",
+                d as char
+            ));
+        } else if self.shared_config.fixed_width.is_none()
+            && csv::suggest_fixed_width(&self.shared_config)
+                .unwrap_or(None)
+                .is_some()
+        {
+            self.user_error = Some(
+                "Values seem to be in fixed-width columns, reopen as such from the palette (:)?"
+                    .to_owned(),
+            );
+        }
+    }
+
+    /// Read the file of the tab again with another delimiter, from the top
+    fn reopen_with_delimiter(&mut self, delimiter: u8) -> Result<()> {
+        let mut config = (*self.shared_config).clone();
+        config.delimiter = delimiter;
+        self.reopen_with_config(config)
+    }
+
+    /// Convert the fixed-width text of the tab again with the column starts,
+    /// or as guessed if none, or convert the file itself if it isn't yet. It
+    /// is converted in the background and shown once done, dropping any
+    /// conversion not done yet.
+    fn reopen_fixed_width(&mut self, starts: Option<Vec<usize>>) -> Result<()> {
+        let (source, skip_rows) = match &self.shared_config.fixed_width {
+            Some(fixed_width) => (fixed_width.source.clone(), fixed_width.skip_rows),
+            None => (self.shared_config.filename().to_owned(), 0),
+        };
+        self.fixed_width_converter =
+            Some(csv::FixedWidthConverter::new(&source, starts, skip_rows)?);
+        Ok(())
+    }
+
+    /// Show the fixed-width text converted in the background
+    fn show_fixed_width(
+        &mut self,
+        converter: &csv::FixedWidthConverter,
+        file: NamedTempFile,
+        starts: Vec<usize>,
+    ) -> Result<()> {
+        // Read as written by the conversion
+        let mut config = (*self.shared_config).clone();
+        config.set_path(file.path().to_str().unwrap());
+        config.delimiter = b',';
+        config.quoting = true;
+        config.quote = b'"';
+        config.escape = None;
+        config.fixed_width = Some(csv::FixedWidth {
+            source: converter.source().to_owned(),
+            starts,
+            skip_rows: converter.skip_rows(),
+        });
+        // Stay where the rows were when only the columns changed
+        let was_fixed_width = self.shared_config.fixed_width.is_some();
+        // Reopening drops the sort, so take the position in file order
+        self.rows_view.set_sorted(None)?;
+        let position = self.rows_view.position();
+        let cols_offset = self.csv_table_state.cols_offset;
+        self.reopen_with_config(config)?;
+        self.fixed_width_file = Some(file);
+        if was_fixed_width {
+            self.rows_view.restore_position(position.0, position.1);
+            let last_column = self.rows_view.headers().len().saturating_sub(1) as u64;
+            self.csv_table_state
+                .set_cols_offset(min(cols_offset, last_column));
+        }
+        Ok(())
+    }
+
+    /// Message with where the columns of fixed-width text start, or will once
+    /// converted again
+    fn column_starts_message(&self) -> String {
+        let converting = self.fixed_width_converter.as_ref();
+        let starts = match (converting, &self.shared_config.fixed_width) {
+            (Some(converter), _) if converter.starts().is_none() => {
+                return "Converting fixed-width text...".to_owned()
+            }
+            (Some(converter), _) => converter.starts().unwrap(),
+            (None, Some(fixed_width)) => &fixed_width.starts,
+            (None, None) => return "Not fixed-width text".to_owned(),
+        };
+        let starts: Vec<String> = starts.iter().map(|s| s.to_string()).collect();
+        format!("Columns start at {}", starts.join(","))
+    }
+
+    /// Move where the current column starts by the number of characters,
+    /// keeping it between the columns around it. Moves add up to those not
+    /// converted yet.
+    fn move_column_start(&mut self, delta: isize) {
+        let converting = self.fixed_width_converter.as_ref().and_then(|c| c.starts());
+        let mut starts = match (converting, &self.shared_config.fixed_width) {
+            (Some(starts), _) => starts.to_vec(),
+            (None, Some(fixed_width)) => fixed_width.starts.clone(),
+            (None, None) => {
+                self.user_error = Some(
+                    "Only fixed-width columns can be moved, reopen as such from the palette (:)"
+                        .to_owned(),
+                );
+                return;
+            }
+        };
+        let current_column = self.csv_table_state.current_column() as usize;
+        let column = match self.rows_view.original_column_index(current_column) {
+            Some(column) if column > 0 => column,
+            _ => {
+                self.user_error = Some("The first column always starts at 0".to_owned());
+                return;
+            }
+        };
+        let start = starts[column] as isize + delta;
+        let next = starts.get(column + 1).map(|&s| s as isize);
+        if start <= starts[column - 1] as isize || matches!(next, Some(next) if start >= next) {
+            self.user_error = Some("Columns can't be moved past each other".to_owned());
+            return;
+        }
+        starts[column] = start as usize;
+        self.reopen_fixed_width_or_warn(Some(starts));
+    }
+
+    /// Same as reopen_fixed_width, with any error shown instead of returned
+    fn reopen_fixed_width_or_warn(&mut self, starts: Option<Vec<usize>>) {
+        match self.reopen_fixed_width(starts) {
+            Ok(()) => self.user_message = Some(self.column_starts_message()),
+            Err(e) => self.user_error = Some(format!("{:#}", e)),
+        }
+    }
+
+    /// Show another reader of the file of the tab with the config, from the
+    /// top
+    fn reopen_with_config(&mut self, config: csv::CsvConfig) -> Result<()> {
+        self.shared_config = Arc::new(config);
+        let reader = csv::CsvLensReader::new(self.shared_config.clone()).context(format!(
+            "Failed to open file: {}",
+            self.shared_config.filename()
+        ))?;
+        self.rows_view.reopen(reader)?;
+        self.finder = None;
+        self.sorter = None;
+        self.counter = None;
+        self.suggested_delimiter = None;
+        self.csv_table_state.finder_state = FinderState::FinderInactive;
+        self.csv_table_state.header_find = None;
+        self.csv_table_state.expanded_row = None;
+        self.csv_table_state.set_cols_offset(0);
+        Ok(())
+    }
+
+    fn next_tab(&mut self) {
+        if let Some(mut tab) = self.tabs.pop_front() {
+            self.swap_active_tab(&mut tab);
+            self.tabs.push_back(tab);
+            self.active_tab = (self.active_tab + 1) % self.num_tabs();
+        }
+    }
+
+    fn prev_tab(&mut self) {
+        if let Some(mut tab) = self.tabs.pop_back() {
+            self.swap_active_tab(&mut tab);
+            self.tabs.push_front(tab);
+            self.active_tab = (self.active_tab + self.num_tabs() - 1) % self.num_tabs();
+        }
+    }
+
+    fn close_tab(&mut self) {
+        // the next tab takes the place of the closed one
+        if let Some(mut tab) = self.tabs.pop_front() {
+            self.swap_active_tab(&mut tab);
+            if self.active_tab == self.num_tabs() {
+                self.active_tab = 0;
+            }
+        }
+    }
+
+    /// Names of all tabs in order
+    fn tab_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Some(self.tab_name.clone())
+            .into_iter()
+            .chain(self.tabs.iter().map(|tab| tab.name.clone()))
+            .collect();
+        names.rotate_right(self.active_tab);
+        names
+    }
+
+    fn for_each_tab<F: FnMut(&mut view::RowsView, &mut CsvTableState)>(&mut self, mut f: F) {
+        f(&mut self.rows_view, &mut self.csv_table_state);
+        for tab in self.tabs.iter_mut() {
+            f(&mut tab.rows_view, &mut tab.csv_table_state);
+        }
+    }
+
+    /// Set stats of each file, in the order the tabs were added
+    pub fn set_file_stats(&mut self, file_stats: Vec<FileStats>) {
+        let mut file_stats = file_stats.into_iter();
+        self.for_each_tab(|_, csv_table_state| {
+            if let Some(stats) = file_stats.next() {
+                csv_table_state.debug_stats.file_stats(stats);
+            }
+        });
+    }
+
+    /// Scroll to the first column when scrolling right past the last one, and
+    /// the other way around
+    pub fn set_wrap_columns(&mut self, wrap_columns: bool) {
+        self.wrap_columns = wrap_columns;
+    }
+
+    /// Start without a selected row, so that scrolling only moves the rows
+    pub fn disable_selection(&mut self) {
+        self.for_each_tab(|rows_view, _| rows_view.reset_selected());
+    }
+
+    pub fn set_scrolloff(&mut self, scrolloff: u64) {
+        self.for_each_tab(|rows_view, _| rows_view.set_scrolloff(scrolloff));
+    }
+
+    pub fn set_page_overlap(&mut self, page_overlap: u64) {
+        self.for_each_tab(|rows_view, _| rows_view.set_page_overlap(page_overlap));
+    }
+
+    /// Positions of the files (not stdin) in all tabs
+    pub fn positions(&mut self) -> Vec<(String, (u64, u64))> {
+        let mut positions = vec![];
+        self.for_each_tab(|rows_view, csv_table_state| {
+            if let Some(filename) = csv_table_state.filename() {
+                positions.push((filename.to_owned(), rows_view.position()));
+            }
+        });
+        positions
+    }
+
+    /// Scroll to the positions of the files given by the function if any
+    pub fn restore_positions<F: Fn(&str) -> Option<(u64, u64)>>(&mut self, f: F) {
+        self.for_each_tab(|rows_view, csv_table_state| {
+            if let Some((rows_from, selected)) = csv_table_state.filename().and_then(&f) {
+                rows_view.restore_position(rows_from, selected);
+            }
+        });
+    }
+
+    pub fn set_min_column_width(&mut self, min_column_width: u16) {
+        self.for_each_tab(|_, csv_table_state| {
+            csv_table_state.min_column_width = min_column_width;
+        });
+    }
+
+    pub fn set_show_empty_cells(&mut self, show_empty_cells: bool) {
+        self.for_each_tab(|_, csv_table_state| {
+            csv_table_state.show_empty_cells = show_empty_cells;
+        });
+    }
+
+    pub fn set_pipe(&mut self, pipe: Pipe) {
+        self.pipe = Some(pipe);
+    }
+
+    pub fn set_source_args(&mut self, source_args: Vec<String>) {
+        self.source_args = source_args;
+    }
+
+    pub fn set_column_groups(&mut self, column_groups: Vec<ColumnGroup>) {
+        self.column_groups = column_groups;
+    }
+
+    pub fn display_options(&self) -> DisplayOptions {
+        DisplayOptions {
+            show_whitespace: self.csv_table_state.show_whitespace,
+            compact: self.csv_table_state.compact,
+            grid: self.csv_table_state.grid,
+            show_changes: self.csv_table_state.show_changes,
+            show_ruler: self.csv_table_state.show_ruler,
+            hide_status: self.csv_table_state.hide_status,
+            show_cell_position: self.csv_table_state.show_cell_position,
+            show_cell_length: self.csv_table_state.show_cell_length,
+            show_booleans: self.csv_table_state.show_booleans,
+            show_scientific: self.csv_table_state.show_scientific,
+            gutter_mode: self.csv_table_state.gutter_mode,
+        }
+    }
+
+    pub fn set_display_options(&mut self, options: DisplayOptions) {
+        self.for_each_tab(|_, csv_table_state| {
+            csv_table_state.show_whitespace = options.show_whitespace;
+            csv_table_state.compact = options.compact;
+            csv_table_state.grid = options.grid;
+            csv_table_state.show_changes = options.show_changes;
+            csv_table_state.show_ruler = options.show_ruler;
+            csv_table_state.hide_status = options.hide_status;
+            csv_table_state.show_cell_position = options.show_cell_position;
+            csv_table_state.show_cell_length = options.show_cell_length;
+            csv_table_state.show_booleans = options.show_booleans;
+            csv_table_state.show_scientific = options.show_scientific;
+            csv_table_state.gutter_mode = options.gutter_mode;
+        });
+    }
+
+    pub fn set_grid(&mut self, grid: bool) {
+        self.for_each_tab(|_, csv_table_state| {
+            csv_table_state.grid = grid;
+        });
+    }
+
+    pub fn set_tab_width(&mut self, tab_width: Option<u16>) {
+        self.for_each_tab(|_, csv_table_state| {
+            csv_table_state.tab_width = tab_width;
+        });
+    }
+
+    pub fn set_ascii(&mut self, ascii: bool) {
+        self.for_each_tab(|_, csv_table_state| {
+            csv_table_state.ascii = ascii;
+        });
+    }
+
+    pub fn set_compact(&mut self, compact: bool) {
+        self.for_each_tab(|_, csv_table_state| {
+            csv_table_state.compact = compact;
+        });
+    }
+
+    pub fn set_show_whitespace(&mut self, show_whitespace: bool) {
+        self.for_each_tab(|_, csv_table_state| {
+            csv_table_state.show_whitespace = show_whitespace;
+        });
+    }
+
+    pub fn set_truncate_side(&mut self, truncate_side: TruncateSide) {
+        self.for_each_tab(|_, csv_table_state| {
+            csv_table_state.truncate_side = truncate_side;
+        });
+    }
+
+    pub fn set_header_mode(&mut self, header_mode: HeaderMode) {
+        self.for_each_tab(|_, csv_table_state| {
+            csv_table_state.header_mode = header_mode;
+        });
+    }
+
+    /// Allow editing cells, which writes the changes back to the file
+    pub fn set_editable(&mut self, editable: bool) {
+        self.editable = editable;
+    }
+
+    pub fn set_quit_keys(&mut self, keys: Vec<Key>) {
+        self.input_handler.set_quit_keys(keys);
+    }
+
+    pub fn set_confirm_quit(&mut self, confirm_quit: bool) {
+        self.input_handler.set_confirm_quit(confirm_quit);
+    }
+
+    pub fn set_cell_format(&mut self, cell_format: export::CellFormat) {
+        self.cell_format = cell_format;
+    }
+
+    pub fn set_export_options(&mut self, export_options: export::ExportOptions) {
+        self.export_options = export_options;
+    }
+
+    /// Filter rows of every tab, e.g. as given on the command line
+    pub fn set_columns_filter(&mut self, s: &str) -> Result<()> {
+        for _ in 0..self.num_tabs() {
+            // Checked first since step only shows the error in the status bar
+            view::ColumnsFilter::parse(s, self.rows_view.all_headers())?;
+            self.step(Control::FilterColumns(s.to_owned()))?;
+            self.next_tab();
+        }
+        Ok(())
+    }
+
+    /// Number the rows from row_base, counting the header as the first row if
+    /// count_header
+    pub fn set_row_numbering(&mut self, row_base: u64, count_header: bool) {
+        self.for_each_tab(|_, csv_table_state| {
+            csv_table_state.row_base = row_base;
+            csv_table_state.count_header = count_header;
+        });
+    }
+
+    /// Scroll to the row once the rows are known, i.e. once done filtering
+    pub fn set_goto(&mut self, n: usize) {
+        self.pending_goto = Some(n);
+    }
+
+    pub fn set_filter(&mut self, s: &str) -> Result<()> {
+        for _ in 0..self.num_tabs() {
+            // Checked first since step only shows the error in the status bar
+            find::Target::parse(s, self.rows_view.all_headers())?;
+            self.step(Control::Filter(s.to_owned()))?;
+            self.next_tab();
+        }
+        Ok(())
+    }
+
+    /// Find the first row matching in each tab, scrolling to and selecting it
+    /// without filtering
+    pub fn set_find(&mut self, s: &str) -> Result<()> {
+        for _ in 0..self.num_tabs() {
+            find::Target::parse(s, self.rows_view.all_headers())?;
+            self.step(Control::Find(s.to_owned()))?;
+            self.select_first_found = true;
+            self.next_tab();
+        }
+        Ok(())
+    }
+
     pub fn main_loop<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        // The number of rows shown is only known once drawn, e.g. for where
+        // the row given by --goto ends up
+        self.draw(terminal)?;
         loop {
             let control = self.input_handler.next();
-            if matches!(control, Control::Quit) {
+            if matches!(control, Control::Quit)
+                || (matches!(control, Control::CloseTab) && self.num_tabs() == 1)
+            {
                 break;
             }
+            // Drawn from scratch rather than only where changed, in case the
+            // terminal got out of sync, e.g. after a glitch while resizing
+            if matches!(control, Control::Refresh) {
+                terminal.clear()?;
+            }
             self.step(control)?;
+            if let Some(input) = self.pending_pipe.take() {
+                self.run_pipe(&input);
+                terminal.clear()?;
+                self.step(Control::Nothing)?;
+            }
             self.draw(terminal)?;
         }
         Ok(())
     }
 
+    fn run_pipe(&mut self, input: &str) {
+        let pipe = match &self.pipe {
+            Some(pipe) => pipe,
+            None => return,
+        };
+        // The command gets the keys instead while it runs
+        self.input_handler.set_paused(true);
+        let result = pipe.run(input);
+        self.input_handler.set_paused(false);
+        match result {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                self.user_error = Some(format!("Command failed ({}): {}", status, pipe.command()))
+            }
+            Err(e) => self.user_error = Some(format!("{:#}", e)),
+        }
+    }
+
+    /// Record indices of all rows, or of those matching the filter if any, or
+    /// none if they aren't all known yet
+    fn filtered_row_indices(&self) -> Option<Vec<u64>> {
+        match &self.finder {
+            Some(fdr) if self.rows_view.is_filter() => {
+                if !fdr.done() {
+                    return None;
+                }
+                let found = fdr.found_between(0, usize::MAX);
+                Some(found.into_iter().map(|i| i as u64).collect())
+            }
+            _ => {
+                let total = self.rows_view.get_total_line_numbers()?;
+                Some((0..total as u64).collect())
+            }
+        }
+    }
+
     fn step(&mut self, control: Control) -> Result<()> {
+        let control = match (self.pending_goto, control) {
+            (Some(n), Control::Nothing) => {
+                let filtering_done = match &self.finder {
+                    Some(fdr) if self.rows_view.is_filter() => {
+                        fdr.done() && self.rows_view.num_filtered_rows() == Some(fdr.count())
+                    }
+                    _ => true,
+                };
+                if filtering_done {
+                    self.pending_goto = None;
+                    Control::ScrollTo(n)
+                } else {
+                    Control::Nothing
+                }
+            }
+            (_, control) => {
+                self.pending_goto = None;
+                control
+            }
+        };
+
+        // Rows are gone to by the numbers shown, which may not start from 1
+        let control = match control {
+            Control::ScrollTo(n) => Control::ScrollTo(self.position_of_row_number(n)),
+            control => control,
+        };
+
+        // Shown after the control, e.g. after going to a row
+        let mut message = None;
+
+        // Any other key stops looking for a value
+        if !matches!(control, Control::Nothing) {
+            self.value_scan = None;
+        }
+
+        // Same as filtering by an expression on the current column
+        let control = match control {
+            Control::ShowEmptyInColumn | Control::ShowNonEmptyInColumn => {
+                let empty = matches!(control, Control::ShowEmptyInColumn);
+                match self.emptiness_expr(empty) {
+                    Ok(expr) => Control::Filter(expr),
+                    Err(e) => {
+                        self.user_error = Some(e.to_string());
+                        Control::Nothing
+                    }
+                }
+            }
+            Control::ReapplyFilter => match self.rows_view.last_filter() {
+                Some(pattern) => Control::Filter(pattern.to_owned()),
+                None => {
+                    self.user_error = Some("No filter to apply again".to_owned());
+                    Control::Nothing
+                }
+            },
+            // Same as going to the row once found
+            Control::NextValueChange
+            | Control::PrevValueChange
+            | Control::FirstSameValue
+            | Control::LastSameValue => {
+                let kind = match control {
+                    Control::NextValueChange => ValueScanKind::NextChange,
+                    Control::PrevValueChange => ValueScanKind::PrevChange,
+                    Control::FirstSameValue => ValueScanKind::FirstSame,
+                    _ => ValueScanKind::LastSame,
+                };
+                self.user_error = None;
+                self.value_scan = self.new_value_scan(kind);
+                self.continue_value_scan(&mut message)?
+            }
+            Control::Nothing if self.value_scan.is_some() => {
+                self.continue_value_scan(&mut message)?
+            }
+            control => control,
+        };
+
         // clear error message without changing other states on any action
         if !matches!(control, Control::Nothing) {
             self.user_error = None;
+            self.user_message = message;
+        }
+
+        match &control {
+            Control::NextTab => self.next_tab(),
+            Control::PrevTab => self.prev_tab(),
+            Control::CloseTab => self.close_tab(),
+            _ => {}
+        }
+        if matches!(
+            control,
+            Control::NextTab | Control::PrevTab | Control::CloseTab
+        ) {
+            self.warn_about_delimiter();
         }
 
         self.rows_view.handle_control(&control)?;
 
         match &control {
-            Control::ScrollTo(_) => {
+            Control::ScrollTo(_) | Control::ScrollToPercent(_) => {
                 self.csv_table_state.reset_buffer();
             }
             Control::ScrollLeft => {
-                let new_cols_offset = self.csv_table_state.cols_offset.saturating_sub(1);
+                // Already at the first column, which stays shown when
+                // freezing panes
+                if self.csv_table_state.current_column() == 0 {
+                    if self.wrap_columns {
+                        let last_column = self.rows_view.headers().len().saturating_sub(1);
+                        self.csv_table_state.set_cols_offset(last_column as u64);
+                        self.user_message = Some("Wrapped around to the last column".to_owned());
+                    }
+                } else {
+                    let cols_offset = self.csv_table_state.cols_offset;
+                    self.csv_table_state
+                        .set_cols_offset(cols_offset.saturating_sub(1));
+                }
+            }
+            Control::ScrollRight if self.csv_table_state.has_more_cols_to_show() => {
+                // From the pinned first column to the column after it
+                let cols_offset = self.csv_table_state.cols_offset;
+                let new_cols_offset = if self.csv_table_state.current_column() < cols_offset {
+                    cols_offset
+                } else {
+                    cols_offset.saturating_add(1)
+                };
                 self.csv_table_state.set_cols_offset(new_cols_offset);
             }
-            Control::ScrollRight => {
-                if self.csv_table_state.has_more_cols_to_show() {
-                    let new_cols_offset = self.csv_table_state.cols_offset.saturating_add(1);
-                    self.csv_table_state.set_cols_offset(new_cols_offset);
+            Control::CenterColumn => {
+                if let Some(frame_width) = self.frame_width {
+                    self.csv_table_state.center_current_column(frame_width);
                 }
             }
+            Control::ScrollRight if self.wrap_columns => {
+                self.csv_table_state.set_cols_offset(0);
+                self.user_message = Some("Wrapped around to the first column".to_owned());
+            }
             Control::ScrollPageLeft => {
                 let new_cols_offset = match self.frame_width {
                     Some(frame_width) => {
@@ -186,22 +981,50 @@ impl App {
                     self.csv_table_state.set_cols_offset(new_cols_offset);
                 }
             }
-            Control::ScrollPageRight => {
-                if self.csv_table_state.has_more_cols_to_show() {
-                    // num_cols_rendered includes the last truncated column
-                    let mut new_cols_offset = self
-                        .csv_table_state
-                        .cols_offset
-                        .saturating_add(self.csv_table_state.num_cols_rendered.saturating_sub(1));
-                    new_cols_offset = min(
-                        new_cols_offset,
-                        self.rows_view.headers().len().saturating_sub(1) as u64,
-                    );
-                    if new_cols_offset != self.csv_table_state.cols_offset {
-                        self.csv_table_state.set_cols_offset(new_cols_offset);
+            Control::ScrollPageRight if self.csv_table_state.has_more_cols_to_show() => {
+                // num_cols_rendered includes the last truncated column
+                let mut new_cols_offset = self
+                    .csv_table_state
+                    .cols_offset
+                    .saturating_add(self.csv_table_state.num_cols_rendered.saturating_sub(1));
+                new_cols_offset = min(
+                    new_cols_offset,
+                    self.rows_view.headers().len().saturating_sub(1) as u64,
+                );
+                if new_cols_offset != self.csv_table_state.cols_offset {
+                    self.csv_table_state.set_cols_offset(new_cols_offset);
+                }
+            }
+            // Find within the popup while it is shown
+            Control::Find(s) if self.csv_table_state.show_detail => {
+                match Regex::new(s) {
+                    Ok(regex) => {
+                        self.csv_table_state.detail_find = Some(DetailFindState::new(regex))
                     }
+                    Err(_) => self.user_error = Some(format!("Invalid regex: {}", s)),
+                }
+                self.csv_table_state.reset_buffer();
+            }
+            Control::ScrollToNextFound if self.csv_table_state.show_detail => {
+                if let Some(find) = self.csv_table_state.detail_find.as_mut() {
+                    find.next();
+                }
+            }
+            Control::ScrollToPrevFound if self.csv_table_state.show_detail => {
+                if let Some(find) = self.csv_table_state.detail_find.as_mut() {
+                    find.prev();
                 }
             }
+            Control::ClearFilter if self.csv_table_state.show_detail => {
+                self.csv_table_state.detail_find = None;
+                self.csv_table_state.reset_buffer();
+            }
+            Control::ScrollToNextFound if self.csv_table_state.header_find.is_some() => {
+                self.find_header(true);
+            }
+            Control::ScrollToPrevFound if self.csv_table_state.header_find.is_some() => {
+                self.find_header(false);
+            }
             Control::ScrollToNextFound if !self.rows_view.is_filter() => {
                 if let Some(fdr) = self.finder.as_mut() {
                     if let Some(found_record) = fdr.next() {
@@ -224,12 +1047,30 @@ impl App {
                     }
                 }
             }
+            Control::Find(s) if s.starts_with("h:") => {
+                match Regex::new(&s["h:".len()..]) {
+                    Ok(regex) => {
+                        // Replaces finding rows, but not filtering them
+                        if !self.rows_view.is_filter() {
+                            self.finder = None;
+                            self.csv_table_state.finder_state = FinderState::FinderInactive;
+                        }
+                        self.csv_table_state.header_find = Some(HeaderFindState::new(regex));
+                        self.find_header(true);
+                    }
+                    Err(_) => self.user_error = Some(format!("Invalid regex: {}", s)),
+                }
+                self.csv_table_state.reset_buffer();
+            }
             Control::Find(s) | Control::Filter(s) => {
-                let re = Regex::new(s.as_str());
-                if let Ok(target) = re {
+                self.csv_table_state.header_find = None;
+                let target =
+                    find::Target::parse(s, self.rows_view.all_headers()).map_err(|e| e.to_string());
+                if let Ok(target) = target {
                     // TODO: need to reset row views filter if any first?
-                    self.finder =
-                        Some(find::Finder::new(self.shared_config.clone(), target).unwrap());
+                    let is_filter = matches!(control, Control::Filter(_));
+                    self.finder = Some(self.new_finder(target, is_filter));
+                    self.rows_view.clear_selection_range();
                     match control {
                         Control::Find(_) => {
                             // will scroll to first result below once ready
@@ -237,6 +1078,10 @@ impl App {
                             self.rows_view.reset_filter().unwrap();
                         }
                         Control::Filter(_) => {
+                            self.rows_view.set_last_filter(s);
+                            // Filtered rows are in file order
+                            self.sorter = None;
+                            self.rows_view.set_sorted(None)?;
                             self.rows_view.set_rows_from(0).unwrap();
                             self.rows_view
                                 .set_filter(self.finder.as_ref().unwrap())
@@ -244,40 +1089,405 @@ impl App {
                         }
                         _ => {}
                     }
-                } else {
-                    self.finder = None;
+                } else if let Err(e) = target {
+                    // Keep showing the previous result so that a typo doesn't
+                    // lose it
                     // TODO: how to show multi-line error
-                    self.user_error = Some(format!("Invalid regex: {}", s));
+                    self.user_error = Some(e);
                 }
                 self.csv_table_state.reset_buffer();
             }
-            Control::FilterColumns(s) => {
-                let re = Regex::new(s.as_str());
-                if let Ok(target) = re {
-                    self.rows_view.set_columns_filter(target).unwrap();
-                } else {
-                    self.rows_view.reset_columns_filter().unwrap();
-                    self.user_error = Some(format!("Invalid regex: {}", s));
+            Control::Count(s) => {
+                // Rows are counted in all columns like when filtering
+                match find::Target::parse(s, self.rows_view.all_headers()) {
+                    Ok(target) => self.counter = Some(self.new_finder(target, true)),
+                    Err(e) => self.user_error = Some(e.to_string()),
                 }
                 self.csv_table_state.reset_buffer();
-                self.csv_table_state.set_cols_offset(0);
             }
-            Control::BufferContent(buf) => {
-                self.csv_table_state
-                    .set_buffer(self.input_handler.mode(), buf.as_str());
+            Control::ClearCount => {
+                self.counter = None;
+                self.csv_table_state.reset_buffer();
             }
-            Control::BufferReset => {
+            Control::FilterColumns(s) => {
+                self.filter_columns(s)?;
                 self.csv_table_state.reset_buffer();
-                if self.finder.is_some() {
-                    self.finder = None;
-                    self.csv_table_state.finder_state = FinderState::FinderInactive;
-                    self.rows_view.reset_filter().unwrap();
-                }
-                self.rows_view.reset_columns_filter().unwrap();
+            }
+            Control::ReplacePreview(s) => {
+                self.csv_table_state.reset_buffer();
+                let current_column = self.csv_table_state.current_column() as usize;
+                // Replaced in the same column again from the preview column
+                let column = self
+                    .rows_view
+                    .original_column_index(current_column)
+                    .or_else(|| self.rows_view.replace_preview().map(|p| p.column()))
+                    .unwrap_or(0);
+                match view::ReplacePreview::parse(s, column, self.rows_view.all_headers()) {
+                    Ok(preview) => {
+                        self.user_message = Some(format!(
+                            "Showing {} replaced in the last column",
+                            self.rows_view.all_headers()[column]
+                        ));
+                        self.rows_view.set_replace_preview(Some(preview))?;
+                    }
+                    Err(e) => self.user_error = Some(e.to_string()),
+                }
+            }
+            Control::ClearReplacePreview => {
+                self.csv_table_state.reset_buffer();
+                if let Some(column) = self.rows_view.replace_preview().map(|p| p.column()) {
+                    // Back to the column replaced in from the preview column
+                    let current_column = self.csv_table_state.current_column() as usize;
+                    if self
+                        .rows_view
+                        .original_column_index(current_column)
+                        .is_none()
+                    {
+                        let cols_offset = self.rows_view.column_index_from_original(column);
+                        self.csv_table_state.set_cols_offset(cols_offset as u64);
+                    }
+                    self.rows_view.set_replace_preview(None)?;
+                }
+            }
+            Control::NextColumnGroup if self.column_groups.is_empty() => {
+                self.user_error = Some(format!(
+                    "No column groups, add them to {}",
+                    column_groups::default_path()
+                        .map_or("column_groups".to_owned(), |p| p.display().to_string())
+                ));
+            }
+            Control::NextColumnGroup => {
+                // Back to all columns after the last group
+                let next = self.active_column_group().map_or(0, |i| i + 1);
+                match self.column_groups.get(next) {
+                    Some(group) => {
+                        let pattern = group.pattern.clone();
+                        self.filter_columns(&pattern)?;
+                    }
+                    None => {
+                        self.update_columns_filter(|rows_view| rows_view.reset_columns_filter())?
+                    }
+                }
+            }
+            Control::ToggleFindAllColumns => {
+                self.find_all_columns = !self.find_all_columns;
+                self.user_message = Some(if self.find_all_columns {
+                    "Finding in all columns".to_owned()
+                } else {
+                    "Finding in shown columns".to_owned()
+                });
+                self.refresh_finder();
+            }
+            Control::ToggleTruncateSide => {
+                let column = self.csv_table_state.current_column() as usize;
+                if let Some(header) = self.rows_view.headers().get(column) {
+                    self.csv_table_state.toggle_truncate_side(header);
+                }
+            }
+            Control::AutofitColumn => {
+                let column = self.csv_table_state.current_column() as usize;
+                if let Some(header) = self.rows_view.headers().get(column) {
+                    self.csv_table_state.autofit_column(column, header);
+                }
+            }
+            Control::AutofitColumns => {
+                let headers = self.rows_view.headers();
+                self.csv_table_state.autofit_columns(headers);
+            }
+            Control::ResetColumnWidths => {
+                self.csv_table_state.reset_column_widths();
+            }
+            Control::CycleAlignment => {
+                let column = self.csv_table_state.current_column() as usize;
+                if let Some(header) = self.rows_view.headers().get(column) {
+                    let alignment = self.csv_table_state.cycle_alignment(header);
+                    let side = match alignment {
+                        Alignment::Left => "left",
+                        Alignment::Right => "right",
+                        Alignment::Center => "center",
+                    };
+                    self.user_message = Some(format!("Column {} aligned {}", header, side));
+                }
+            }
+            Control::ResetColumnAlignments => {
+                self.csv_table_state.reset_column_alignments();
+            }
+            Control::CycleGutterMode => {
+                self.csv_table_state.cycle_gutter_mode();
+            }
+            Control::ResetDisplayOptions => {
+                self.set_display_options(DisplayOptions::default());
+                self.user_message = Some("Reset display options".to_owned());
+            }
+            Control::FreezePanes => {
+                self.csv_table_state.toggle_freeze_panes();
+            }
+            Control::ToggleWhitespace => {
+                self.csv_table_state.show_whitespace = !self.csv_table_state.show_whitespace;
+            }
+            Control::ToggleCompact => {
+                self.csv_table_state.compact = !self.csv_table_state.compact;
+            }
+            Control::ToggleGrid => {
+                self.csv_table_state.grid = !self.csv_table_state.grid;
+            }
+            Control::ToggleChanges => {
+                self.csv_table_state.show_changes = !self.csv_table_state.show_changes;
+            }
+            Control::ToggleRuler => {
+                self.csv_table_state.show_ruler = !self.csv_table_state.show_ruler;
+            }
+            Control::ToggleStatus => {
+                self.csv_table_state.hide_status = !self.csv_table_state.hide_status;
+            }
+            Control::ToggleCellPosition => {
+                self.csv_table_state.show_cell_position = !self.csv_table_state.show_cell_position;
+            }
+            Control::ToggleCellLength => {
+                self.csv_table_state.show_cell_length = !self.csv_table_state.show_cell_length;
+            }
+            Control::ToggleBooleans => {
+                self.csv_table_state.show_booleans = !self.csv_table_state.show_booleans;
+            }
+            Control::ToggleScientific => {
+                self.csv_table_state.show_scientific = !self.csv_table_state.show_scientific;
+            }
+            Control::ToggleDetail => {
+                self.csv_table_state.show_detail = !self.csv_table_state.show_detail;
+                self.csv_table_state.detail_find = None;
+            }
+            Control::ToggleExpandRow => {
+                self.csv_table_state.expanded_row = match self.csv_table_state.expanded_row {
+                    Some(_) => None,
+                    None => self.selected_row().map(|row| row.record_num),
+                };
+            }
+            Control::ToggleDetailRaw if self.csv_table_state.show_detail => {
+                self.csv_table_state.detail_raw = !self.csv_table_state.detail_raw;
+            }
+            Control::ToggleRawFields => {
+                let raw_fields = !self.rows_view.raw_fields();
+                self.rows_view.set_raw_fields(raw_fields)?;
+                self.user_message = Some(if raw_fields {
+                    "Showing fields as in the file".to_owned()
+                } else {
+                    "Showing parsed values".to_owned()
+                });
+            }
+            Control::EditCell => self.edit_cell(),
+            Control::SetCell(value) => {
+                self.csv_table_state.reset_buffer();
+                self.set_cell(value)?;
+            }
+            Control::Sort => {
+                let column = self.csv_table_state.current_column() as usize;
+                self.sort_by_column(column)?;
+            }
+            Control::MouseClick(x, y) => {
+                if let Some(col_index) = self.csv_table_state.header_column_at(*x, *y) {
+                    self.sort_by_column(col_index)?;
+                }
+            }
+            Control::CopySelection => match self.get_selected_rows_as_csv() {
+                Ok(Some((num_rows, text))) => match clipboard::copy(text.as_str()) {
+                    Ok(_) => {
+                        self.user_message = Some(format!("Copied {} rows", num_rows));
+                    }
+                    Err(e) => {
+                        self.user_error = Some(format!("Failed to copy: {}", e));
+                    }
+                },
+                Ok(None) => {}
+                Err(e) => {
+                    self.user_error = Some(format!("Failed to copy: {}", e));
+                }
+            },
+            Control::Pipe if self.pipe.is_none() => {
+                self.user_error = Some("Start csvlens with --exec to pipe to a command".to_owned());
+            }
+            Control::Pipe => match self.get_pipe_input()? {
+                Some(input) => self.pending_pipe = Some(input),
+                None => self.user_error = Some("No row selected".to_owned()),
+            },
+            Control::CopyColumnName => {
+                let column = self.csv_table_state.current_column() as usize;
+                if let Some(header) = self.rows_view.headers().get(column) {
+                    match clipboard::copy(header) {
+                        Ok(_) => {
+                            self.user_message = Some(format!("Copied column name: {}", header));
+                        }
+                        Err(e) => {
+                            self.user_error = Some(format!("Failed to copy: {}", e));
+                        }
+                    }
+                }
+            }
+            Control::CopyCell => {
+                let column = self.csv_table_state.current_column() as usize;
+                let value = self
+                    .rows_view
+                    .selected()
+                    .and_then(|i| self.rows_view.rows().get(i as usize))
+                    .map(|row| row.fields.get(column).cloned().unwrap_or_default());
+                match (self.rows_view.headers().get(column), value) {
+                    (Some(header), Some(value)) => {
+                        let text = self.cell_format.format(header, &value);
+                        match clipboard::copy(text.as_str()) {
+                            Ok(_) => self.user_message = Some(format!("Copied: {}", text)),
+                            Err(e) => self.user_error = Some(format!("Failed to copy: {}", e)),
+                        }
+                    }
+                    _ => self.user_error = Some("No row selected".to_owned()),
+                }
+            }
+            Control::CopyCommandLine => {
+                let command_line = self.command_line();
+                match clipboard::copy(command_line.as_str()) {
+                    Ok(_) => {
+                        self.user_message = Some(format!("Copied command: {}", command_line));
+                    }
+                    Err(e) => {
+                        self.user_error =
+                            Some(format!("Failed to copy: {} (command: {})", e, command_line));
+                    }
+                }
+            }
+            Control::MarkAll | Control::InvertMarks => match self.filtered_row_indices() {
+                Some(indices) => {
+                    if matches!(control, Control::MarkAll) {
+                        self.rows_view.mark_all(&indices);
+                    } else {
+                        self.rows_view.invert_marks(&indices);
+                    }
+                    self.user_message =
+                        Some(format!("{} rows marked", self.rows_view.marked().len()));
+                }
+                None => {
+                    self.user_error =
+                        Some("Not all rows are read yet, try again once done".to_owned());
+                }
+            },
+            Control::ExportMarked(path) => {
+                self.csv_table_state.reset_buffer();
+                if self.rows_view.marked().is_empty() {
+                    self.user_error = Some("No marked rows to export".to_owned());
+                } else {
+                    let result = self.rows_view.get_marked_rows().and_then(|rows| {
+                        export::write(
+                            path,
+                            self.rows_view.headers(),
+                            &rows,
+                            &self.export_options,
+                            self.shared_config.delimiter,
+                        )?;
+                        Ok(rows.len())
+                    });
+                    match result {
+                        Ok(num_rows) => {
+                            self.user_message =
+                                Some(format!("Exported {} rows to {}", num_rows, path));
+                        }
+                        Err(e) => {
+                            self.user_error = Some(format!("{:#}", e));
+                        }
+                    }
+                }
+            }
+            Control::BufferContent(buf) => {
+                self.csv_table_state
+                    .set_buffer(self.input_handler.mode(), buf.as_str());
+            }
+            Control::BufferReset => {
+                self.csv_table_state.reset_buffer();
+            }
+            Control::ClearFilter => {
+                self.csv_table_state.reset_buffer();
+                self.csv_table_state.header_find = None;
+                if self.finder.is_some() {
+                    self.finder = None;
+                    self.csv_table_state.finder_state = FinderState::FinderInactive;
+                    self.rows_view.reset_filter().unwrap();
+                }
+            }
+            Control::ClearColumnsFilter => {
+                self.csv_table_state.reset_buffer();
+                self.update_columns_filter(|rows_view| rows_view.reset_columns_filter())?;
+            }
+            Control::ReopenWithSuggestedDelimiter => match self.suggested_delimiter {
+                Some(d) => {
+                    self.reopen_with_delimiter(d)?;
+                    self.user_message = Some(format!(
+                        "Reopened with {:?} as the delimiter, as with -d {}",
+                        d as char,
+                        shell_quote(&delimiter_name(d))
+                    ));
+                }
+                None => self.user_error = Some("No other delimiter to suggest".to_owned()),
+            },
+            Control::ReopenAsFixedWidth => self.reopen_fixed_width_or_warn(None),
+            Control::MoveColumnStart(delta) => self.move_column_start(*delta),
+            Control::SetColumnStarts(s) => {
+                self.csv_table_state.reset_buffer();
+                match csv::parse_column_starts(s) {
+                    Ok(starts) => self.reopen_fixed_width_or_warn(Some(starts)),
+                    Err(e) => self.user_error = Some(e.to_string()),
+                }
             }
             _ => {}
         }
 
+        // Moving the selection keeps the current column in view, e.g. when it
+        // was centered before the window got narrower. Scrolling horizontally
+        // changes the current column instead.
+        let is_vertical = matches!(
+            control,
+            Control::ScrollUp
+                | Control::ScrollDown
+                | Control::ScrollTop
+                | Control::ScrollBottom
+                | Control::ScrollPageUp
+                | Control::ScrollPageDown
+                | Control::ScrollTo(_)
+                | Control::ScrollToPercent(_)
+                | Control::SelectUp
+                | Control::SelectDown
+        );
+        if let (true, Some(frame_width)) = (is_vertical, self.frame_width) {
+            if !self.csv_table_state.is_current_column_visible() {
+                self.csv_table_state.center_current_column(frame_width);
+            }
+        }
+
+        // show fixed-width text once converted again
+        let converted = self
+            .fixed_width_converter
+            .as_ref()
+            .and_then(|c| c.take_result());
+        if let Some(result) = converted {
+            let converter = self.fixed_width_converter.take().unwrap();
+            let shown =
+                result.and_then(|(file, starts)| self.show_fixed_width(&converter, file, starts));
+            match shown {
+                Ok(()) => self.user_message = Some(self.column_starts_message()),
+                Err(e) => self.user_error = Some(format!("{:#}", e)),
+            }
+        }
+
+        // show the rows in the new order once sorted
+        if let Some(sorted_rows) = self.sorter.as_ref().and_then(|s| s.sorted_rows()) {
+            let is_applied = self
+                .rows_view
+                .sorted()
+                .is_some_and(|sorted| Arc::ptr_eq(sorted, &sorted_rows));
+            if !is_applied {
+                self.rows_view.set_sorted(Some(sorted_rows))?;
+                self.rows_view.set_rows_from(0)?;
+                if self.rows_view.selected().is_some() {
+                    self.rows_view.select_top();
+                }
+            }
+        }
+
         if let Some(fdr) = self.finder.as_mut() {
             if !self.rows_view.is_filter() {
                 // scroll to first result once ready
@@ -285,13 +1495,29 @@ impl App {
                     // set row_hint to 0 so that this always scrolls to first result
                     fdr.set_row_hint(0);
                     if let Some(found_record) = fdr.next() {
+                        let row_index = found_record.row_index();
                         scroll_to_found_record(
                             found_record,
                             &mut self.rows_view,
                             &mut self.csv_table_state,
                         );
+                        if self.select_first_found && self.rows_view.selected().is_some() {
+                            let position = self
+                                .rows_view
+                                .rows()
+                                .iter()
+                                .position(|row| row.record_num - 1 == row_index);
+                            if let Some(i) = position {
+                                self.rows_view.set_selected(i as u64);
+                            }
+                        }
                     }
                     self.first_found_scrolled = true;
+                    self.select_first_found = false;
+                }
+                if self.select_first_found && fdr.done() && fdr.count() == 0 {
+                    self.user_error = Some(format!("No rows match '{}'", fdr.target()));
+                    self.select_first_found = false;
                 }
 
                 // reset cursor if out of view
@@ -301,7 +1527,12 @@ impl App {
                     }
                 }
 
-                fdr.set_row_hint(self.rows_view.rows_from() as usize);
+                // the first row shown, which is not at rows_from when sorted
+                let row_hint = match self.rows_view.rows().first() {
+                    Some(row) => row.record_num - 1,
+                    None => self.rows_view.rows_from() as usize,
+                };
+                fdr.set_row_hint(row_hint);
             } else {
                 self.rows_view.set_filter(fdr).unwrap();
             }
@@ -312,6 +1543,9 @@ impl App {
             self.csv_table_state
                 .debug_stats
                 .rows_view_elapsed(self.rows_view.elapsed());
+            self.csv_table_state
+                .debug_stats
+                .subset_elapsed(self.rows_view.subset_elapsed());
             if let Some(fdr) = &self.finder {
                 self.csv_table_state
                     .debug_stats
@@ -319,44 +1553,637 @@ impl App {
             } else {
                 self.csv_table_state.debug_stats.finder_elapsed(None);
             }
+            self.csv_table_state.debug_stats.rows_buffered(
+                self.rows_view.rows().len(),
+                self.rows_view.memory_footprint(),
+            );
         }
 
         // TODO: is this update too late?
         self.csv_table_state
             .set_rows_offset(self.rows_view.rows_from());
         self.csv_table_state.selected = self.rows_view.selected();
+        self.csv_table_state.selection_range = self.rows_view.selection_range();
+        // Collapsed again once another row is selected
+        if self.csv_table_state.expanded_row.is_some()
+            && self.csv_table_state.expanded_row != self.selected_row().map(|row| row.record_num)
+        {
+            self.csv_table_state.expanded_row = None;
+        }
 
         if let Some(n) = self.rows_view.get_total_line_numbers() {
-            self.csv_table_state.set_total_line_number(n);
+            self.csv_table_state.set_total_line_number(n, false);
         } else if let Some(n) = self.rows_view.get_total_line_numbers_approx() {
-            self.csv_table_state.set_total_line_number(n);
+            self.csv_table_state.set_total_line_number(n, true);
         }
+        self.csv_table_state.indexing = !self.rows_view.done_indexing();
         self.csv_table_state
             .set_total_cols(self.rows_view.headers().len());
+        self.csv_table_state.num_filtered_rows = self.rows_view.num_filtered_rows();
 
         if let Some(f) = &self.finder {
             // TODO: need to create a new finder every time?
             self.csv_table_state.finder_state = FinderState::from_finder(f, &self.rows_view);
         }
+        let group = self
+            .active_column_group()
+            .map(|i| self.column_groups[i].name.as_str());
         self.csv_table_state.filter_columns_state =
-            FilterColumnsState::from_rows_view(&self.rows_view);
+            FilterColumnsState::from_rows_view(&self.rows_view, group);
+        self.csv_table_state.count_state = self.counter.as_ref().map(CountState::from_finder);
+
+        // Only recompute when the selection or column might have changed since
+        // this reads the selected rows. Moving around doesn't change the values
+        // in the rows, so the sum is only read again if the range or column did.
+        if !matches!(control, Control::Nothing) {
+            let keeps_values = matches!(
+                control,
+                Control::ScrollUp
+                    | Control::ScrollDown
+                    | Control::ScrollLeft
+                    | Control::ScrollRight
+                    | Control::ScrollTop
+                    | Control::ScrollBottom
+                    | Control::ScrollPageUp
+                    | Control::ScrollPageDown
+                    | Control::ScrollTo(_)
+                    | Control::ScrollToPercent(_)
+                    | Control::SelectUp
+                    | Control::SelectDown
+                    | Control::CenterColumn
+            );
+            if !keeps_values {
+                self.selection_sum = None;
+            }
+            self.csv_table_state.selection_sum = self.get_selection_sum()?;
+        }
 
         self.csv_table_state.user_error = self.user_error.clone();
+        self.csv_table_state.user_message = self.user_message.clone();
+        self.csv_table_state.tab_names = if self.num_tabs() > 1 {
+            self.tab_names()
+        } else {
+            vec![]
+        };
+        self.csv_table_state.active_tab = self.active_tab;
+        self.csv_table_state.raw_fields = self.rows_view.raw_fields();
+        self.csv_table_state.sort_state = self.sorter.as_ref().map(|sorter| SortState {
+            column: self.rows_view.shown_column_index(sorter.column()),
+            name: self.rows_view.all_headers()[sorter.column()].clone(),
+            order: sorter.order(),
+            done: self.rows_view.sorted().is_some(),
+        });
+
+        let marked = self.rows_view.marked();
+        self.csv_table_state.num_marked = marked.len();
+        self.csv_table_state.num_skipped = self.rows_view.num_skipped();
+        self.csv_table_state.marked_rows = self
+            .rows_view
+            .rows()
+            .iter()
+            .filter(|row| marked.contains(&(row.record_num.saturating_sub(1) as u64)))
+            .map(|row| row.record_num)
+            .collect();
+
+        // shown rows are not in file order when sorted
+        let record_nums = self.rows_view.rows().iter().map(|row| row.record_num);
+        let (first, last) = (record_nums.clone().min(), record_nums.max());
+        self.csv_table_state.duplicate_rows = match (&self.finder, first, last) {
+            (Some(fdr), Some(first), Some(last)) if fdr.target().is_duplicates() => fdr
+                .found_between(first - 1, last - 1)
+                .iter()
+                .map(|i| i + 1)
+                .collect(),
+            _ => HashSet::new(),
+        };
 
         // self.csv_table_state.debug = format!("{:?}", self.rows_view.columns_filter());
 
         Ok(())
     }
 
+    /// Filter expression for rows where the current column is empty, i.e.
+    /// blank or only whitespace, or otherwise where it isn't
+    fn emptiness_expr(&self, empty: bool) -> Result<String> {
+        let column = self.csv_table_state.current_column() as usize;
+        let header = match self.rows_view.headers().get(column) {
+            Some(header) => header,
+            None => bail!("No column to filter on"),
+        };
+        if header.contains('"') {
+            bail!("Can't filter on a column with quotes in its name");
+        }
+        let op = if empty { "~" } else { "!~" };
+        // Quotes can't be escaped in expressions, but can be in the regex
+        let mut alternatives = vec!["\\s*".to_owned()];
+        for token in &self.shared_config.null_tokens {
+            alternatives.push(regex::escape(token).replace('"', "\\x22"));
+        }
+        let regex = match alternatives.len() {
+            1 => "^\\s*$".to_owned(),
+            _ => format!("^({})$", alternatives.join("|")),
+        };
+        Ok(format!(":\"{}\" {} \"{}\"", header, op, regex))
+    }
+
+    fn selected_row(&self) -> Option<&csv::Row> {
+        let i = self.rows_view.selected()?;
+        self.rows_view.rows().get(i as usize)
+    }
+
+    /// The rows in the extended selection range along with the header as
+    /// delimited lines, otherwise the current cell as it is
+    fn get_pipe_input(&mut self) -> Result<Option<String>> {
+        if let Some(rows) = self.get_selection_range_rows()? {
+            let header = csv::Row {
+                record_num: 0,
+                fields: self.rows_view.headers().clone(),
+            };
+            let rows: Vec<csv::Row> = std::iter::once(header).chain(rows).collect();
+            let text = export::to_csv_string(&rows, self.shared_config.delimiter)?;
+            return Ok(Some(text));
+        }
+        let column = self.csv_table_state.current_column() as usize;
+        Ok(self
+            .rows_view
+            .selected()
+            .and_then(|i| self.rows_view.rows().get(i as usize))
+            .and_then(|row| row.fields.get(column))
+            .cloned())
+    }
+
+    /// Rows in the extended selection range if any
+    fn get_selection_range_rows(&mut self) -> Result<Option<Vec<csv::Row>>> {
+        if let Some((first, last)) = self.rows_view.selection_range() {
+            let indices = self.row_indices_between(first, last);
+            Ok(Some(self.rows_view.get_rows_for_indices(&indices)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Row indices at the offsets (inclusive) among the rows shown, i.e. the
+    /// filtered rows if filtering. Offsets past the last row are left out.
+    fn row_indices_between(&self, first: u64, last: u64) -> Vec<u64> {
+        if self.rows_view.is_filter() {
+            match &self.finder {
+                Some(fdr) if (first as usize) < fdr.count() => {
+                    fdr.get_subset_found(first as usize, (last - first + 1) as usize)
+                }
+                _ => vec![],
+            }
+        } else {
+            self.rows_view.row_indices_between(first, last)
+        }
+    }
+
+    /// Look for the value of the selected row in the current column from
+    /// the selected row, or from the first row for the rows with the same value
+    fn new_value_scan(&self, kind: ValueScanKind) -> Option<ValueScan> {
+        let column = self.csv_table_state.current_column() as usize;
+        let selected = self.rows_view.selected()?;
+        let offset = match kind {
+            ValueScanKind::NextChange => self.rows_view.selected_offset()? + 1,
+            ValueScanKind::PrevChange => self.rows_view.selected_offset()?,
+            ValueScanKind::FirstSame | ValueScanKind::LastSame => 0,
+        };
+        let value = self
+            .rows_view
+            .rows()
+            .get(selected as usize)?
+            .fields
+            .get(column);
+        Some(ValueScan {
+            kind,
+            column,
+            value: value.cloned(),
+            offset,
+            rows_read: 0,
+            found: None,
+            count: 0,
+        })
+    }
+
+    /// Read rows for the value scan for a while, returning the control going
+    /// to the row found once done and showing how many rows were read if not
+    fn continue_value_scan(&mut self, message: &mut Option<String>) -> Result<Control> {
+        let mut scan = match self.value_scan.take() {
+            Some(scan) => scan,
+            None => return Ok(Control::Nothing),
+        };
+        let start = Instant::now();
+        while !self.read_value_scan_chunk(&mut scan)? {
+            if start.elapsed() >= VALUE_SCAN_STEP {
+                self.user_message = Some(format!(
+                    "Looking for the value in the column ({} rows read)",
+                    scan.rows_read
+                ));
+                self.value_scan = Some(scan);
+                return Ok(Control::Nothing);
+            }
+        }
+        let control = match (scan.kind, scan.found) {
+            (ValueScanKind::NextChange | ValueScanKind::PrevChange, Some((offset, _))) => {
+                Control::ScrollTo(offset as usize + 1)
+            }
+            (ValueScanKind::NextChange | ValueScanKind::PrevChange, None) => {
+                self.user_message = Some("No other value in the column".to_owned());
+                Control::Nothing
+            }
+            (kind, Some((first, last))) => {
+                *message = Some(format!(
+                    "{} rows with the same value in the column",
+                    scan.count
+                ));
+                let offset = if matches!(kind, ValueScanKind::FirstSame) {
+                    first
+                } else {
+                    last
+                };
+                Control::ScrollTo(offset as usize + 1)
+            }
+            (_, None) => {
+                self.user_message = None;
+                Control::Nothing
+            }
+        };
+        Ok(control)
+    }
+
+    /// Read the next chunk of rows shown for the value scan, returning whether
+    /// it is done
+    fn read_value_scan_chunk(&mut self, scan: &mut ValueScan) -> Result<bool> {
+        const CHUNK_SIZE: u64 = 1000;
+        let backward = matches!(scan.kind, ValueScanKind::PrevChange);
+        let (first, last) = if !backward {
+            (scan.offset, scan.offset + CHUNK_SIZE - 1)
+        } else if scan.offset > 0 {
+            (scan.offset.saturating_sub(CHUNK_SIZE), scan.offset - 1)
+        } else {
+            return Ok(true);
+        };
+        let indices = self.row_indices_between(first, last);
+        let rows = self.rows_view.get_rows_for_indices(&indices)?;
+        scan.rows_read += rows.len() as u64;
+        let same: Vec<bool> = rows
+            .iter()
+            .map(|row| row.fields.get(scan.column) == scan.value.as_ref())
+            .collect();
+        match scan.kind {
+            ValueScanKind::NextChange => {
+                if let Some(i) = same.iter().position(|&same| !same) {
+                    scan.found = Some((first + i as u64, first + i as u64));
+                    return Ok(true);
+                }
+            }
+            ValueScanKind::PrevChange => {
+                if let Some(i) = same.iter().rposition(|&same| !same) {
+                    scan.found = Some((first + i as u64, first + i as u64));
+                    return Ok(true);
+                }
+            }
+            ValueScanKind::FirstSame | ValueScanKind::LastSame => {
+                for (i, _) in same.iter().enumerate().filter(|(_, &same)| same) {
+                    let offset = first + i as u64;
+                    scan.found = match scan.found {
+                        Some((first_found, _)) => Some((first_found, offset)),
+                        None => Some((offset, offset)),
+                    };
+                    scan.count += 1;
+                }
+            }
+        }
+        if backward {
+            scan.offset = first;
+            Ok(first == 0)
+        } else {
+            scan.offset = last + 1;
+            Ok((rows.len() as u64) < CHUNK_SIZE)
+        }
+    }
+
+    /// Start editing the current column of the selected row
+    fn edit_cell(&mut self) {
+        let error = if !self.editable {
+            Some("Start csvlens with --edit to edit cells")
+        } else if self.csv_table_state.filename() != Some(self.shared_config.filename()) {
+            Some("Only files read as they are can be edited, not stdin or converted input")
+        } else if self.rows_view.raw_fields() {
+            Some("Show parsed values with Q to edit cells")
+        } else if self.is_replace_preview_current() {
+            Some("Can't edit the replacement preview")
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            self.user_error = Some(error.to_owned());
+            return;
+        }
+        let column = self.csv_table_state.current_column() as usize;
+        let row = match self.rows_view.selected() {
+            Some(i) => self.rows_view.rows().get(i as usize),
+            None => None,
+        };
+        if let Some(row) = row {
+            let value = row.fields.get(column).cloned().unwrap_or_default();
+            self.input_handler.edit(&value);
+            self.csv_table_state
+                .set_buffer(InputMode::EditCell, value.as_str());
+        }
+    }
+
+    fn is_replace_preview_current(&self) -> bool {
+        let current_column = self.csv_table_state.current_column() as usize;
+        self.rows_view.is_replace_preview_column(current_column)
+    }
+
+    /// Write the edited value of the current cell to the file
+    fn set_cell(&mut self, value: &str) -> Result<()> {
+        if self.is_replace_preview_current() {
+            self.user_error = Some("Can't edit the replacement preview".to_owned());
+            return Ok(());
+        }
+        let current_column = self.csv_table_state.current_column() as usize;
+        let record_num = match self.rows_view.selected() {
+            Some(i) => self
+                .rows_view
+                .rows()
+                .get(i as usize)
+                .map(|row| row.record_num),
+            None => None,
+        };
+        let column = self.rows_view.original_column_index(current_column);
+        let (record_num, column) = match (record_num, column) {
+            (Some(record_num), Some(column)) => (record_num, column),
+            _ => return Ok(()),
+        };
+        if let Err(e) = edit::write_field(&self.shared_config, record_num, column, value) {
+            self.user_error = Some(format!("Failed to save: {:#}", e));
+            return Ok(());
+        }
+        // Rows stay where they are even if they would now be sorted or
+        // filtered differently, so that the edited row doesn't jump away
+        let reader = csv::CsvLensReader::new(self.shared_config.clone())?;
+        self.rows_view.reload(reader)?;
+        self.refresh_finder();
+        if let Some(target) = self.counter.as_ref().map(|c| c.target()) {
+            self.counter = Some(self.new_finder(target, true));
+        }
+        let header = &self.rows_view.all_headers()[column];
+        self.user_message = Some(format!("Saved {} of row {}", header, record_num));
+        Ok(())
+    }
+
+    /// Sort by the shown column, or sort the other way if already sorted by it
+    /// in ascending order, or else go back to the file order
+    fn sort_by_column(&mut self, col_index: usize) -> Result<()> {
+        if self.rows_view.is_filter() {
+            self.user_error = Some("Clear the rows filter to sort".to_owned());
+            return Ok(());
+        }
+        let column = match self.rows_view.original_column_index(col_index) {
+            Some(column) => column,
+            None => return Ok(()),
+        };
+        let order = match &self.sorter {
+            Some(sorter) if sorter.column() == column => match sorter.order() {
+                sort::SortOrder::Ascending => Some(sort::SortOrder::Descending),
+                sort::SortOrder::Descending => None,
+            },
+            _ => Some(sort::SortOrder::Ascending),
+        };
+        self.sorter =
+            order.map(|order| sort::Sorter::new(self.shared_config.clone(), column, order));
+        if self.sorter.is_none() {
+            self.rows_view.set_sorted(None)?;
+            self.rows_view.set_rows_from(0)?;
+            if self.rows_view.selected().is_some() {
+                self.rows_view.select_top();
+            }
+        }
+        // keep the column in view, e.g. when sorting by clicking
+        self.csv_table_state.set_cols_offset(col_index as u64);
+        Ok(())
+    }
+
+    /// Scroll to the next or previous column whose name matches
+    fn find_header(&mut self, forward: bool) {
+        let current_column = self.csv_table_state.current_column() as usize;
+        let headers = self.rows_view.headers();
+        if let Some(header_find) = self.csv_table_state.header_find.as_mut() {
+            if let Some(column) = header_find.step(headers, current_column, forward) {
+                self.csv_table_state.set_cols_offset(column as u64);
+            }
+        }
+    }
+
+    fn filter_columns(&mut self, s: &str) -> Result<()> {
+        match view::ColumnsFilter::parse(s, self.rows_view.all_headers()) {
+            Ok(columns_filter) => {
+                self.update_columns_filter(|rows_view| rows_view.set_columns_filter(columns_filter))
+            }
+            Err(e) => {
+                self.update_columns_filter(|rows_view| rows_view.reset_columns_filter())?;
+                self.user_error = Some(e.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// Index of the column group whose regex is the columns filter
+    fn active_column_group(&self) -> Option<usize> {
+        let pattern = self.rows_view.columns_filter()?.pattern();
+        self.column_groups.iter().position(|g| g.pattern == pattern)
+    }
+
+    /// Change the columns filter, scrolling to the same column as before if it
+    /// is still shown, or else the one after it
+    fn update_columns_filter<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut view::RowsView) -> Result<()>,
+    {
+        let current_column = self.csv_table_state.current_column() as usize;
+        let column = self.rows_view.original_column_index(current_column);
+        f(&mut self.rows_view)?;
+        let new_cols_offset = column.map_or(0, |c| self.rows_view.column_index_from_original(c));
+        self.csv_table_state.set_cols_offset(new_cols_offset as u64);
+        self.refresh_finder();
+        Ok(())
+    }
+
+    /// Finder for the target. Finding only looks at the shown columns unless
+    /// finding in all columns, while filtering rows always looks at all
+    /// columns so that changing the columns filter doesn't change the rows.
+    fn new_finder(&self, target: find::Target, is_filter: bool) -> find::Finder {
+        let columns = match self.rows_view.columns_filter() {
+            Some(columns_filter) if !is_filter && !self.find_all_columns => {
+                Some(columns_filter.indices().clone())
+            }
+            _ => None,
+        };
+        find::Finder::new(self.shared_config.clone(), target, columns).unwrap()
+    }
+
+    /// Find again after the columns to look at changed, staying at the same
+    /// rows. Filters are left as is since they look at all columns.
+    fn refresh_finder(&mut self) {
+        if self.rows_view.is_filter() {
+            return;
+        }
+        if let Some(target) = self.finder.as_ref().map(|fdr| fdr.target()) {
+            self.finder = Some(self.new_finder(target, false));
+        }
+    }
+
+    /// Selected rows (the extended selection range if any, otherwise the
+    /// selected row) formatted as delimited lines
+    fn get_selected_rows_as_csv(&mut self) -> Result<Option<(usize, String)>> {
+        let rows = if let Some(rows) = self.get_selection_range_rows()? {
+            rows
+        } else if let Some(row) = self
+            .rows_view
+            .selected()
+            .and_then(|i| self.rows_view.rows().get(i as usize))
+        {
+            vec![row.clone()]
+        } else {
+            return Ok(None);
+        };
+        let text = export::to_csv_string(&rows, self.shared_config.delimiter)?;
+        Ok(Some((rows.len(), text)))
+    }
+
+    /// Command line to open the file showing the same rows and columns, with
+    /// the selected row at the top. Covers how the file was read, the row
+    /// numbering and the filters, but not display options, e.g. --grid.
+    fn command_line(&self) -> String {
+        let mut args = vec!["csvlens".to_owned()];
+        if let Some(filename) = self.csv_table_state.filename() {
+            args.push(filename.to_owned());
+        }
+        args.extend(self.source_args.iter().cloned());
+        let config = &self.shared_config;
+        if let Some(fixed_width) = &config.fixed_width {
+            let starts: Vec<String> = fixed_width.starts.iter().map(|s| s.to_string()).collect();
+            args.push("--column-starts".to_owned());
+            args.push(starts.join(","));
+        }
+        if config.delimiter != b',' {
+            args.push("-d".to_owned());
+            args.push(delimiter_name(config.delimiter));
+        }
+        if !config.quoting {
+            args.push("--raw".to_owned());
+        }
+        if config.quote != b'"' {
+            args.push("--quote".to_owned());
+            args.push((config.quote as char).to_string());
+        }
+        if let Some(escape) = config.escape {
+            args.push("--escape".to_owned());
+            args.push((escape as char).to_string());
+        }
+        if self.csv_table_state.row_base != 1 {
+            args.push("--row-base".to_owned());
+            args.push(self.csv_table_state.row_base.to_string());
+        }
+        if self.csv_table_state.count_header {
+            args.push("--count-header".to_owned());
+        }
+        if let Some(columns_filter) = self.rows_view.columns_filter() {
+            if !columns_filter.disabled_because_no_match() {
+                args.push("--columns".to_owned());
+                args.push(columns_filter.pattern());
+            }
+        }
+        if let (true, Some(fdr)) = (self.rows_view.is_filter(), &self.finder) {
+            args.push("--filter".to_owned());
+            args.push(fdr.target().pattern());
+        }
+        let position = self
+            .rows_view
+            .selected_offset()
+            .unwrap_or_else(|| self.rows_view.rows_from());
+        // there is no option to sort, so the position would be a different row
+        if position > 0 && self.rows_view.sorted().is_none() {
+            args.push("--goto".to_owned());
+            args.push((position + self.first_row_number()).to_string());
+        }
+        args.iter()
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Number of the first row shown, counting the rows matching the filter
+    /// if filtering or all rows of the file otherwise
+    fn first_row_number(&self) -> u64 {
+        if self.rows_view.is_filter() {
+            self.csv_table_state.row_base
+        } else {
+            self.csv_table_state.first_row_number()
+        }
+    }
+
+    /// Position counting from 1 among the rows shown of the row with the number
+    fn position_of_row_number(&self, n: usize) -> usize {
+        (n as u64).saturating_sub(self.first_row_number()) as usize + 1
+    }
+
+    /// Sum and number of numeric values in the current column within the
+    /// extended selection range, as last read if neither changed
+    fn get_selection_sum(&mut self) -> Result<Option<(f64, usize)>> {
+        let column = self.csv_table_state.current_column() as usize;
+        let range = match self.rows_view.selection_range() {
+            Some(range) => range,
+            None => return Ok(None),
+        };
+        match &self.selection_sum {
+            Some(last) if last.range == range && last.column == column => return Ok(last.sum),
+            _ => {}
+        }
+        let sum = self.read_selection_sum(column)?;
+        self.selection_sum = Some(SelectionSum { range, column, sum });
+        Ok(sum)
+    }
+
+    fn read_selection_sum(&mut self, column: usize) -> Result<Option<(f64, usize)>> {
+        let rows = match self.get_selection_range_rows()? {
+            Some(rows) => rows,
+            None => return Ok(None),
+        };
+        let values: Vec<f64> = rows
+            .iter()
+            .filter_map(|row| row.fields.get(column))
+            .filter(|field| !self.shared_config.is_null(field))
+            .filter_map(|field| stats::parse_number(field))
+            .filter(|x| x.is_finite())
+            .collect();
+        if values.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((values.iter().sum(), values.len())))
+    }
+
     fn render_frame<B: Backend>(&mut self, f: &mut Frame<B>) {
         let size = f.size();
 
         // TODO: check type of num_rows too big?
-        let frame_size_adjusted_num_rows =
-            size.height.saturating_sub(self.num_rows_not_visible as u16) as u64;
-        self.rows_view
-            .set_num_rows(frame_size_adjusted_num_rows)
-            .unwrap();
+        // headers may take more than one line
+        let num_header_lines = self.csv_table_state.header_lines();
+        // Another row fits without the status bar
+        let hidden_status_lines = 2 - self.csv_table_state.status_height();
+        let frame_size_adjusted_num_rows = size
+            .height
+            .saturating_sub(self.num_rows_not_visible + num_header_lines - 1 - hidden_status_lines)
+            as u64;
+        // Fewer rows fit when the expanded row takes more than one line
+        let expanded_extra_lines = self.csv_table_state.expanded_extra_lines() as u64;
+        let num_rows = max(
+            frame_size_adjusted_num_rows.saturating_sub(expanded_extra_lines),
+            min(frame_size_adjusted_num_rows, 1),
+        );
+        self.rows_view.set_num_rows(num_rows).unwrap();
         self.frame_width = Some(size.width);
 
         let rows = self.rows_view.rows();
@@ -381,24 +2208,40 @@ mod tests {
     use std::thread;
 
     use super::*;
+    use crate::ui::GutterMode;
     use tui::backend::TestBackend;
-    use tui::buffer::Buffer;
+    use tui::buffer::{Buffer, Cell};
+    use tui::style::{Color, Modifier};
+
+    impl App {
+        fn new(
+            filename: &str,
+            delimiter: Option<u8>,
+            original_filename: Option<String>,
+            show_stats: bool,
+        ) -> Result<Self> {
+            let mut config = csv::CsvConfig::new(filename);
+            if let Some(d) = delimiter {
+                config.delimiter = d;
+            }
+            Self::with_config(config, original_filename, show_stats)
+        }
+    }
 
     fn to_lines(buf: &Buffer) -> Vec<String> {
         let mut symbols: String = "".to_owned();
-        let area = buf.area().clone();
+        let area = *buf.area();
         for y in 0..area.bottom() {
             for x in 0..area.right() {
                 let symbol = buf.get(x, y).symbol.clone();
                 symbols.push_str(&symbol);
             }
             if y != area.bottom() - 1 {
-                symbols.push_str("\n");
+                symbols.push('\n');
             }
         }
         let res: Vec<&str> = symbols.split("\n").collect();
-        let res = res.into_iter().map(|s| s.to_string()).collect();
-        res
+        res.into_iter().map(|s| s.to_string()).collect()
     }
 
     fn step_and_draw<B: Backend>(app: &mut App, terminal: &mut Terminal<B>, control: Control) {
@@ -412,6 +2255,30 @@ mod tests {
         terminal.draw(|f| app.render_frame(f)).unwrap();
     }
 
+    /// Step with the control looking for a value and draw once found
+    fn step_and_scan<B: Backend>(app: &mut App, terminal: &mut Terminal<B>, control: Control) {
+        step_and_draw(app, terminal, control);
+        step_until(app, terminal, |app| app.value_scan.is_none());
+    }
+
+    /// Step and draw until the condition holds, e.g. once rows are filtered,
+    /// failing if it takes too long
+    fn step_until<B: Backend, F: Fn(&App) -> bool>(
+        app: &mut App,
+        terminal: &mut Terminal<B>,
+        condition: F,
+    ) {
+        let start = Instant::now();
+        loop {
+            step_and_draw(app, terminal, Control::Nothing);
+            if condition(app) {
+                return;
+            }
+            assert!(start.elapsed() < time::Duration::from_secs(10), "Timed out");
+            thread::sleep(time::Duration::from_millis(10));
+        }
+    }
+
     #[test]
     fn test_simple() {
         let mut app = App::new("tests/data/simple.csv", None, None, false).unwrap();
@@ -432,7 +2299,7 @@ mod tests {
             "──────────────────────────────",
             "      a     b                 ",
             "───┬──────────────┬───────────",
-            "4  │  A4    B4    │           ",
+            "4  │  A4    B4    │          ▐",
             "5  │  A5    B5    │           ",
             "6  │  A6    B6    │           ",
             "7  │  A7    B7    │           ",
@@ -445,6 +2312,297 @@ mod tests {
         assert_eq!(lines, expected);
     }
 
+    #[test]
+    fn test_no_select() {
+        let mut app = App::new("tests/data/simple.csv", None, None, false).unwrap();
+        app.disable_selection();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(30, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        // Scrolling moves the rows right away instead of the selected row
+        for _ in 0..3 {
+            step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        }
+        let expected = vec![
+            "──────────────────────────────",
+            "      a     b                 ",
+            "───┬──────────────┬───────────",
+            "4  │  A4    B4    │          ▐",
+            "5  │  A5    B5    │           ",
+            "6  │  A6    B6    │           ",
+            "───┴──────────────┴───────────",
+            "stdin [Row 4/5000, Col 1/2]   ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+        assert_eq!(app.rows_view.selected(), None);
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollBottom);
+        step_and_draw(&mut app, &mut terminal, Control::Sort);
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        assert_eq!(app.rows_view.selected(), None);
+    }
+
+    #[test]
+    fn test_scrolloff() {
+        let mut app = App::new("tests/data/simple.csv", None, None, false).unwrap();
+        app.set_scrolloff(2);
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(30, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        for _ in 0..3 {
+            step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        }
+        let expected = vec![
+            "──────────────────────────────",
+            "      a     b                 ",
+            "───┬──────────────┬───────────",
+            "2  │  A2    B2    │          ▐",
+            "3  │  A3    B3    │           ",
+            "4  │  A4    B4    │           ",
+            "5  │  A5    B5    │           ",
+            "6  │  A6    B6    │           ",
+            "───┴──────────────┴───────────",
+            "stdin [Row 4/5000, Col 1/2]   ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        // The margin is kept above the row gone to and when scrolling up
+        step_and_draw(&mut app, &mut terminal, Control::ScrollTo(100));
+        step_and_draw(&mut app, &mut terminal, Control::ScrollUp);
+        let expected = vec![
+            "──────────────────────────────",
+            "        a       b             ",
+            "─────┬──────────────────┬─────",
+            "97   │  A97     B97     │    ▐",
+            "98   │  A98     B98     │     ",
+            "99   │  A99     B99     │     ",
+            "100  │  A100    B100    │     ",
+            "101  │  A101    B101    │     ",
+            "─────┴──────────────────┴─────",
+            "stdin [Row 99/5000, Col 1/2]  ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        // So is it below the top row selected after a page down
+        step_and_draw(&mut app, &mut terminal, Control::ScrollPageDown);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].starts_with("104  │  A104"), "{}", lines[5]);
+        assert!(lines[9].starts_with("stdin [Row 104/5000"), "{}", lines[9]);
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollBottom);
+        let expected = vec![
+            "──────────────────────────────",
+            "         a        b           ",
+            "──────┬────────────────────┬──",
+            "4998  │  A4998    B4998    │  ",
+            "4999  │  A4999    B4999    │  ",
+            "5000  │  A5000    B5000    │  ",
+            "      │                    │  ",
+            "      │                    │ ▐",
+            "──────┴────────────────────┴──",
+            "stdin [Row 5000/5000, Col 1/2]",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_page_overlap() {
+        let mut app = App::new("tests/data/simple.csv", None, None, false).unwrap();
+        app.set_page_overlap(1);
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        // The last row of the previous page is shown first
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollPageDown);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("5  │  A5    B5"));
+        assert!(lines[7].starts_with("9  │  A9    B9"));
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollPageDown);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollPageUp);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("5  │  A5    B5"));
+    }
+
+    #[test]
+    fn test_goto_near_bottom() {
+        let mut app = App::new("tests/data/simple.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        // The last page can't start at the row, but the row is still selected
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollTo(4998));
+        let selected = app.rows_view.selected().unwrap() as usize;
+        assert_eq!(app.rows_view.rows()[selected].fields[0], "A4998");
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("4996  │  A4996"), "{}", lines[3]);
+    }
+
+    #[test]
+    fn test_header_always_drawn() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(30, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        let controls = vec![
+            Control::Nothing,
+            Control::ScrollTo(50),
+            Control::ScrollPageDown,
+            Control::ScrollBottom,
+        ];
+        for control in controls {
+            step_and_draw(&mut app, &mut terminal, control);
+            let lines = to_lines(&terminal.backend().buffer().clone());
+            assert!(lines[1].trim_start().starts_with("LatD    LatM"));
+        }
+
+        // Header scrolls horizontally together with the rows
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[1].trim_start().starts_with("LatM    LatS"));
+    }
+
+    #[test]
+    fn test_wrap_columns() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollLeft);
+        assert_eq!(app.csv_table_state.cols_offset, 0);
+
+        app.set_wrap_columns(true);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollLeft);
+        let expected = vec![
+            "──────────────────────────────────────────────────",
+            "      State                                       ",
+            "───┬───────────┬──────────────────────────────────",
+            "1  │  OH       │                                 ▐",
+            "───┴───────────┴──────────────────────────────━━━━",
+            "Wrapped around to the last column                 ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[1].starts_with("      LatD    LatM    LatS"));
+        assert_eq!(lines[5].trim_end(), "Wrapped around to the first column");
+    }
+
+    #[test]
+    fn test_approx_total() {
+        let mut app = App::new("tests/data/simple.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].starts_with("stdin [Row 1/5000, Col 1/2]"));
+
+        // As if the file were still being parsed
+        app.csv_table_state.set_total_line_number(4990, true);
+        app.csv_table_state.indexing = true;
+        terminal.draw(|f| app.render_frame(f)).unwrap();
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].starts_with("stdin [Row 1/~4990, Col 1/2] [Indexing…]"));
+    }
+
+    #[test]
+    fn test_center_column() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        for _ in 0..4 {
+            step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        }
+        step_and_draw(&mut app, &mut terminal, Control::CenterColumn);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "      LatM    LatS    NS    LonD    LonM    LonS    EW    … ",
+            "───┬────────────────────────────────────────────────────────",
+            "1  │  5       59      N     80      39      0       W     …▐",
+            "───┴──────━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━──────",
+            "stdin [Row 1/128, Col 5/10]                                 ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Copying the name, sorting etc. still act on the centered column
+        step_and_draw(&mut app, &mut terminal, Control::Sort);
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].contains("[Sorted by LonD ▲]"));
+    }
+
+    #[test]
+    fn test_current_column_kept_visible() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 7);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        for _ in 0..4 {
+            step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        }
+        step_and_draw(&mut app, &mut terminal, Control::CenterColumn);
+
+        // LonD is no longer shown in a narrower window until moving down
+        terminal.backend_mut().resize(30, 7);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(!lines[1].contains("LonD"));
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        let expected = vec![
+            "──────────────────────────────",
+            "      NS    LonD    LonM    … ",
+            "───┬──────────────────────────",
+            "1  │  N     80      39      …▐",
+            "2  │  N     97      23      … ",
+            "───┴────────━━━━━━━━━━────────",
+            "stdin [Row 2/128, Col 5/10]   ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Scrolling horizontally is not undone when moving down
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[1].trim_start().starts_with("LonM"), "{}", lines[1]);
+    }
+
     #[test]
     fn test_scroll_horizontal() {
         let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
@@ -458,12 +2616,12 @@ mod tests {
             "──────────────────────────────",
             "      LatD    LatM    LatS    ",
             "───┬──────────────────────────",
-            "1  │  41      5       59      ",
+            "1  │  41      5       59     ▐",
             "2  │  42      52      48      ",
             "3  │  46      35      59      ",
             "4  │  42      16      12      ",
             "5  │  43      37      48      ",
-            "───┴──────────────────────────",
+            "───┴━━━━━━━━━━────────────────",
             "stdin [Row 1/128, Col 1/10]   ",
         ];
         let actual_buffer = terminal.backend().buffer().clone();
@@ -475,12 +2633,12 @@ mod tests {
             "──────────────────────────────",
             "      NS    LonD    LonM    … ",
             "───┬──────────────────────────",
-            "1  │  N     80      39      … ",
+            "1  │  N     80      39      …▐",
             "2  │  N     97      23      … ",
             "3  │  N     120     30      … ",
             "4  │  N     71      48      … ",
             "5  │  N     89      46      … ",
-            "───┴──────────────────────────",
+            "───┴────────━━━━━━━━━━────────",
             "stdin [Row 1/128, Col 4/10]   ",
         ];
         let actual_buffer = terminal.backend().buffer().clone();
@@ -492,12 +2650,12 @@ mod tests {
             "──────────────────────────────",
             "      LatD    LatM    LatS    ",
             "───┬──────────────────────────",
-            "1  │  41      5       59      ",
+            "1  │  41      5       59     ▐",
             "2  │  42      52      48      ",
             "3  │  46      35      59      ",
             "4  │  42      16      12      ",
             "5  │  43      37      48      ",
-            "───┴──────────────────────────",
+            "───┴━━━━━━━━━━────────────────",
             "stdin [Row 1/128, Col 1/10]   ",
         ];
         let actual_buffer = terminal.backend().buffer().clone();
@@ -522,7 +2680,7 @@ mod tests {
             "────────────────────────────────────────────────────────────────────────────────",
             "      LonD    LonM    LonS    City                                              ",
             "───┬─────────────────────────────────────────────┬──────────────────────────────",
-            "1  │  80      39      0       Youngstown         │                              ",
+            "1  │  80      39      0       Youngstown         │                             ▐",
             "2  │  97      23      23      Yankton            │                              ",
             "3  │  120     30      36      Yakima             │                              ",
             "4  │  71      48      0       Worcester          │                              ",
@@ -535,6 +2693,2471 @@ mod tests {
         assert_eq!(lines, expected);
     }
 
+    #[test]
+    fn test_filter_columns_keeps_scroll_position() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        for _ in 0..4 {
+            step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        }
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[1].starts_with("      LonD    LonM    LonS    EW    City"));
+
+        // The column scrolled to is still the first one shown
+        step_and_draw(&mut app, &mut terminal, Control::FilterColumns("^L".into()));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[1].starts_with("      LonD    LonM    LonS    "));
+        assert!(lines[5].starts_with("stdin [Row 1/128, Col 4/6]"));
+
+        step_and_draw(&mut app, &mut terminal, Control::ClearColumnsFilter);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[1].starts_with("      LonD    LonM    LonS    EW    City"));
+
+        // Or the next one if it is filtered out
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("^Lat|^C".into()),
+        );
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[1].starts_with("      City    "));
+        assert!(lines[5].starts_with("stdin [Row 1/128, Col 4/4]"));
+    }
+
+    #[test]
+    fn test_find_in_shown_columns() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("City".into()),
+        );
+        step_and_draw(&mut app, &mut terminal, Control::Find("^OH$".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].starts_with("stdin [Row 1/128, Col 1/1] [Find \"^OH$\": Not found]"));
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleFindAllColumns);
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[5].trim_end(), "Finding in all columns");
+        step_and_draw(&mut app, &mut terminal, Control::ScrollToNextFound);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].starts_with("stdin [Row 50/128, Col 1/1] [Find \"^OH$\": 2/6]"));
+
+        // Matches within the shown columns are highlighted
+        step_and_draw(&mut app, &mut terminal, Control::ToggleFindAllColumns);
+        step_and_draw(&mut app, &mut terminal, Control::ClearColumnsFilter);
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("State".into()),
+        );
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::ScrollToNextFound);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].starts_with("stdin [Row 50/128, Col 1/1] [Find \"^OH$\": 2/6]"));
+        let x = lines[3].chars().position(|c| c == 'O').unwrap() as u16;
+        let cell = terminal.backend().buffer().get(x, 3).clone();
+        assert_eq!(cell.bg, Color::LightYellow);
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(80, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Find(":dup(Nope)".into()));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[5].trim_end(), "Unknown column: Nope");
+
+        // Youngstown, OH is a duplicate by State but Yankton, SD is not
+        step_and_draw(&mut app, &mut terminal, Control::Find(":dup(State)".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5]
+            .starts_with("stdin [Row 1/128, Col 1/10] [Find \"duplicates of State\": 1/112]"));
+        let duplicate = terminal.backend().buffer().get(0, 3).clone();
+        let unique = terminal.backend().buffer().get(0, 4).clone();
+        assert_eq!(duplicate.fg, Color::Rgb(200, 120, 0));
+        assert_eq!(unique.fg, Color::Rgb(64, 64, 64));
+
+        step_and_draw(&mut app, &mut terminal, Control::Filter(":dup()".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].starts_with(
+            "stdin [Row -/128, Col 1/10] [0/128 rows (filtered)] [Filter \"duplicate rows\""
+        ));
+    }
+
+    #[test]
+    fn test_command_line() {
+        let filename = "tests/data/cities.csv";
+        let mut app = App::new(filename, None, Some(filename.to_owned()), false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        assert_eq!(app.command_line(), "csvlens tests/data/cities.csv");
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("City|State".into()),
+        );
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::Filter(":State == OH".into()),
+        );
+        let filter_done = |app: &App| match &app.finder {
+            Some(f) => f.done() && app.rows_view.num_filtered_rows() == Some(f.count()),
+            None => false,
+        };
+        step_until(&mut app, &mut terminal, filter_done);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        let command_line = app.command_line();
+        assert_eq!(
+            command_line,
+            "csvlens tests/data/cities.csv --columns 'City|State' --filter ':State == OH' --goto 3"
+        );
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].starts_with("62  │  Steubenville"));
+
+        // The same view with the command line options
+        let mut app = App::new(filename, None, Some(filename.to_owned()), false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+        app.set_columns_filter("City|State").unwrap();
+        app.set_filter(":State == OH").unwrap();
+        app.set_goto(3);
+        // Drawn first like in the main loop
+        app.draw(&mut terminal).unwrap();
+        step_until(&mut app, &mut terminal, |app| {
+            filter_done(app) && app.pending_goto.is_none()
+        });
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[4].starts_with("62   │  Steubenville"));
+        assert_eq!(app.command_line(), command_line);
+
+        // How the file was read comes first
+        app.set_source_args(vec!["--skip-rows".to_owned(), "2".to_owned()]);
+        assert!(app
+            .command_line()
+            .starts_with("csvlens tests/data/cities.csv --skip-rows 2 --columns"));
+
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn test_filter_columns_exclude() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(70, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("!Lat|Lon|NS|EW".into()),
+        );
+        let expected = vec![
+            "──────────────────────────────────────────────────────────────────────",
+            "      City          State                                             ",
+            "───┬─────────────────────────┬────────────────────────────────────────",
+            "1  │  Youngstown    OH       │                                       ▐",
+            "───┴─────────────────────────┴────────────────────────────────────────",
+            "stdin [Row 1/128, Col 1/2] [Filter \"!Lat|Lon|NS|EW\": 2/10 cols]       ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Excluding everything shows all columns
+        step_and_draw(&mut app, &mut terminal, Control::FilterColumns("!.".into()));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].starts_with(
+            "stdin [Row 1/128, Col 9/10] [Filter \"!.\": no match, showing all column"
+        ));
+    }
+
+    #[test]
+    fn test_current_column_header() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        let is_current = |terminal: &Terminal<TestBackend>, header: &str| {
+            let lines = to_lines(&terminal.backend().buffer().clone());
+            let x = lines[1].find(header).unwrap() as u16;
+            let cell = terminal.backend().buffer().get(x, 1).clone();
+            cell.modifier.contains(Modifier::UNDERLINED)
+        };
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        assert!(is_current(&terminal, "LatD"));
+        assert!(!is_current(&terminal, "LatM"));
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        assert!(is_current(&terminal, "LatM"));
+
+        // Still the leftmost column shown after the frozen first column
+        step_and_draw(&mut app, &mut terminal, Control::FreezePanes);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        assert!(!is_current(&terminal, "LatD"));
+        assert!(is_current(&terminal, "LatS"));
+    }
+
+    #[test]
+    fn test_min_column_width() {
+        let mut app = App::new("tests/data/empty_cells.csv", None, None, false).unwrap();
+        app.set_min_column_width(5);
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "────────────────────────────────────────",
+            "      a        b        c               ",
+            "───┬─────────────────────────────┬──────",
+            "1  │  1                 x        │     ▐",
+            "───┴─────────────────────────────┴──────",
+            "stdin [Row 1/3, Col 1/3]                ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_restore_positions() {
+        let filename = "tests/data/simple.csv";
+        let mut app = App::new(filename, None, Some(filename.to_owned()), false).unwrap();
+        app.restore_positions(|_| Some((1000, 2)));
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("1001"));
+        assert!(lines[9].starts_with("tests/data/simple.csv [Row 1003/5000"));
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        assert_eq!(app.positions(), vec![(filename.to_owned(), (1000, 3))]);
+
+        // Kept within the rows if the file is shorter now
+        let mut app = App::new(filename, None, Some(filename.to_owned()), false).unwrap();
+        app.restore_positions(|_| Some((10000, 2)));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("4956"));
+        assert!(lines[9].starts_with("tests/data/simple.csv [Row 4958/5000"));
+    }
+
+    #[test]
+    fn test_raw_fields() {
+        let mut app = App::new("tests/data/good_double_quote.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 7);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleRawFields);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "──────────────────────────────────────────────────",
+            "      Column1    column2                          ",
+            "───┬───────────────────────────────┬──────────────",
+            "1  │  1          \"quote\"           │              ",
+            "2  │  5          \"Comma, comma\"    │              ",
+            "───┴───────────────────────────────┴──────────────",
+            "Showing fields as in the file                     ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleRawFields);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("1  │  1          quote "));
+        assert_eq!(lines[6].trim_end(), "Showing parsed values");
+    }
+
+    #[test]
+    fn test_edit_cell() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("edit.csv");
+        std::fs::write(&path, "a,b\n\"x\",1\n\"y\",2\n").unwrap();
+        let filename = path.to_str().unwrap();
+        let mut app = App::new(filename, None, Some(filename.to_owned()), false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::EditCell);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines[7].trim_end(),
+            "Start csvlens with --edit to edit cells"
+        );
+
+        app.set_editable(true);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        step_and_draw(&mut app, &mut terminal, Control::FilterColumns("b".into()));
+        step_and_draw(&mut app, &mut terminal, Control::EditCell);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[7].trim_end(), "Edit: 2█");
+
+        step_and_draw(&mut app, &mut terminal, Control::SetCell("20, 30".into()));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[4].trim_end(), "2  │  20, 30    │");
+        assert_eq!(lines[7].trim_end(), "Saved b of row 2");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "a,b\n\"x\",1\n\"y\",\"20, 30\"\n"
+        );
+
+        // Values replaced in the preview are not in the file
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::ReplacePreview("0/9".into()),
+        );
+        // Both columns fit, so there is nothing to scroll right to
+        app.csv_table_state.set_cols_offset(1);
+        step_and_draw(&mut app, &mut terminal, Control::EditCell);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[7].trim_end(), "Can't edit the replacement preview");
+        step_and_draw(&mut app, &mut terminal, Control::SetCell("x".into()));
+        assert_eq!(
+            app.user_error.as_deref(),
+            Some("Can't edit the replacement preview")
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "a,b\n\"x\",1\n\"y\",\"20, 30\"\n"
+        );
+    }
+
+    #[test]
+    fn test_cell_length() {
+        let mut app = App::new("tests/data/unicode.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(80, 7);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[6].trim_end(), "stdin [Row 1/2, Col 1/2]");
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleCellLength);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines[6].trim_end(),
+            "stdin [Row 1/2, Col 1/2] [4 chars, 5 bytes]"
+        );
+
+        // e followed by a combining accent is a single character
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("city".into()),
+        );
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[6].trim_end().ends_with("[1 char, 3 bytes]"));
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        step_and_draw(&mut app, &mut terminal, Control::Sort);
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "       LatD ▲    LatM    LatS    NS    LonD    LonM    …    ",
+            "────┬───────────────────────────────────────────────────────",
+            "17  │  26        43      11      N     80      3       0   ▐",
+            "53  │  27        57      0       N     82      26      …    ",
+            "85  │  27        20      24      N     82      31      …    ",
+            "────┴━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━─────────────────",
+            "stdin [Row 17/128, Col 1/10] [Sorted by LatD ▲]             ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Clicking the header of the sorted column sorts the other way
+        step_and_draw(&mut app, &mut terminal, Control::MouseClick(8, 1));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "        LatD ▼    LatM    LatS    NS    LonD    LonM    …   ",
+            "─────┬──────────────────────────────────────────────────────",
+            "125  │  50        25      11      N     104     39      …  ▐",
+            "7    │  49        52      48      N     97      9       …   ",
+            "34   │  49        16      12      N     123     7       …   ",
+            "─────┴━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━─────────────────",
+            "stdin [Row 125/128, Col 1/10] [Sorted by LatD ▼]            ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // and then goes back to the file order
+        step_and_draw(&mut app, &mut terminal, Control::MouseClick(8, 1));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("1  │  41"));
+        assert!(!lines[7].contains("Sorted"));
+
+        // Filtering clears the sort
+        step_and_draw(&mut app, &mut terminal, Control::Sort);
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Filter("^OH$".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("1   │  41"));
+        assert!(!lines[7].contains("Sorted"));
+
+        step_and_draw(&mut app, &mut terminal, Control::Sort);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[7].starts_with("Clear the rows filter to sort"));
+    }
+
+    #[test]
+    fn test_long_headers() {
+        let mut app = App::new("tests/data/long_headers.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        app.set_header_mode(HeaderMode::Wrap);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "      customer_     is_account_    registration             ",
+            "      identifier    active         date                     ",
+            "───┬────────────────────────────────────────────────┬───────",
+            "1  │  1             true           2021-01-05       │       ",
+            "2  │  2             false          2022-11-30       │       ",
+            "───┴────────────────────────────────────────────────┴───────",
+            "stdin [Row 1/2, Col 1/3]                                    ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // The full name of the current column is shown when shortened
+        app.set_header_mode(HeaderMode::Abbreviate);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "      cstmr_idntfr    is_accnt_actv    rgstrtn dt           ",
+            "───┬─────────────────────────────────────────────────┬──────",
+            "1  │  1               true             2021-01-05    │      ",
+            "2  │  2               false            2022-11-30    │      ",
+            "   │                                                 │      ",
+            "───┴─────────────────────────────────────────────────┴──────",
+            "stdin [Row 1/2, Col 1/3: customer_identifier]               ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_debug_timings() {
+        let mut app = App::new("tests/data/cities.csv", None, None, true).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 12);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Filter("^OH$".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].ends_with("┌ Timings ───────────────┐ "));
+        for (line, label) in
+            lines[4..8]
+                .iter()
+                .zip(["read rows", "pick columns", "render", "find/filter"])
+        {
+            assert!(line.contains(&format!("│{}", label)), "{}", line);
+            assert!(line.ends_with("ms│ "), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_command_palette() {
+        let mut app = App::new("tests/data/simple.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        app.csv_table_state
+            .set_buffer(crate::input::InputMode::Palette, "copy");
+        terminal.draw(|f| app.render_frame(f)).unwrap();
+        let expected = vec![
+            "────────────────────────────────────────",
+            "      a     b                           ",
+            "┌ Commands ────────────────────────────┐",
+            "│Copy selected rows                   y│",
+            "│Copy column name                     Y│",
+            "│Copy cell with column name           V│",
+            "│Copy command line                    C│",
+            "└──────────────────────────────────────┘",
+            "───┴──────────────┴─────────────────────",
+            "Command: copy█                          ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_show_empty_cells() {
+        let mut app = App::new("tests/data/empty_cells.csv", None, None, false).unwrap();
+        app.set_show_empty_cells(true);
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(30, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "──────────────────────────────",
+            "      a    b      c           ",
+            "───┬───────────────────┬──────",
+            "1  │  1    ∅      x    │      ",
+            "2  │  2    ···    ∅    │      ",
+            "3  │  3    z      y    │      ",
+            "───┴───────────────────┴──────",
+            "stdin [Row 1/3, Col 1/3]      ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_column_groups() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::NextColumnGroup);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].starts_with("No column groups, add them to"));
+
+        let group = |name: &str, pattern: &str| ColumnGroup {
+            name: name.to_owned(),
+            pattern: pattern.to_owned(),
+        };
+        app.set_column_groups(vec![group("lat", "^Lat"), group("place", "City|State")]);
+        step_and_draw(&mut app, &mut terminal, Control::NextColumnGroup);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "      LatD    LatM    LatS                                  ",
+            "───┬──────────────────────────┬─────────────────────────────",
+            "1  │  41      5       59      │                            ▐",
+            "───┴──────────────────────────┴─────────────────────────────",
+            "stdin [Row 1/128, Col 1/3] [Group lat: 3/10 cols]           ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::NextColumnGroup);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].contains("[Group place: 2/10 cols]"));
+
+        // Typing the same regex also counts as the group
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("^Lat".into()),
+        );
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[5].contains("[Group lat: 3/10 cols]"));
+
+        step_and_draw(&mut app, &mut terminal, Control::NextColumnGroup);
+        step_and_draw(&mut app, &mut terminal, Control::NextColumnGroup);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[5].trim_end(), "stdin [Row 1/128, Col 9/10]");
+    }
+
+    #[test]
+    fn test_count() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(110, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::Count(":State == OH".into()),
+        );
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "──────────────────────────────────────────────────────────────────────────────────────────────────────────────",
+            "      LatD    LatM    LatS    NS    LonD    LonM    LonS    EW    City          State                         ",
+            "───┬─────────────────────────────────────────────────────────────────────────────────────┬────────────────────",
+            "1  │  41      5       59      N     80      39      0       W     Youngstown    OH       │                   ▐",
+            "───┴─────────────────────────────────────────────────────────────────────────────────────┴────────────────────",
+            "stdin [Row 1/128, Col 1/10] [Count \"State == OH\": 6 rows]                                                     ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Counting is independent from filtering
+        step_and_draw(&mut app, &mut terminal, Control::Filter("Ma".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(
+            lines[5].contains("[Count \"State == OH\": 6 rows]"),
+            "{}",
+            lines[5]
+        );
+
+        step_and_draw(&mut app, &mut terminal, Control::ClearCount);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(!lines[5].contains("Count"));
+    }
+
+    #[test]
+    fn test_ascii() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+        app.set_ascii(true);
+        app.set_grid(true);
+
+        let backend = TestBackend::new(50, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Sort);
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "--------------------------------------------------",
+            "       LatD ^     LatM     LatS     NS     L~     ",
+            "----+----------+--------+--------+------+---------",
+            "17  |  26      |  43    |  11    |  N   |  80    #",
+            "53  |  27      |  57    |  0     |  N   |  82     ",
+            "85  |  27      |  20    |  24    |  N   |  82     ",
+            "----+======================------+------+---------",
+            "stdin [Row 17/128, Col 1/10] [Sorted by LatD ^]   ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleDetail);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines.iter().all(|line| line.is_ascii()), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_value_change() {
+        // Groups longer than the rows read at a time
+        let mut content = "group,n\n".to_owned();
+        for i in 0..2500 {
+            content.push_str(&format!("{},{}\n", i / 1200, i));
+        }
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), content).unwrap();
+        let mut app = App::new(file.path().to_str().unwrap(), None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+        let selected_n = |app: &App| {
+            let selected = app.rows_view.selected().unwrap() as usize;
+            app.rows_view.rows()[selected].fields[1].clone()
+        };
+
+        step_and_scan(&mut app, &mut terminal, Control::NextValueChange);
+        assert_eq!(selected_n(&app), "1200");
+        step_and_scan(&mut app, &mut terminal, Control::NextValueChange);
+        assert_eq!(selected_n(&app), "2400");
+        step_and_scan(&mut app, &mut terminal, Control::NextValueChange);
+        assert_eq!(selected_n(&app), "2400");
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[7].trim_end(), "No other value in the column");
+        step_and_scan(&mut app, &mut terminal, Control::PrevValueChange);
+        assert_eq!(selected_n(&app), "2399");
+
+        // Among the filtered rows only
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::Filter(":n < 5 OR n > 2497".into()),
+        );
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        step_and_scan(&mut app, &mut terminal, Control::NextValueChange);
+        assert_eq!(selected_n(&app), "2498");
+        step_and_scan(&mut app, &mut terminal, Control::PrevValueChange);
+        assert_eq!(selected_n(&app), "4");
+    }
+
+    #[test]
+    fn test_same_value() {
+        // The last group comes back after another one
+        let mut content = "group,n\n".to_owned();
+        for i in 0..2500 {
+            content.push_str(&format!("{},{}\n", (i / 1200) % 2, i));
+        }
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), content).unwrap();
+        let mut app = App::new(file.path().to_str().unwrap(), None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+        let selected_n = |app: &App| {
+            let selected = app.rows_view.selected().unwrap() as usize;
+            app.rows_view.rows()[selected].fields[1].clone()
+        };
+
+        step_and_scan(&mut app, &mut terminal, Control::LastSameValue);
+        assert_eq!(selected_n(&app), "2499");
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines[7].trim_end(),
+            "1300 rows with the same value in the column"
+        );
+        step_and_scan(&mut app, &mut terminal, Control::FirstSameValue);
+        assert_eq!(selected_n(&app), "0");
+
+        step_and_scan(&mut app, &mut terminal, Control::NextValueChange);
+        step_and_scan(&mut app, &mut terminal, Control::LastSameValue);
+        assert_eq!(selected_n(&app), "2399");
+        step_and_scan(&mut app, &mut terminal, Control::FirstSameValue);
+        assert_eq!(selected_n(&app), "1200");
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines[7].trim_end(),
+            "1200 rows with the same value in the column"
+        );
+
+        // Read a chunk at a time until done or another key is pressed
+        let mut scan = app.new_value_scan(ValueScanKind::LastSame).unwrap();
+        assert!(!app.read_value_scan_chunk(&mut scan).unwrap());
+        assert_eq!((scan.rows_read, scan.count), (1000, 0));
+        app.value_scan = Some(scan);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        assert!(app.value_scan.is_none());
+        assert_eq!(selected_n(&app), "1201");
+    }
+
+    #[test]
+    fn test_show_booleans() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            "id,active,n,ok,f\n1,true,1,yes,1\n2,FALSE,2,no,0\n3,,0,1,1\n",
+        )
+        .unwrap();
+        let mut app = App::new(file.path().to_str().unwrap(), None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleBooleans);
+        let expected = vec![
+            "────────────────────────────────────────",
+            "      id    active    n    ok     f     ",
+            "───┬───────────────────────────────────┬",
+            "1  │  1     ✓         1    yes    1    │",
+            "2  │  2     ✗         2    no     0    │",
+            "3  │  3               0    1      1    │",
+            "───┴───────────────────────────────────┴",
+            "stdin [Row 1/3, Col 1/5]                ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        app.set_ascii(true);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(
+            lines[4].starts_with("2  |  2     N         2"),
+            "{}",
+            lines[4]
+        );
+    }
+
+    #[test]
+    fn test_show_scientific() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            "id,big,small,name\n1,12345678901234,0.000012345,a\n2,1000000000000,0.5,b\n3,,0.0,12345678901234\n",
+        )
+        .unwrap();
+        let mut app = App::new(file.path().to_str().unwrap(), None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleScientific);
+        let expected = vec![
+            "──────────────────────────────────────────────────",
+            "      id    big         small       name          ",
+            "───┬──────────────────────────────────────────────",
+            "1  │  1     1.235e13    1.234e-5    a             ",
+            "2  │  2     1e12        0.5         b             ",
+            "3  │  3                 0.0         123456789…    ",
+            "───┴──────────────────────────────────────────────",
+            "stdin [Row 1/3, Col 1/4]                          ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_show_changes() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleChanges);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[4].starts_with("2  │  42      52      48      N"));
+        let buffer = terminal.backend().buffer().clone();
+        // LatD changed from the row above but NS didn't
+        assert_eq!(buffer.get(6, 4).fg, Color::Rgb(0, 200, 100));
+        assert_eq!(buffer.get(30, 4).fg, Color::Rgb(100, 100, 100));
+        // Nothing to compare the first row with
+        assert_eq!(buffer.get(30, 3).fg, Color::Rgb(255, 200, 0));
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleChanges);
+        let buffer = terminal.backend().buffer().clone();
+        assert_eq!(buffer.get(6, 4).fg, Color::Reset);
+    }
+
+    #[test]
+    fn test_grid() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleGrid);
+        let expected = vec![
+            "──────────────────────────────────────────────────",
+            "      LatD     LatM     LatS     NS     LonD     …",
+            "───┬────────┬────────┬────────┬──────┬────────┬───",
+            "1  │  41    │  5     │  59    │  N   │  80    │  ▐",
+            "───┴━━━━━━━━━━━━━━━━━━━━━━━━━━━──────┴────────┴───",
+            "stdin [Row 1/128, Col 1/10]                       ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleCompact);
+        let expected = vec![
+            "──────────────────────────────────────────────────",
+            "    LatD   LatM   LatS   NS   LonD   LonM   Lo…   ",
+            "──┬──────┬──────┬──────┬────┬──────┬──────┬───────",
+            "1 │ 41   │ 5    │ 59   │ N  │ 80   │ 39   │ 0    ▐",
+            "──┴━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┴──────┴───────",
+            "stdin [Row 1/128, Col 1/10]                       ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_find_header() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Find("h:^Lon".into()));
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "      LonD    LonM    LonS    EW    City          State     ",
+            "───┬───────────────────────────────────────────────────────┬",
+            "1  │  80      39      0       W     Youngstown    OH       ▐",
+            "───┴───────────────────────━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━",
+            "stdin [Row 1/128, Col 5/10] [Find header \"^Lon\": 1/3]       ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        let status = |app: &mut App, terminal: &mut Terminal<TestBackend>, control| {
+            step_and_draw(app, terminal, control);
+            to_lines(&terminal.backend().buffer().clone())[5].clone()
+        };
+        let line = status(&mut app, &mut terminal, Control::ScrollToNextFound);
+        assert!(line.starts_with("stdin [Row 1/128, Col 6/10] [Find header \"^Lon\": 2/3]"));
+        let line = status(&mut app, &mut terminal, Control::ScrollToPrevFound);
+        assert!(line.contains("Col 5/10] [Find header \"^Lon\": 1/3]"));
+        let line = status(&mut app, &mut terminal, Control::ScrollToPrevFound);
+        assert!(line.contains("Col 7/10] [Find header \"^Lon\": 3/3]"));
+
+        let line = status(&mut app, &mut terminal, Control::Find("h:xyz".into()));
+        assert!(line.contains("Col 7/10] [Find header \"xyz\": Not found]"));
+        let line = status(&mut app, &mut terminal, Control::ClearFilter);
+        assert!(!line.contains("Find header"));
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleCompact);
+        let expected = vec![
+            "────────────────────────────────────────",
+            "    LatD LatM LatS NS LonD LonM LonS EW ",
+            "──┬─────────────────────────────────────",
+            "1 │ 41   5    59   N  80   39   0    W ▐",
+            "──┴━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━────",
+            "stdin [Row 1/128, Col 1/10]             ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleCompact);
+        let expected = vec![
+            "────────────────────────────────────────",
+            "      LatD    LatM    LatS    NS    …   ",
+            "───┬────────────────────────────────────",
+            "1  │  41      5       59      N     …  ▐",
+            "───┴━━━━━━━━━━━━━━━━━━──────────────────",
+            "stdin [Row 1/128, Col 1/10]             ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_show_whitespace() {
+        let mut app = App::new("tests/data/small.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 7);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleWhitespace);
+        let expected = vec![
+            "────────────────────────────────────────",
+            "      COL1     COL2                     ",
+            "───┬───────────────────┬────────────────",
+            "1  │  c1      ·v1      │                ",
+            "2  │  c2      ·v2      │                ",
+            "───┴───────────────────┴────────────────",
+            "stdin [Row 1/2, Col 1/2]                ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_truncate_left() {
+        let mut app = App::new("tests/data/paths.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 7);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "────────────────────────────────────────",
+            "      id    path                        ",
+            "───┬────────────────────────────────────",
+            "1  │  1     /home/user/projects/dat…    ",
+            "2  │  2     /var/log/app.log            ",
+            "───┴────────────────────────────────────",
+            "stdin [Row 1/2, Col 1/2]                ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        // Toggle applies to the current column only
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        step_and_draw(&mut app, &mut terminal, Control::ToggleTruncateSide);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollLeft);
+        let expected = vec![
+            "────────────────────────────────────────",
+            "      id    path                        ",
+            "───┬────────────────────────────────────",
+            "1  │  1     …rojects/data/report.csv    ",
+            "2  │  2     /var/log/app.log            ",
+            "───┴────────────────────────────────────",
+            "stdin [Row 1/2, Col 1/2]                ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_control_characters() {
+        let mut app = App::new("tests/data/control_chars.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "────────────────────────────────────────",
+            "      name    note                      ",
+            "───┬──────────────────────────┬─────────",
+            "1  │  a       ␛[31mred␛[0m    │         ",
+            "2  │  b       tab␉here        │         ",
+            "   │                          │         ",
+            "   │                          │         ",
+            "   │                          │         ",
+            "───┴──────────────────────────┴─────────",
+            "stdin [Row 1/2, Col 1/2]                ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleDetail);
+        step_and_draw(&mut app, &mut terminal, Control::ToggleDetailRaw);
+        let expected = vec![
+            "────────────────────────────────────────",
+            "    ┌ Row 1 (raw) ─────────────────┐    ",
+            "───┬│name: a                       │────",
+            "1  ││note: \\x1b[31mred\\x1b[0m      │    ",
+            "2  ││                              │    ",
+            "   ││                              │    ",
+            "   ││                              │    ",
+            "   ││                              │    ",
+            "───┴└──────────────────────────────┘────",
+            "stdin [Row 1/2, Col 1/2]                ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_tab_width() {
+        let mut app = App::new("tests/data/control_chars.csv", None, None, false).unwrap();
+        app.set_tab_width(Some(4));
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 7);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[4].trim_end(), "2  │  b       tab    here     │");
+    }
+
+    #[test]
+    fn test_detail_find() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 12);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleDetail);
+        step_and_draw(&mut app, &mut terminal, Control::Find("^[0-9]$".into()));
+        let expected = vec![
+            "──────────────────────────────────────────────────",
+            "     ┌ Row 1 [Found 1/2] ───────────────────┐…    ",
+            "───┬─│LatD: 41                              │─────",
+            "1  │ │LatM: 5                               │9   ▐",
+            "2  │ │LatS: 59                              │3    ",
+            "3  │ │NS: N                                 │0    ",
+            "4  │ │LonD: 80                              │8    ",
+            "5  │ │LonM: 39                              │6    ",
+            "6  │ │LonS: 0                               │5    ",
+            "7  │ └──────────────────────────────────────┘     ",
+            "───┴━━━━━━━━━━━━━━━━━━━━━━━━━━━───────────────────",
+            "stdin [Row 1/128, Col 1/10]                       ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        // The current match is highlighted differently
+        let cells: Vec<&Cell> = (0..12).map(|y| actual_buffer.get(12, y)).collect();
+        assert_eq!(cells[3].bg, Color::LightYellow);
+        assert_eq!(cells[3].fg, Color::Rgb(200, 0, 0));
+
+        // Scrolls to matches further down, wrapping around at the ends
+        step_and_draw(&mut app, &mut terminal, Control::Find("OH|W".into()));
+        step_and_draw(&mut app, &mut terminal, Control::ScrollToNextFound);
+        let expected = vec![
+            "──────────────────────────────────────────────────",
+            "     ┌ Row 1 [Found 2/2] ───────────────────┐…    ",
+            "───┬─│NS: N                                 │─────",
+            "1  │ │LonD: 80                              │9   ▐",
+            "2  │ │LonM: 39                              │3    ",
+            "3  │ │LonS: 0                               │0    ",
+            "4  │ │EW: W                                 │8    ",
+            "5  │ │City: Youngstown                      │6    ",
+            "6  │ │State: OH                             │5    ",
+            "7  │ └──────────────────────────────────────┘     ",
+            "───┴━━━━━━━━━━━━━━━━━━━━━━━━━━━───────────────────",
+            "stdin [Row 1/128, Col 1/10]                       ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollToNextFound);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[1].contains(" Row 1 [Found 1/2] "));
+        step_and_draw(&mut app, &mut terminal, Control::ScrollToPrevFound);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[1].contains(" Row 1 [Found 2/2] "));
+
+        // Finding outside of the popup is not affected
+        step_and_draw(&mut app, &mut terminal, Control::ClearFilter);
+        step_and_draw(&mut app, &mut terminal, Control::ToggleDetail);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[11].trim_end(), "stdin [Row 1/128, Col 1/10]");
+    }
+
+    #[test]
+    fn test_freeze_panes() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        // Freezing panes stays on the first column
+        step_and_draw(&mut app, &mut terminal, Control::FreezePanes);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        let expected = vec![
+            "──────────────────────────────────────────────────",
+            "      LatD    NS    LonD    LonM    LonS    EW    ",
+            "───┬──────────────────────────────────────────────",
+            "1  │  41      N     80      39      0       W    ▐",
+            "2  │  42      N     97      23      23            ",
+            "3  │  46      N     120     30      36      W     ",
+            "───┴──────────────━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━",
+            "stdin [Row 2/128, Col 4/10]                       ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        // Can't scroll the pinned column away, but can move onto it
+        for _ in 0..5 {
+            step_and_draw(&mut app, &mut terminal, Control::ScrollLeft);
+        }
+        let expected = vec![
+            "──────────────────────────────────────────────────",
+            "      LatD    LatM    LatS    NS    LonD    L…    ",
+            "───┬──────────────────────────────────────────────",
+            "1  │  41      5       59      N     80      39   ▐",
+            "2  │  42      52      48      N     97      23    ",
+            "3  │  46      35      59      N     120     30    ",
+            "───┴────━━━━━━━━━━━━━━━━━━━━━━━━━━━───────────────",
+            "stdin [Row 2/128, Col 1/10]                       ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines[1],
+            "      LatD    LatM    LatS    NS    LonD    L…    "
+        );
+        assert!(lines
+            .last()
+            .unwrap()
+            .starts_with("stdin [Row 2/128, Col 2/10]"));
+
+        step_and_draw(&mut app, &mut terminal, Control::FreezePanes);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollLeft);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines
+            .last()
+            .unwrap()
+            .starts_with("stdin [Row 2/128, Col 1/10]"));
+    }
+
+    #[test]
+    fn test_gutter_mode() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::Filter(":LatD < 40".into()),
+        );
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        let gutter: Vec<&str> = lines[3..6]
+            .iter()
+            .map(|x| x.split('│').next().unwrap())
+            .collect();
+        assert_eq!(gutter, vec!["8   ", "9   ", "10  "]);
+
+        step_and_draw(&mut app, &mut terminal, Control::CycleGutterMode);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        let gutter: Vec<&str> = lines[3..6]
+            .iter()
+            .map(|x| x.split('│').next().unwrap())
+            .collect();
+        assert_eq!(gutter, vec!["2  ", "3  ", "4  "]);
+
+        step_and_draw(&mut app, &mut terminal, Control::CycleGutterMode);
+        let expected = vec![
+            "──────────────────────────────────────────────────",
+            "       LatD    LatM    LatS    NS    LonD    …    ",
+            "────┬─────────────────────────────────────────────",
+            "-2  │  39      11      23      N     78      9   ▐",
+            "-1  │  34      14      24      N     77      …    ",
+            "0   │  39      45      0       N     75      …    ",
+            "────┴━━━━━━━━━━━━━━━━━━━━━━━━━━━──────────────────",
+            "stdin [Row 10/128, Col 1/10] [66/128 rows (filtere",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollUp);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        let gutter: Vec<&str> = lines[3..6]
+            .iter()
+            .map(|x| x.split('│').next().unwrap())
+            .collect();
+        assert_eq!(gutter, vec!["-1  ", "0   ", "+1  "]);
+    }
+
+    #[test]
+    fn test_row_numbering() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+        app.set_row_numbering(0, true);
+
+        let backend = TestBackend::new(50, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        // The header is row 0, so that row 5 is the 5th row after it
+        step_and_draw(&mut app, &mut terminal, Control::ScrollTo(5));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        let gutter: Vec<&str> = lines[3..6]
+            .iter()
+            .map(|x| x.split('│').next().unwrap())
+            .collect();
+        assert_eq!(gutter, vec!["5  ", "6  ", "7  "]);
+        assert!(lines[7].starts_with("stdin [Row 5/128"));
+        assert_eq!(app.rows_view.selected_offset(), Some(4));
+
+        // Only the base applies to the rows matching a filter
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::Filter(":LatD < 40".into()),
+        );
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollTo(2));
+        assert_eq!(app.rows_view.selected_offset(), Some(2));
+        assert_eq!(
+            app.command_line(),
+            "csvlens --row-base 0 --count-header --filter ':LatD < 40' --goto 2"
+        );
+    }
+
+    #[test]
+    fn test_replace_preview() {
+        let mut app = App::new("tests/data/simple.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::ReplacePreview("B(\\d)/b-$1".into()),
+        );
+        let expected = vec![
+            "────────────────────────────────────────",
+            "      b     b (replaced)                ",
+            "───┬────────────────────────┬───────────",
+            "1  │  B1    b-1             │          ▐",
+            "───┴────────────━━━━━━━━━━━━━━━━━━━━━━━━",
+            "Showing b replaced in the last column   ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        // Replaced in the same column again from the preview column
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::ReplacePreview("B/x".into()),
+        );
+        assert_eq!(app.rows_view.rows()[0].fields, vec!["A1", "B1", "x1"]);
+
+        step_and_draw(&mut app, &mut terminal, Control::ClearReplacePreview);
+        assert_eq!(app.rows_view.headers(), &vec!["a", "b"]);
+        assert_eq!(app.csv_table_state.cols_offset, 1);
+    }
+
+    #[test]
+    fn test_expand_row() {
+        let mut app = App::new("tests/data/paths.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(30, 9);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        step_and_draw(&mut app, &mut terminal, Control::ToggleExpandRow);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "──────────────────────────────",
+            "      id    path              ",
+            "───┬──────────────────────────",
+            "1  │  1     /home/user/pro    ",
+            "   │        jects/data/rep    ",
+            "   │        ort.csv           ",
+            "2  │  2     /var/log/app.…    ",
+            "───┴──────────────────────────",
+            "stdin [Row 1/2, Col 1/2]      ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        // Collapsed once another row is selected
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        assert_eq!(app.csv_table_state.expanded_row, None);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[4].starts_with("2  "));
+    }
+
+    #[test]
+    fn test_reopen_with_suggested_delimiter() {
+        let mut app = App::new("tests/data/semicolon.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines[7],
+            "Many values contain ';', reopen with it as the delimiter fro"
+        );
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::ReopenWithSuggestedDelimiter,
+        );
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "      name    city    amount                                ",
+            "───┬────────────────────────────┬───────────────────────────",
+            "1  │  Ann     Oslo    1,5       │                          ▐",
+            "2  │  Bob     Rome    2,25      │                          ▐",
+            "3  │  Cid     Lima    3         │                           ",
+            "───┴────────────────────────────┴───────────────────────────",
+            "Reopened with ';' as the delimiter, as with -d ';'          ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+        assert!(app.command_line().starts_with("csvlens -d ';'"));
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::ReopenWithSuggestedDelimiter,
+        );
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[7].starts_with("No other delimiter to suggest"));
+    }
+
+    #[test]
+    fn test_fixed_width() {
+        let mut app = App::new("tests/data/fixed_width.txt", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[7], "Values seem to be in fixed-width columns");
+
+        step_and_draw(&mut app, &mut terminal, Control::ReopenAsFixedWidth);
+        step_until(&mut app, &mut terminal, |app| {
+            app.fixed_width_converter.is_none()
+        });
+        let expected = vec![
+            "────────────────────────────────────────",
+            "      name             city        …    ",
+            "───┬────────────────────────────────────",
+            "1  │  Alice    Wong    New York    …    ",
+            "2  │  Bob              Chicago     …    ",
+            "3  │  Carol            Boston      …    ",
+            "───┴────────────────────────────────────",
+            "Columns start at 0,6,12,26              ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        // Moving the start of the second column to the third one is not allowed
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        for _ in 0..6 {
+            step_and_draw(&mut app, &mut terminal, Control::MoveColumnStart(1));
+        }
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines[7].trim_end(),
+            "Columns can't be moved past each other"
+        );
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::SetColumnStarts("0,12,26".into()),
+        );
+        step_until(&mut app, &mut terminal, |app| {
+            app.fixed_width_converter.is_none()
+        });
+        let expected = vec![
+            "────────────────────────────────────────",
+            "      city        amount                ",
+            "───┬────────────────────────┬───────────",
+            "1  │  New York    12        │           ",
+            "2  │  Chicago     1200      │           ",
+            "3  │  Boston      7.5       │           ",
+            "───┴────────────━━━━━━━━━━━━━━━━━━━━━━━━",
+            "Columns start at 0,12,26                ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+        assert!(app
+            .command_line()
+            .starts_with("csvlens --column-starts 0,12,26"));
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollLeft);
+        step_and_draw(&mut app, &mut terminal, Control::MoveColumnStart(1));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[7].trim_end(), "The first column always starts at 0");
+    }
+
+    #[test]
+    fn test_move_column_start_while_converting() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::copy("tests/data/fixed_width.txt", file.path()).unwrap();
+        let mut app = App::new(file.path().to_str().unwrap(), None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ReopenAsFixedWidth);
+        step_until(&mut app, &mut terminal, |app| {
+            app.fixed_width_converter.is_none()
+        });
+
+        // Moves not converted yet add up
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        step_and_draw(&mut app, &mut terminal, Control::MoveColumnStart(1));
+        step_and_draw(&mut app, &mut terminal, Control::MoveColumnStart(1));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[7].trim_end(), "Columns start at 0,8,12,26");
+        step_until(&mut app, &mut terminal, |app| {
+            app.fixed_width_converter.is_none()
+        });
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("1  │  ng    New York"), "{}", lines[3]);
+        assert_eq!(lines[7].trim_end(), "Columns start at 0,8,12,26");
+
+        // Failing to convert again is shown instead of quitting
+        drop(file);
+        step_and_draw(&mut app, &mut terminal, Control::MoveColumnStart(1));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(
+            lines[7].starts_with("Failed to open file: "),
+            "{}",
+            lines[7]
+        );
+    }
+
+    #[test]
+    fn test_cycle_alignment() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "id,name\n7,Ann\n123456,Bob\n").unwrap();
+        let mut app = App::new(file.path().to_str().unwrap(), None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 7);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::CycleAlignment);
+        let expected = vec![
+            "────────────────────────────────────────",
+            "          id    name                    ",
+            "───┬────────────────────┬───────────────",
+            "1  │       7    Ann     │               ",
+            "2  │  123456    Bob     │               ",
+            "───┴────────────────────┴───────────────",
+            "Column id aligned right                 ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::CycleAlignment);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("1  │    7   "), "{}", lines[3]);
+
+        step_and_draw(&mut app, &mut terminal, Control::CycleAlignment);
+        step_and_draw(&mut app, &mut terminal, Control::CycleAlignment);
+        step_and_draw(&mut app, &mut terminal, Control::ResetColumnAlignments);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("1  │  7   "), "{}", lines[3]);
+    }
+
+    #[test]
+    fn test_autofit_column() {
+        let mut app = App::new("tests/data/paths.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(46, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        let expected = vec![
+            "──────────────────────────────────────────────",
+            "      path                                    ",
+            "───┬──────────────────────────────────────┬───",
+            "1  │  /home/user/projects/data/report…    │  ▐",
+            "───┴─────────────────────━━━━━━━━━━━━━━━━━━━━━",
+            "stdin [Row 1/2, Col 2/2]                      ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::AutofitColumn);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "──────────────────────────────────────────────",
+            "      path                                    ",
+            "───┬─────────────────────────────────────────┬",
+            "1  │  /home/user/projects/data/report.csv    ▐",
+            "───┴─────────────────────━━━━━━━━━━━━━━━━━━━━━",
+            "stdin [Row 1/2, Col 2/2]                      ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // The width is kept after scrolling back
+        step_and_draw(&mut app, &mut terminal, Control::ScrollLeft);
+        assert_eq!(app.csv_table_state.column_widths, Some(vec![6, 39]));
+
+        step_and_draw(&mut app, &mut terminal, Control::ResetColumnWidths);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        assert_eq!(app.csv_table_state.column_widths, Some(vec![6, 36]));
+    }
+
+    #[test]
+    fn test_hide_status() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleStatus);
+        let expected = vec![
+            "──────────────────────────────────────────────────",
+            "      LatD    LatM    LatS    NS    LonD    L…    ",
+            "───┬──────────────────────────────────────────────",
+            "1  │  41      5       59      N     80      39   ▐",
+            "2  │  42      52      48      N     97      23    ",
+            "3  │  46      35      59      N     120     30    ",
+            "4  │  42      16      12      N     71      48    ",
+            "───┴━━━━━━━━━━━━━━━━━━━━━━━━━━━───────────────────",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Shown again for messages until the next key
+        step_and_draw(&mut app, &mut terminal, Control::LastSameValue);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines[7].trim_end(),
+            "12 rows with the same value in the column"
+        );
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[7].contains("┴━"), "{}", lines[7]);
+    }
+
+    #[test]
+    fn test_cell_position() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(80, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Filter("WA".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::ToggleCellPosition);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(
+            lines[7].starts_with("stdin [Row 18/128, Col 2/10] [R2 C2 (LatM)] [6/128 rows"),
+            "{}",
+            lines[7]
+        );
+    }
+
+    #[test]
+    fn test_ruler() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleRuler);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        let expected = vec![
+            "──────────────────────────────────────────────────",
+            "      2       3       4     5       6       7     ",
+            "      LatM    LatS    NS    LonD    LonM    L…    ",
+            "───┬──────────────────────────────────────────────",
+            "1  │  5       59      N     80      39      0    ▐",
+            "2  │  52      48      N     97      23      23    ",
+            "───┴────━━━━━━━━━━━━━━━━━━━━━━━━━━━───────────────",
+            "stdin [Row 1/128, Col 2/10]                       ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Clicking the header below the numbers still sorts
+        step_and_draw(&mut app, &mut terminal, Control::MouseClick(8, 2));
+        assert!(app.csv_table_state.sort_state.is_some());
+    }
+
+    #[test]
+    fn test_reset_display_options() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(50, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ToggleCompact);
+        step_and_draw(&mut app, &mut terminal, Control::ToggleGrid);
+        step_and_draw(&mut app, &mut terminal, Control::CycleGutterMode);
+        let options = DisplayOptions {
+            compact: true,
+            grid: true,
+            gutter_mode: GutterMode::Position,
+            ..Default::default()
+        };
+        assert_eq!(app.display_options(), options);
+
+        step_and_draw(&mut app, &mut terminal, Control::ResetDisplayOptions);
+        assert_eq!(app.display_options(), DisplayOptions::default());
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[7].trim_end(), "Reset display options");
+
+        app.set_display_options(options);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        assert_eq!(app.display_options(), options);
+    }
+
+    #[test]
+    fn test_export_marked() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("City|State".into()),
+        );
+        step_and_draw(&mut app, &mut terminal, Control::ToggleMark);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        step_and_draw(&mut app, &mut terminal, Control::ToggleMark);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines.last().unwrap(),
+            "stdin [Row 3/128, Col 1/2] [2 marked] [Filter \"City|State\": "
+        );
+
+        let out = tempfile::NamedTempFile::new().unwrap();
+        let path = out.path().to_str().unwrap().to_owned();
+        step_and_draw(&mut app, &mut terminal, Control::ExportMarked(path.clone()));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines.last().unwrap().trim_end(),
+            format!("Exported 2 rows to {}", path)
+        );
+
+        let content = std::fs::read_to_string(path).unwrap();
+        assert_eq!(content, "City,State\nYoungstown,OH\nYakima,WA\n");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.md").to_str().unwrap().to_owned();
+        step_and_draw(&mut app, &mut terminal, Control::ExportMarked(path.clone()));
+        let content = std::fs::read_to_string(path).unwrap();
+        assert_eq!(
+            content,
+            "| City | State |\n| --- | --- |\n| Youngstown | OH |\n| Yakima | WA |\n"
+        );
+    }
+
+    #[test]
+    fn test_mark_all_and_invert() {
+        let mut app = App::new("tests/data/simple.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::MarkAll);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[9].trim_end(), "5000 rows marked");
+        step_and_draw(&mut app, &mut terminal, Control::ClearMarks);
+
+        // Only the rows matching the filter are inverted
+        step_and_draw(&mut app, &mut terminal, Control::Filter("A4.$".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        step_and_draw(&mut app, &mut terminal, Control::ToggleMark);
+        step_and_draw(&mut app, &mut terminal, Control::InvertMarks);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[9].trim_end(), "9 rows marked");
+        assert_eq!(
+            app.rows_view.get_marked_rows().unwrap()[0].fields,
+            vec!["A41", "B41"]
+        );
+
+        step_and_draw(&mut app, &mut terminal, Control::ClearFilter);
+        step_and_draw(&mut app, &mut terminal, Control::InvertMarks);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[9].trim_end(), "4991 rows marked");
+    }
+
+    #[test]
+    fn test_select_range() {
+        let mut app = App::new("tests/data/simple.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        step_and_draw(&mut app, &mut terminal, Control::SelectDown);
+        step_and_draw(&mut app, &mut terminal, Control::SelectDown);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines.last().unwrap(),
+            "stdin [Row 4/5000, Col 1/2] [3 selected]"
+        );
+        let (num_rows, text) = app.get_selected_rows_as_csv().unwrap().unwrap();
+        assert_eq!(num_rows, 3);
+        assert_eq!(text, "A2,B2\nA3,B3\nA4,B4\n");
+
+        // Selection range under a filter is contiguous within the filtered rows
+        step_and_draw(&mut app, &mut terminal, Control::Filter("A1.?$".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        step_and_draw(&mut app, &mut terminal, Control::SelectDown);
+        step_and_draw(&mut app, &mut terminal, Control::SelectDown);
+        let (num_rows, text) = app.get_selected_rows_as_csv().unwrap().unwrap();
+        assert_eq!(num_rows, 3);
+        assert_eq!(text, "A1,B1\nA10,B10\nA11,B11\n");
+
+        // Plain movement goes back to single row selection
+        step_and_draw(&mut app, &mut terminal, Control::ScrollUp);
+        let (num_rows, text) = app.get_selected_rows_as_csv().unwrap().unwrap();
+        assert_eq!(num_rows, 1);
+        assert_eq!(text, "A10,B10\n");
+    }
+
+    #[test]
+    fn test_pipe_input() {
+        let mut app = App::new("tests/data/simple.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Pipe);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines.last().unwrap().trim_end(),
+            "Start csvlens with --exec to pipe to a command"
+        );
+        assert_eq!(app.pending_pipe, None);
+
+        app.set_pipe(Pipe::new("cat", false));
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        step_and_draw(&mut app, &mut terminal, Control::Pipe);
+        assert_eq!(app.pending_pipe.take(), Some("A2".to_owned()));
+
+        // Rows in the selection range go along with the header
+        step_and_draw(&mut app, &mut terminal, Control::SelectDown);
+        step_and_draw(&mut app, &mut terminal, Control::Pipe);
+        assert_eq!(
+            app.pending_pipe.take(),
+            Some("a,b\nA2,B2\nA3,B3\n".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_selection_sum() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(80, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::SelectDown);
+        step_and_draw(&mut app, &mut terminal, Control::SelectDown);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines.last().unwrap().trim_end(),
+            "stdin [Row 3/128, Col 1/10] [3 selected, sum 129, avg 43]"
+        );
+
+        // Sum of the current column
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollRight);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines.last().unwrap().trim_end(),
+            "stdin [Row 3/128, Col 3/10] [3 selected, sum 166, avg 55.3333]"
+        );
+
+        // Read again only once the range or column changes, or the values may
+        app.selection_sum.as_mut().unwrap().sum = Some((1.0, 1));
+        step_and_draw(&mut app, &mut terminal, Control::CenterColumn);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines.last().unwrap().contains("[3 selected, sum 1, avg 1]"));
+        step_and_draw(&mut app, &mut terminal, Control::ToggleGrid);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines
+            .last()
+            .unwrap()
+            .contains("[3 selected, sum 166, avg 55.3333]"));
+        step_and_draw(&mut app, &mut terminal, Control::ToggleGrid);
+
+        // Values that are not numbers are skipped
+        step_and_draw(&mut app, &mut terminal, Control::FilterColumns("NS".into()));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines.last().unwrap().trim_end(),
+            "stdin [Row 3/128, Col 1/1] [3 selected] [Filter \"NS\": 1/10 cols]"
+        );
+    }
+
+    #[test]
+    fn test_scroll_to_percent() {
+        let mut app = App::new("tests/data/simple.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ScrollToPercent(50));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("2501  │  A2501    B2501    │"));
+        assert_eq!(
+            lines.last().unwrap().trim_end(),
+            "stdin [Row 2501/5000, Col 1/2]"
+        );
+
+        // The last rows are shown at the bottom
+        step_and_draw(&mut app, &mut terminal, Control::ScrollToPercent(100));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[7].starts_with("5000  │  A5000    B5000    │"));
+        assert_eq!(
+            lines.last().unwrap().trim_end(),
+            "stdin [Row 5000/5000, Col 1/2]"
+        );
+    }
+
+    #[test]
+    fn test_filter_column_shorthand() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(90, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("EW|City|State".into()),
+        );
+        step_and_draw(&mut app, &mut terminal, Control::Filter("State:^W".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "──────────────────────────────────────────────────────────────────────────────────────────",
+            "       EW    City               State                                                     ",
+            "────┬────────────────────────────────────┬────────────────────────────────────────────────",
+            "3   │  W     Yakima             WA       │                                               ▐",
+            "5   │  W     Wisconsin Dells    WI       │                                                ",
+            "13  │  W     Williamson         WV       │                                                ",
+            "────┴────────────────────────────────────┴────────────────────────────────────────────────",
+            "stdin [Row 3/128, Col 1/3] [14/128 rows (filtered)] [Filter \"^W\" in State: 1/14] [Filter \"",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+        // Matches are only highlighted in the column
+        let buffer = terminal.backend().buffer().clone();
+        assert_eq!(buffer.get(32, 4).fg, Color::Rgb(200, 0, 0));
+        assert_eq!(buffer.get(7, 4).fg, Color::Reset);
+
+        // Not a column name, so a regex as usual
+        step_and_draw(&mut app, &mut terminal, Control::Filter("Town:W".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[7].contains("[0/128 rows (filtered)]"), "{}", lines[7]);
+    }
+
+    #[test]
+    fn test_reapply_filter() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::ReapplyFilter);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[9].trim_end(), "No filter to apply again");
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::Filter(":LatD < 30".into()),
+        );
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        step_and_draw(&mut app, &mut terminal, Control::ClearFilter);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        assert!(!app.rows_view.is_filter());
+
+        step_and_draw(&mut app, &mut terminal, Control::ReapplyFilter);
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        assert!(app.rows_view.is_filter());
+        assert_eq!(app.rows_view.num_filtered_rows(), Some(6));
+    }
+
+    #[test]
+    fn test_filter_no_rows() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Filter("Atlantis".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "      LatD    LatM    LatS    NS    LonD    LonM    LonS    ",
+            "───┬────────────────────────────────────────────────────────",
+            "   │                                                        ",
+            "   │                 No rows match 'Atlantis'               ",
+            "   │          Press & then Enter to clear the filter        ",
+            "   │                                                        ",
+            "   │                                                        ",
+            "───┴━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━────────────",
+            "stdin [Row -/128, Col 1/10] [0/128 rows (filtered)] [Filter ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_terminal_too_small() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(1, 1);
+        let mut terminal = Terminal::new(backend).unwrap();
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, vec!["T"]);
+
+        terminal.backend_mut().resize(30, 4);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        let expected = vec![
+            "                              ",
+            "                              ",
+            "      Terminal too small      ",
+            "                              ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        terminal.backend_mut().resize(30, 6);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "──────────────────────────────",
+            "      LatD    LatM    LatS    ",
+            "───┬──────────────────────────",
+            "2  │  42      52      48     ▐",
+            "───┴━━━━━━━━━━────────────────",
+            "stdin [Row 2/128, Col 1/10]   ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_set_find() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        app.set_find("Salt Lake").unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let selected = app.rows_view.selected().unwrap() as usize;
+        assert_eq!(app.rows_view.rows()[selected].record_num, 97);
+        assert!(!app.rows_view.is_filter());
+
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+        app.set_find("Atlantis").unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        assert_eq!(app.rows_view.rows_from(), 0);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines[9].trim_end(), "No rows match 'Atlantis'");
+    }
+
+    #[test]
+    fn test_show_empty_in_column() {
+        let mut app = App::new("tests/data/empty_cells.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 8);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        app.csv_table_state.set_cols_offset(1);
+        step_and_draw(&mut app, &mut terminal, Control::ShowEmptyInColumn);
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "      b      c                                              ",
+            "───┬──────────────┬─────────────────────────────────────────",
+            "1  │         x    │                                         ",
+            "2  │              │                                         ",
+            "   │              │                                         ",
+            "───┴──────────────┴────━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━",
+            "stdin [Row 1/3, Col 2/3] [2/3 rows (filtered)] [Filter \"\"b\" ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::ShowNonEmptyInColumn);
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[3].starts_with("3  │  z"), "{}", lines[3]);
+        assert!(lines[7].contains("[1/3 rows (filtered)]"), "{}", lines[7]);
+    }
+
+    #[test]
+    fn test_null_tokens() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "a,b\n1,NA\n2,\n3,x\n4,\"say \"\"hi\"\"\"\n").unwrap();
+        let mut config = csv::CsvConfig::new(file.path().to_str().unwrap());
+        config.null_tokens = vec!["NA".to_owned(), "say \"hi\"".to_owned()];
+        let mut app = App::with_config(config, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 9);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let buffer = terminal.backend().buffer().clone();
+        assert_eq!(buffer.get(11, 6).fg, Color::Rgb(100, 100, 100));
+        assert_eq!(buffer.get(11, 5).fg, Color::Reset);
+
+        app.set_show_empty_cells(true);
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[6].starts_with("4  │  4    ∅ "), "{}", lines[6]);
+
+        app.csv_table_state.set_cols_offset(1);
+        step_and_draw(&mut app, &mut terminal, Control::ShowEmptyInColumn);
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert!(lines[8].contains("[3/4 rows"), "{}", lines[8]);
+    }
+
+    #[test]
+    fn test_filter_expression() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("LatD|City".into()),
+        );
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::Filter(":LatD >= 47".into()),
+        );
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "       LatD    City                                         ",
+            "────┬───────────────────────┬───────────────────────────────",
+            "7   │  49      Winnipeg     │                              ▐",
+            "11  │  48      Williston    │                              ▐",
+            "18  │  47      Wenatchee    │                              ▐",
+            "34  │  49      Vancouver    │                               ",
+            "55  │  47      Tacoma       │                               ",
+            "────┴───────────────────────┴───────────────────────────────",
+            "stdin [Row 7/128, Col 1/2] [8/128 rows (filtered)] [Filter \"",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+
+        step_and_draw(&mut app, &mut terminal, Control::Filter(":Lat > 47".into()));
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines.last().unwrap().trim_end(),
+            "Invalid expression: Unknown column: Lat"
+        );
+        // The previous filter is still applied
+        assert_eq!(lines[3..8], expected[3..8]);
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::Filter(":LatD >= 47 AND (City ~ ^W OR City == Tacoma)".into()),
+        );
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        let expected = vec![
+            "7   │  49      Winnipeg     │                               ",
+            "11  │  48      Williston    │                               ",
+            "18  │  47      Wenatchee    │                               ",
+            "55  │  47      Tacoma       │                               ",
+            "    │                       │                               ",
+        ];
+        assert_eq!(lines[3..8], expected);
+    }
+
+    #[test]
+    fn test_selection_in_view_after_filter_change() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("City".into()),
+        );
+        step_and_draw(&mut app, &mut terminal, Control::Filter("^OH$".into()));
+        thread::sleep(time::Duration::from_millis(100));
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        for _ in 0..3 {
+            step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        }
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(
+            lines[6],
+            "65  │  Springfield     │                                   ▐"
+        );
+
+        // The selected record stays selected and in view without the filter
+        step_and_draw(&mut app, &mut terminal, Control::ClearFilter);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "       City                                                 ",
+            "────┬──────────────────┬────────────────────────────────────",
+            "62  │  Steubenville    │                                    ",
+            "63  │  Sterling        │                                   ▐",
+            "64  │  Staunton        │                                    ",
+            "65  │  Springfield     │                                    ",
+            "66  │  Springfield     │                                    ",
+            "────┴──────────────────┴────────────────────────────────────",
+            "stdin [Row 65/128, Col 1/1] [Filter \"City\": 1/10 cols]      ",
+        ];
+        let actual_buffer = terminal.backend().buffer().clone();
+        let lines = to_lines(&actual_buffer);
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_row_and_columns_filters_kept_independently() {
+        let mut app = App::new("tests/data/cities.csv", None, None, false).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(60, 10);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        let filter_rows = |app: &mut App, terminal: &mut Terminal<TestBackend>| {
+            step_and_draw(app, terminal, Control::Filter("^OH$".into()));
+            thread::sleep(time::Duration::from_millis(100));
+            step_and_draw(app, terminal, Control::Nothing);
+        };
+
+        // Setting a row filter keeps the columns filter
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("City|State".into()),
+        );
+        filter_rows(&mut app, &mut terminal);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "       City            State                                ",
+            "────┬───────────────────────────┬───────────────────────────",
+            "1   │  Youngstown      OH       │                          ▐",
+            "50  │  Toledo          OH       │                          ▐",
+            "62  │  Steubenville    OH       │                          ▐",
+            "65  │  Springfield     OH       │                          ▐",
+            "92  │  Sandusky        OH       │                           ",
+            "────┴───────────────────────────┴───────────────────────────",
+            "stdin [Row 1/128, Col 1/2] [6/128 rows (filtered)] [Filter \"",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Clearing the row filter keeps the columns filter
+        step_and_draw(&mut app, &mut terminal, Control::ClearFilter);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "      City               State                              ",
+            "───┬──────────────────────────────┬─────────────────────────",
+            "1  │  Youngstown         OH       │                        ▐",
+            "2  │  Yankton            SD       │                         ",
+            "3  │  Yakima             WA       │                         ",
+            "4  │  Worcester          MA       │                         ",
+            "5  │  Wisconsin Dells    WI       │                         ",
+            "───┴──────────────────────────────┴─────────────────────────",
+            "stdin [Row 1/128, Col 1/2] [Filter \"City|State\": 2/10 cols] ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Setting a columns filter keeps the row filter
+        filter_rows(&mut app, &mut terminal);
+        step_and_draw(
+            &mut app,
+            &mut terminal,
+            Control::FilterColumns("City".into()),
+        );
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "       City                                                 ",
+            "────┬──────────────────┬────────────────────────────────────",
+            "1   │  Youngstown      │                                   ▐",
+            "50  │  Toledo          │                                   ▐",
+            "62  │  Steubenville    │                                   ▐",
+            "65  │  Springfield     │                                   ▐",
+            "92  │  Sandusky        │                                    ",
+            "────┴──────────────────┴────────────────────────────────────",
+            "stdin [Row 1/128, Col 1/1] [6/128 rows (filtered)] [Filter \"",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Clearing the columns filter keeps the row filter
+        step_and_draw(&mut app, &mut terminal, Control::ClearColumnsFilter);
+        let expected = vec![
+            "────────────────────────────────────────────────────────────",
+            "       City            State                                ",
+            "────┬───────────────────────────┬───────────────────────────",
+            "1   │  Youngstown      OH       │                          ▐",
+            "50  │  Toledo          OH       │                          ▐",
+            "62  │  Steubenville    OH       │                          ▐",
+            "65  │  Springfield     OH       │                          ▐",
+            "92  │  Sandusky        OH       │                           ",
+            "────┴───────────────────────────┴────────────────━━━━━━━━━━━",
+            "stdin [Row 1/128, Col 9/10] [6/128 rows (filtered)] [Filter ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_tabs() {
+        let mut app = App::new("tests/data/small.csv", None, Some("a.csv".into()), false).unwrap();
+        let config = csv::CsvConfig::new("tests/data/cities.csv");
+        app.add_tab(config, Some("b.csv".into())).unwrap();
+        let config = csv::CsvConfig::new("tests/data/simple.csv");
+        app.add_tab(config, Some("c.csv".into())).unwrap();
+        thread::sleep(time::Duration::from_millis(100));
+
+        let backend = TestBackend::new(40, 7);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        step_and_draw(&mut app, &mut terminal, Control::Nothing);
+        let expected = vec![
+            " 1:a.csv  2:b.csv  3:c.csv ─────────────",
+            "      COL1     COL2                     ",
+            "───┬───────────────────┬────────────────",
+            "1  │  c1       v1      │                ",
+            "2  │  c2       v2      │                ",
+            "───┴───────────────────┴────────────────",
+            "a.csv [Row 1/2, Col 1/2]                ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Cycling wraps around
+        step_and_draw(&mut app, &mut terminal, Control::PrevTab);
+        step_and_draw(&mut app, &mut terminal, Control::ScrollDown);
+        step_and_draw(&mut app, &mut terminal, Control::NextTab);
+        step_and_draw(&mut app, &mut terminal, Control::NextTab);
+        let expected = vec![
+            " 1:a.csv  2:b.csv  3:c.csv ─────────────",
+            "      LatD    LatM    LatS    NS    …   ",
+            "───┬────────────────────────────────────",
+            "1  │  41      5       59      N     …  ▐",
+            "2  │  42      52      48      N     …   ",
+            "───┴━━━━━━━━━━━━━━━━━━──────────────────",
+            "b.csv [Row 1/128, Col 1/10]             ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Each tab keeps its own state
+        step_and_draw(&mut app, &mut terminal, Control::NextTab);
+        let expected = vec![
+            " 1:a.csv  2:b.csv  3:c.csv ─────────────",
+            "      a     b                           ",
+            "───┬──────────────┬─────────────────────",
+            "1  │  A1    B1    │                    ▐",
+            "2  │  A2    B2    │                     ",
+            "───┴──────────────┴─────────────────────",
+            "c.csv [Row 2/5000, Col 1/2]             ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+
+        // Closing the last tab activates the first one
+        step_and_draw(&mut app, &mut terminal, Control::CloseTab);
+        let expected = vec![
+            " 1:a.csv  2:b.csv ──────────────────────",
+            "      COL1     COL2                     ",
+            "───┬───────────────────┬────────────────",
+            "1  │  c1       v1      │                ",
+            "2  │  c2       v2      │                ",
+            "───┴───────────────────┴────────────────",
+            "a.csv [Row 1/2, Col 1/2]                ",
+        ];
+        let lines = to_lines(&terminal.backend().buffer().clone());
+        assert_eq!(lines, expected);
+    }
+
     #[test]
     fn test_extra_fields_in_some_rows() {
         // Test getting column widths should not fail on data with bad formatting (some rows having
@@ -0,0 +1,190 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Keep the cache small by forgetting the least recently used files
+const MAX_ENTRIES: usize = 200;
+
+/// Directory for csvlens' own files, i.e. $XDG_CONFIG_HOME/csvlens or
+/// ~/.config/csvlens
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("csvlens"))
+}
+
+/// Values remembered for each file, stored one file per line as the value
+/// followed by the absolute path, e.g. `59 /home/me/foo.csv` for the
+/// delimiter. The most recently used file is last.
+pub struct FileCache<T> {
+    path: PathBuf,
+    entries: Vec<(PathBuf, T)>,
+}
+
+/// Value that can be stored in a line of a FileCache, i.e. without spaces
+pub trait CacheValue: Sized + Copy {
+    fn parse(s: &str) -> Option<Self>;
+    fn format(&self) -> String;
+}
+
+/// Last used delimiter of each file
+pub type DelimiterCache = FileCache<u8>;
+
+impl CacheValue for u8 {
+    fn parse(s: &str) -> Option<Self> {
+        s.parse().ok()
+    }
+
+    fn format(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl DelimiterCache {
+    pub fn default_path() -> Option<PathBuf> {
+        Some(config_dir()?.join("delimiters"))
+    }
+}
+
+/// Last scroll position of each file
+pub type PositionCache = FileCache<Position>;
+
+/// Index of the first row shown and the selected row within the shown rows,
+/// stored as e.g. `120,5`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub rows_from: u64,
+    pub selected: u64,
+}
+
+impl CacheValue for Position {
+    fn parse(s: &str) -> Option<Self> {
+        let (rows_from, selected) = s.split_once(',')?;
+        Some(Position {
+            rows_from: rows_from.parse().ok()?,
+            selected: selected.parse().ok()?,
+        })
+    }
+
+    fn format(&self) -> String {
+        format!("{},{}", self.rows_from, self.selected)
+    }
+}
+
+impl PositionCache {
+    pub fn default_path() -> Option<PathBuf> {
+        Some(config_dir()?.join("positions"))
+    }
+}
+
+impl<T: CacheValue> FileCache<T> {
+    pub fn load(path: &Path) -> FileCache<T> {
+        // A missing or corrupted cache is the same as an empty one
+        let entries = fs::read_to_string(path)
+            .map(|s| s.lines().filter_map(parse_line).collect())
+            .unwrap_or_default();
+        FileCache {
+            path: path.to_owned(),
+            entries,
+        }
+    }
+
+    pub fn get(&self, filename: &str) -> Option<T> {
+        let key = cache_key(filename)?;
+        self.entries
+            .iter()
+            .find(|(path, _)| *path == key)
+            .map(|(_, value)| *value)
+    }
+
+    pub fn set(&mut self, filename: &str, value: T) {
+        if let Some(key) = cache_key(filename) {
+            self.entries.retain(|(path, _)| *path != key);
+            self.entries.push((key, value));
+            if self.entries.len() > MAX_ENTRIES {
+                let excess = self.entries.len() - MAX_ENTRIES;
+                self.entries.drain(..excess);
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .context(format!("Failed to create directory: {}", dir.display()))?;
+        }
+        let mut content = String::new();
+        for (path, value) in &self.entries {
+            content.push_str(&format!("{} {}\n", value.format(), path.display()));
+        }
+        fs::write(&self.path, content)
+            .context(format!("Failed to write cache: {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+fn parse_line<T: CacheValue>(line: &str) -> Option<(PathBuf, T)> {
+    let (value, path) = line.split_once(' ')?;
+    Some((PathBuf::from(path), T::parse(value)?))
+}
+
+fn cache_key(filename: &str) -> Option<PathBuf> {
+    // Only remember regular files, not e.g. pipes from process substitution
+    let path = fs::canonicalize(filename).ok()?;
+    if !path.is_file() || path.to_str()?.contains('\n') {
+        return None;
+    }
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("csvlens").join("delimiters");
+
+        let mut cache = DelimiterCache::load(&path);
+        assert_eq!(cache.get("tests/data/simple.csv"), None);
+        cache.set("tests/data/simple.csv", b';');
+        cache.set("tests/data/cities.csv", b'\t');
+        cache.set("./tests/data/simple.csv", b'|');
+        cache.set("tests/data/does_not_exist.csv", b';');
+        cache.save().unwrap();
+
+        let cache = DelimiterCache::load(&path);
+        assert_eq!(cache.entries.len(), 2);
+        assert_eq!(cache.get("tests/data/simple.csv"), Some(b'|'));
+        assert_eq!(cache.get("tests/data/cities.csv"), Some(b'\t'));
+        assert_eq!(cache.get("tests/data/does_not_exist.csv"), None);
+    }
+
+    #[test]
+    fn test_positions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("positions");
+
+        let mut cache = PositionCache::load(&path);
+        let position = Position {
+            rows_from: 120,
+            selected: 5,
+        };
+        cache.set("tests/data/simple.csv", position);
+        cache.save().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!(
+                "120,5 {}\n",
+                fs::canonicalize("tests/data/simple.csv").unwrap().display()
+            )
+        );
+
+        let cache = PositionCache::load(&path);
+        assert_eq!(cache.get("tests/data/simple.csv"), Some(position));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::cache::config_dir;
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Named columns filter, e.g. to switch between views of a wide table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnGroup {
+    pub name: String,
+    /// Regex as typed for the columns filter, e.g. `^(amount|invoice)`
+    pub pattern: String,
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("column_groups"))
+}
+
+/// Read the groups from a file with one group per line as the name followed
+/// by the regex, e.g. `billing ^(amount|invoice)`. Blank lines and lines
+/// starting with `#` are skipped. A missing file has no groups.
+pub fn load(path: &Path) -> Result<Vec<ColumnGroup>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => {
+            return Err(e).context(format!("Failed to read file: {}", path.display()));
+        }
+    };
+    let mut groups = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once(char::is_whitespace) {
+            Some((name, pattern)) if !pattern.trim().is_empty() => groups.push(ColumnGroup {
+                name: name.to_owned(),
+                pattern: pattern.trim().to_owned(),
+            }),
+            _ => bail!(
+                "Column group without a regex on line {} of {}",
+                i + 1,
+                path.display()
+            ),
+        }
+    }
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("column_groups");
+        assert_eq!(load(&path).unwrap(), vec![]);
+
+        fs::write(
+            &path,
+            "# views\nbilling ^(amount|invoice)\n\nidentity  name | id \n",
+        )
+        .unwrap();
+        let group = |name: &str, pattern: &str| ColumnGroup {
+            name: name.to_owned(),
+            pattern: pattern.to_owned(),
+        };
+        assert_eq!(
+            load(&path).unwrap(),
+            vec![
+                group("billing", "^(amount|invoice)"),
+                group("identity", "name | id")
+            ]
+        );
+
+        fs::write(&path, "billing\n").unwrap();
+        assert!(load(&path).is_err());
+    }
+}
@@ -1,12 +1,19 @@
 extern crate csv;
 
-use anyhow::Result;
-use csv::{Position, Reader, ReaderBuilder};
-use std::cmp::max;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+
+use anyhow::{anyhow, bail, Context, Result};
+use csv::{ByteRecord, Position, Reader, ReaderBuilder, WriterBuilder};
+use std::cmp::{max, min};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
+use tempfile::NamedTempFile;
 
 fn string_record_to_vec(record: &csv::StringRecord) -> Vec<String> {
     let mut string_vec = Vec::new();
@@ -16,9 +23,662 @@ fn string_record_to_vec(record: &csv::StringRecord) -> Vec<String> {
     string_vec
 }
 
+/// Split a record as it is in the file into fields, keeping quotes and escape
+/// characters. Line terminators are left out, including the end of a CRLF
+/// before the record and blank lines, which the reader skips.
+pub fn split_raw_fields(record: &str, config: &CsvConfig) -> Vec<String> {
+    #[derive(PartialEq)]
+    enum State {
+        Start,
+        Unquoted,
+        Quoted,
+        AfterQuote,
+    }
+    let record = record.trim_start_matches(['\r', '\n']);
+    let record = record
+        .strip_suffix("\r\n")
+        .or_else(|| record.strip_suffix('\n'))
+        .or_else(|| record.strip_suffix('\r'))
+        .unwrap_or(record);
+    let (delimiter, quote) = (config.delimiter as char, config.quote as char);
+    let escape = config.escape.map(|c| c as char);
+
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut state = State::Start;
+    let mut chars = record.chars();
+    while let Some(c) = chars.next() {
+        state = match state {
+            State::Quoted if Some(c) == escape => {
+                field.push(c);
+                field.extend(chars.next());
+                continue;
+            }
+            State::Quoted if c == quote => State::AfterQuote,
+            State::Quoted => State::Quoted,
+            State::Start if config.quoting && c == quote => State::Quoted,
+            State::AfterQuote if c == quote => State::Quoted,
+            _ if c == delimiter => {
+                fields.push(std::mem::take(&mut field));
+                state = State::Start;
+                continue;
+            }
+            _ => State::Unquoted,
+        };
+        field.push(c);
+    }
+    fields.push(field);
+    fields
+}
+
+/// Like `read_until(b'\n', line)`, but also ending lines at a lone carriage
+/// return as the csv reader does. The line ending is kept.
+fn read_line<R: BufRead>(reader: &mut R, line: &mut Vec<u8>) -> io::Result<usize> {
+    let start = line.len();
+    loop {
+        let (line_ending, used) = {
+            let available = reader.fill_buf()?;
+            if available.is_empty() {
+                return Ok(line.len() - start);
+            }
+            match available.iter().position(|&c| c == b'\n' || c == b'\r') {
+                Some(i) => {
+                    line.extend_from_slice(&available[..=i]);
+                    (Some(available[i]), i + 1)
+                }
+                None => {
+                    line.extend_from_slice(available);
+                    (None, available.len())
+                }
+            }
+        };
+        reader.consume(used);
+        match line_ending {
+            Some(b'\r') => {
+                if reader.fill_buf()?.first() == Some(&b'\n') {
+                    line.push(b'\n');
+                    reader.consume(1);
+                }
+                return Ok(line.len() - start);
+            }
+            Some(_) => return Ok(line.len() - start),
+            None => {}
+        }
+    }
+}
+
+/// Convert data with fields separated by runs of spaces or tabs into comma
+/// separated values, skipping blank lines
+pub fn convert_whitespace_delimited<R: Read, W: Write>(input: R, output: W) -> Result<()> {
+    let mut writer = WriterBuilder::new().flexible(true).from_writer(output);
+    let mut buf_reader = BufReader::new(input);
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if read_line(&mut buf_reader, &mut line)? == 0 {
+            break;
+        }
+        let fields: Vec<&[u8]> = line
+            .split(|c| c.is_ascii_whitespace())
+            .filter(|field| !field.is_empty())
+            .collect();
+        if fields.is_empty() {
+            continue;
+        }
+        writer.write_record(&fields)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Text with columns at fixed character positions rather than delimited,
+/// which is converted to CSV to be viewed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FixedWidth {
+    /// Path of the text to convert it again, e.g. with other columns
+    pub source: String,
+    /// Character positions where the columns start, the first being 0
+    pub starts: Vec<usize>,
+    /// Number of rows after the header left out, as with --skip-rows
+    pub skip_rows: usize,
+}
+
+/// Number of lines of fixed-width text used to guess its columns
+const FIXED_WIDTH_SAMPLE: usize = 100;
+
+/// Guess where the columns of fixed-width text start, which is after every
+/// run of positions that are blank in all of the lines. Tabs count as one
+/// character like any other.
+pub fn guess_column_starts<S: AsRef<str>>(lines: &[S]) -> Vec<usize> {
+    let mut blank: Vec<bool> = vec![];
+    for line in lines {
+        for (i, c) in line.as_ref().chars().enumerate() {
+            if i >= blank.len() {
+                blank.resize(i + 1, true);
+            }
+            if !c.is_whitespace() {
+                blank[i] = false;
+            }
+        }
+    }
+    let mut starts = vec![0];
+    for i in 1..blank.len() {
+        if blank[i - 1] && !blank[i] && blank[..i].iter().any(|b| !b) {
+            starts.push(i);
+        }
+    }
+    starts
+}
+
+/// Parse column starts like `0,8,20`, where the first column always starts at
+/// 0 even if not given
+pub fn parse_column_starts(s: &str) -> Result<Vec<usize>> {
+    let invalid = || {
+        anyhow!(
+            "Column starts should be increasing numbers like 0,8,20, got {}",
+            s
+        )
+    };
+    let mut starts = vec![0];
+    for part in s.split(',') {
+        let start: usize = part.trim().parse().map_err(|_| invalid())?;
+        match starts.last() {
+            Some(&last) if start == 0 && last == 0 => {}
+            Some(&last) if start <= last => return Err(invalid()),
+            _ => starts.push(start),
+        }
+    }
+    Ok(starts)
+}
+
+/// Values of a line of fixed-width text at the column starts, without the
+/// spaces around them
+fn split_fixed_width(line: &str, starts: &[usize]) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(chars.len());
+            let field: String = chars[min(start, chars.len())..min(end, chars.len())]
+                .iter()
+                .collect();
+            field.trim().to_owned()
+        })
+        .collect()
+}
+
+/// Convert fixed-width text into comma separated values, skipping blank lines
+/// and `skip_rows` lines after the header. The columns are guessed from the
+/// first lines unless given, and the column starts used are returned.
+pub fn convert_fixed_width<R: Read, W: Write>(
+    input: R,
+    output: W,
+    starts: Option<&[usize]>,
+    skip_rows: usize,
+) -> Result<Vec<usize>> {
+    let mut writer = WriterBuilder::new().flexible(true).from_writer(output);
+    let mut buf_reader = BufReader::new(input);
+    let mut next_line = || -> Result<Option<String>> {
+        let mut line = vec![];
+        loop {
+            line.clear();
+            if read_line(&mut buf_reader, &mut line)? == 0 {
+                return Ok(None);
+            }
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+            if !line.trim().is_empty() {
+                return Ok(Some(line.to_owned()));
+            }
+        }
+    };
+
+    let mut sample = vec![];
+    let mut num_lines = 0;
+    while sample.len() < FIXED_WIDTH_SAMPLE {
+        match next_line()? {
+            Some(line) => {
+                num_lines += 1;
+                if num_lines == 1 || num_lines > skip_rows + 1 {
+                    sample.push(line);
+                }
+            }
+            None => break,
+        }
+    }
+    let starts = match starts {
+        Some(starts) => starts.to_vec(),
+        None => guess_column_starts(&sample),
+    };
+    for line in &sample {
+        writer.write_record(split_fixed_width(line, &starts))?;
+    }
+    while let Some(line) = next_line()? {
+        writer.write_record(split_fixed_width(&line, &starts))?;
+    }
+    writer.flush()?;
+    Ok(starts)
+}
+
+/// Convert fixed-width text into a temporary file in the background, e.g.
+/// again with other column starts while the last conversion is still shown
+pub struct FixedWidthConverter {
+    source: String,
+    starts: Option<Vec<usize>>,
+    skip_rows: usize,
+    internal: Arc<Mutex<ConverterInternalState>>,
+}
+
+impl FixedWidthConverter {
+    pub fn new(source: &str, starts: Option<Vec<usize>>, skip_rows: usize) -> Result<Self> {
+        let input = File::open(source).context(format!("Failed to open file: {}", source))?;
+        let mut file = NamedTempFile::new()?;
+        let internal = Arc::new(Mutex::new(ConverterInternalState {
+            result: None,
+            should_terminate: false,
+        }));
+
+        let _m = internal.clone();
+        let _starts = starts.clone();
+        let _handle = thread::spawn(move || {
+            let output = TerminableWriter {
+                inner: file.as_file_mut(),
+                internal: _m.clone(),
+            };
+            let result = convert_fixed_width(input, output, _starts.as_deref(), skip_rows);
+            _m.lock().unwrap().result = Some(result.map(|starts| (file, starts)));
+        });
+
+        Ok(FixedWidthConverter {
+            source: source.to_owned(),
+            starts,
+            skip_rows,
+            internal,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn skip_rows(&self) -> usize {
+        self.skip_rows
+    }
+
+    /// Column starts converted with, or None if guessed
+    pub fn starts(&self) -> Option<&[usize]> {
+        self.starts.as_deref()
+    }
+
+    /// Converted file and the column starts used, once done
+    pub fn take_result(&self) -> Option<Result<(NamedTempFile, Vec<usize>)>> {
+        self.internal.lock().unwrap().result.take()
+    }
+}
+
+impl Drop for FixedWidthConverter {
+    fn drop(&mut self) {
+        self.internal.lock().unwrap().should_terminate = true;
+    }
+}
+
+struct ConverterInternalState {
+    result: Option<Result<(NamedTempFile, Vec<usize>)>>,
+    should_terminate: bool,
+}
+
+// Fails writing once the conversion isn't needed anymore, which stops it
+struct TerminableWriter<W> {
+    inner: W,
+    internal: Arc<Mutex<ConverterInternalState>>,
+}
+
+impl<W: Write> Write for TerminableWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.internal.lock().unwrap().should_terminate {
+            return Err(io::Error::other("Conversion terminated"));
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Columns guessed for the file if it seems to be fixed-width text, i.e. if
+/// nearly all sampled records are a single field but are lined up in columns
+pub fn suggest_fixed_width(config: &CsvConfig) -> Result<Option<Vec<usize>>> {
+    let mut reader = config
+        .reader_builder()
+        .has_headers(false)
+        .from_path(config.path.as_str())?;
+    let records = reader.byte_records().take(FIXED_WIDTH_SAMPLE);
+    let records = records.collect::<csv::Result<Vec<ByteRecord>>>()?;
+    if records.len() < 2 || consistent_num_fields(&records) != Some(1) {
+        return Ok(None);
+    }
+    let lines: Vec<String> = BufReader::new(File::open(config.path.as_str())?)
+        .lines()
+        .take(FIXED_WIDTH_SAMPLE)
+        .collect::<io::Result<Vec<String>>>()?
+        .into_iter()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let starts = guess_column_starts(&lines);
+    Ok(if starts.len() > 1 { Some(starts) } else { None })
+}
+
+/// Copy the input without its first `n` lines, e.g. to drop title lines
+/// before the header
+pub fn skip_lines<R: Read, W: Write>(input: R, mut output: W, n: usize) -> Result<()> {
+    let mut buf_reader = BufReader::new(input);
+    let mut line = vec![];
+    for _ in 0..n {
+        line.clear();
+        if read_line(&mut buf_reader, &mut line)? == 0 {
+            break;
+        }
+    }
+    io::copy(&mut buf_reader, &mut output)?;
+    Ok(())
+}
+
+/// Copy the file without the first `n` records after the header. Records are
+/// skipped as parsed, so that line breaks in quoted fields are not counted.
+pub fn skip_records<W: Write>(config: &CsvConfig, mut output: W, n: usize) -> Result<()> {
+    let mut reader = config.new_reader()?;
+    reader.headers()?;
+    let header_end = reader.position().byte();
+    let mut records_start = header_end;
+    let mut record = csv::ByteRecord::new();
+    for _ in 0..n {
+        if !reader.read_byte_record(&mut record)? {
+            break;
+        }
+        records_start = reader.position().byte();
+    }
+
+    // The position is just past the record, before the rest of its line break
+    let mut input = File::open(config.filename())?;
+    io::copy(&mut (&mut input).take(header_end), &mut output)?;
+    input.seek(SeekFrom::Start(records_start))?;
+    io::copy(&mut input, &mut output)?;
+    Ok(())
+}
+
+/// First or last records to keep, like head and tail do with lines
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordSlice {
+    Head(usize),
+    Tail(usize),
+}
+
+/// Keep only the first or last `n` records after the header, reading no
+/// further than needed for the first ones
+pub fn slice_records<W: Write>(
+    config: &CsvConfig,
+    mut output: W,
+    slice: RecordSlice,
+) -> Result<()> {
+    let mut reader = config.new_reader()?;
+    reader.headers()?;
+    let header_end = reader.position().byte();
+    let mut record = csv::ByteRecord::new();
+    let mut input = File::open(config.filename())?;
+    match slice {
+        RecordSlice::Head(n) => {
+            for _ in 0..n {
+                if !reader.read_byte_record(&mut record)? {
+                    break;
+                }
+            }
+            // The position is just past the record, in or before its line
+            // break, so the rest of the line break is copied too
+            let records_end = reader.position().byte();
+            io::copy(&mut (&mut input).take(records_end), &mut output)?;
+            let mut line_break = vec![];
+            input.take(2).read_to_end(&mut line_break)?;
+            let len = match line_break.as_slice() {
+                [b'\r', b'\n'] => 2,
+                [b'\r' | b'\n', ..] => 1,
+                _ => 0,
+            };
+            output.write_all(&line_break[..len])?;
+        }
+        RecordSlice::Tail(n) => {
+            // Ends of the last records read (and of the one before them)
+            let mut ends = VecDeque::from(vec![header_end]);
+            while reader.read_byte_record(&mut record)? {
+                ends.push_back(reader.position().byte());
+                if ends.len() > n + 1 {
+                    ends.pop_front();
+                }
+            }
+            io::copy(&mut (&mut input).take(header_end), &mut output)?;
+            input.seek(SeekFrom::Start(ends[0]))?;
+            io::copy(&mut input, &mut output)?;
+        }
+    }
+    Ok(())
+}
+
+/// Line ending used in a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    /// Carriage return alone, e.g. from old Mac applications
+    Cr,
+    /// More than one kind
+    Mixed,
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+            LineEnding::Cr => "CR",
+            LineEnding::Mixed => "mixed",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Number of line endings of each kind, counted a chunk at a time
+#[derive(Default)]
+struct LineEndingCounts {
+    lf: usize,
+    crlf: usize,
+    cr: usize,
+    // A carriage return at the end of the last chunk may be part of CRLF
+    pending_cr: bool,
+}
+
+impl LineEndingCounts {
+    fn update(&mut self, bytes: &[u8]) {
+        for &c in bytes {
+            match (self.pending_cr, c) {
+                (true, b'\n') => self.crlf += 1,
+                (true, _) => self.cr += 1,
+                (false, b'\n') => self.lf += 1,
+                _ => {}
+            }
+            self.pending_cr = c == b'\r';
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.pending_cr {
+            self.cr += 1;
+            self.pending_cr = false;
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.lf + self.crlf + self.cr
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        match (self.lf > 0, self.crlf > 0, self.cr > 0) {
+            (false, false, false) => None,
+            (true, false, false) => Some(LineEnding::Lf),
+            (false, true, false) => Some(LineEnding::Crlf),
+            (false, false, true) => Some(LineEnding::Cr),
+            _ => Some(LineEnding::Mixed),
+        }
+    }
+}
+
+// Number of bytes to look at for detecting the line ending
+const LINE_ENDING_DETECTION_SAMPLE: u64 = 64 * 1024;
+
+/// Detect the line ending from the start of the file, or None if it is a
+/// single line. Parsing accepts any of them, so this is only informational.
+pub fn detect_line_ending(path: &str) -> Result<Option<LineEnding>> {
+    let mut sample = vec![];
+    File::open(path)?
+        .take(LINE_ENDING_DETECTION_SAMPLE)
+        .read_to_end(&mut sample)?;
+    let mut counts = LineEndingCounts::default();
+    counts.update(&sample);
+    counts.finish();
+    Ok(counts.line_ending())
+}
+
+/// Line of the header, either given or detected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeaderLine {
+    Auto,
+    Line(usize),
+}
+
+impl FromStr for HeaderLine {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(HeaderLine::Auto);
+        }
+        match s.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(HeaderLine::Line(n)),
+            _ => bail!(
+                "Header line should be a line number starting from 1 or auto, got {}",
+                s
+            ),
+        }
+    }
+}
+
+// Number of records to look at for detecting the header line
+const HEADER_DETECTION_SAMPLE: usize = 100;
+
+/// Guess the line of the header when there are preamble lines (e.g. a title)
+/// before it, as the first record with as many fields as most records. Blank
+/// lines right before the header are counted as part of it, which doesn't
+/// matter since blank lines are skipped when reading.
+pub fn detect_header_line(config: &CsvConfig) -> Result<usize> {
+    let mut reader = config
+        .reader_builder()
+        .has_headers(false)
+        .from_path(config.path.as_str())?;
+    let mut records = vec![];
+    for record in reader.byte_records().take(HEADER_DETECTION_SAMPLE) {
+        let record = record?;
+        let line = record.position().map_or(1, |pos| pos.line() as usize);
+        records.push((line, record.len()));
+    }
+
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for (_, num_fields) in records.iter() {
+        *counts.entry(*num_fields).or_insert(0) += 1;
+    }
+    // Ties go to the wider records since preamble lines are usually narrow
+    let mode = counts
+        .into_iter()
+        .max_by_key(|&(num_fields, count)| (count, num_fields))
+        .map(|(num_fields, _)| num_fields);
+    let line = records
+        .iter()
+        .find(|(_, num_fields)| Some(*num_fields) == mode)
+        .map_or(1, |(line, _)| *line);
+    Ok(line)
+}
+
+// Number of records to look at for suggesting another delimiter
+const DELIMITER_DETECTION_SAMPLE: usize = 100;
+
+// Delimiters suggested when the values look like they are split by them
+const CANDIDATE_DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// Another delimiter the file seems to be delimited by, e.g. `;` for a file
+/// read with `,`. It's suggested when most of the sampled records have values
+/// containing it and splitting by it gives more fields in nearly all records.
+pub fn suggest_delimiter(config: &CsvConfig) -> Result<Option<u8>> {
+    let sample = |delimiter: u8| -> Result<Vec<ByteRecord>> {
+        let mut reader = config
+            .reader_builder()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .from_path(config.path.as_str())?;
+        let records = reader.byte_records().take(DELIMITER_DETECTION_SAMPLE);
+        Ok(records.collect::<csv::Result<Vec<ByteRecord>>>()?)
+    };
+    let records = sample(config.delimiter)?;
+    let num_fields = consistent_num_fields(&records).unwrap_or(1);
+    let mut suggested: Option<(u8, usize)> = None;
+    for delimiter in CANDIDATE_DELIMITERS {
+        if delimiter == config.delimiter {
+            continue;
+        }
+        let containing = records
+            .iter()
+            .filter(|record| record.iter().any(|field| field.contains(&delimiter)))
+            .count();
+        if containing == 0 || containing * 2 < records.len() {
+            continue;
+        }
+        if let Some(n) = consistent_num_fields(&sample(delimiter)?) {
+            if n > num_fields && !matches!(suggested, Some((_, m)) if m >= n) {
+                suggested = Some((delimiter, n));
+            }
+        }
+    }
+    Ok(suggested.map(|(delimiter, _)| delimiter))
+}
+
+/// Number of fields of at least 90% of the records, if any
+fn consistent_num_fields(records: &[ByteRecord]) -> Option<usize> {
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for record in records {
+        *counts.entry(record.len()).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .find(|&(_, count)| count * 10 >= records.len() * 9)
+        .map(|(num_fields, _)| num_fields)
+}
+
+#[derive(Clone)]
 pub struct CsvConfig {
     path: String,
     pub delimiter: u8,
+    pub quoting: bool,
+    pub quote: u8,
+    /// Escape character for quotes, otherwise quotes are escaped by doubling
+    pub escape: Option<u8>,
+    /// Number of bytes to sample for the approximate total, otherwise all
+    /// lines are counted
+    pub approx_sample_bytes: Option<u64>,
+    /// Values that stand for a missing value, e.g. NA or NULL
+    pub null_tokens: Vec<String>,
+    /// Fixed-width text the file was converted from, if any
+    pub fixed_width: Option<FixedWidth>,
+    /// Number of rows searched in the background before the rows found among
+    /// them are shown
+    pub find_batch_rows: usize,
 }
 
 impl CsvConfig {
@@ -26,29 +686,110 @@ impl CsvConfig {
         CsvConfig {
             path: path.to_string(),
             delimiter: b',',
+            quoting: true,
+            quote: b'"',
+            escape: None,
+            approx_sample_bytes: None,
+            null_tokens: vec![],
+            fixed_width: None,
+            find_batch_rows: 1000,
         }
     }
 
+    pub fn is_null(&self, value: &str) -> bool {
+        self.null_tokens.iter().any(|token| token == value)
+    }
+
     pub fn new_reader(&self) -> Result<Reader<File>> {
-        let reader = ReaderBuilder::new()
+        let reader = self.reader_builder().from_path(self.path.as_str())?;
+        Ok(reader)
+    }
+
+    fn reader_builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
             .flexible(true)
             .delimiter(self.delimiter)
-            .from_path(self.path.as_str())?;
-        Ok(reader)
+            .quoting(self.quoting)
+            .quote(self.quote)
+            .escape(self.escape)
+            .double_quote(self.escape.is_none());
+        builder
     }
 
     pub fn filename(&self) -> &str {
         self.path.as_str()
     }
+
+    pub fn set_path(&mut self, path: &str) {
+        self.path = path.to_string();
+    }
+}
+
+/// File read by CsvLensReader, which seeks around it to get rows. Built with
+/// the `mmap` feature, regular files are mapped into memory instead of read,
+/// which is faster for scanning between positions in very large files.
+enum Source {
+    File(File),
+    #[cfg(feature = "mmap")]
+    Mmap(io::Cursor<Mmap>),
+}
+
+impl Source {
+    fn open(path: &str) -> io::Result<Source> {
+        let file = File::open(path)?;
+        #[cfg(feature = "mmap")]
+        if let Some(mmap) = Source::map(&file) {
+            return Ok(Source::Mmap(io::Cursor::new(mmap)));
+        }
+        Ok(Source::File(file))
+    }
+
+    /// Map the file, or none if it should be read as usual instead, e.g. if
+    /// it is empty or not a regular file
+    #[cfg(feature = "mmap")]
+    fn map(file: &File) -> Option<Mmap> {
+        let metadata = file.metadata().ok()?;
+        if !metadata.is_file() || metadata.len() == 0 {
+            return None;
+        }
+        // The file must not be truncated while mapped, since reading past its
+        // new end would crash, but it can be replaced, e.g. by an edit that
+        // writes a new file and renames it over this one
+        unsafe { Mmap::map(file).ok() }
+    }
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::File(file) => file.read(buf),
+            #[cfg(feature = "mmap")]
+            Source::Mmap(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Source::File(file) => file.seek(pos),
+            #[cfg(feature = "mmap")]
+            Source::Mmap(cursor) => cursor.seek(pos),
+        }
+    }
 }
 
 pub struct CsvLensReader {
-    reader: Reader<File>,
+    config: Arc<CsvConfig>,
+    reader: Reader<Source>,
     pub headers: Vec<String>,
     internal: Arc<Mutex<ReaderInternalState>>,
+    // Indices out of range when last getting rows for indices
+    num_skipped: u64,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Row {
     pub record_num: usize,
     pub fields: Vec<String>,
@@ -69,33 +810,115 @@ impl Row {
 
 impl CsvLensReader {
     pub fn new(config: Arc<CsvConfig>) -> Result<Self> {
-        let mut reader = config.new_reader()?;
+        let source = Source::open(config.filename())?;
+        Self::with_source(config, source)
+    }
+
+    fn with_source(config: Arc<CsvConfig>, source: Source) -> Result<Self> {
+        let mut reader = config.reader_builder().from_reader(source);
         let headers_record = reader.headers().unwrap();
         let headers = string_record_to_vec(headers_record);
 
-        let (m_internal, _handle) = ReaderInternalState::init_internal(config);
+        let (m_internal, _handle) = ReaderInternalState::init_internal(config.clone());
 
         let reader = Self {
+            config,
             reader,
             headers,
             internal: m_internal,
+            num_skipped: 0,
         };
         Ok(reader)
     }
 
     pub fn get_rows(&mut self, rows_from: u64, num_rows: u64) -> Result<Vec<Row>> {
         let indices: Vec<u64> = (rows_from..rows_from + num_rows).collect();
-        self.get_rows_impl(&indices).map(|x| x.0)
+        self.get_rows_impl(&indices, false).map(|x| x.0)
     }
 
+    /// Rows at the given record indices in file order. Indices out of range,
+    /// e.g. stale ones kept from before the file was reloaded, are skipped.
     pub fn get_rows_for_indices(&mut self, indices: &[u64]) -> Result<Vec<Row>> {
-        self.get_rows_impl(indices).map(|x| x.0)
+        let (rows, stats) = self.get_rows_impl(indices, false)?;
+        self.num_skipped = stats.num_skipped;
+        Ok(rows)
+    }
+
+    /// Like get_rows_for_indices, but with the fields exactly as they are in
+    /// the file, e.g. including quotes
+    pub fn get_raw_rows_for_indices(&mut self, indices: &[u64]) -> Result<Vec<Row>> {
+        let (rows, stats) = self.get_rows_impl(indices, true)?;
+        self.num_skipped = stats.num_skipped;
+        Ok(rows)
     }
 
-    fn get_rows_impl(&mut self, indices: &[u64]) -> Result<(Vec<Row>, GetRowsStats)> {
+    /// Number of indices skipped as out of range when last getting rows for
+    /// indices
+    pub fn num_skipped(&self) -> u64 {
+        self.num_skipped
+    }
+
+    /// Rows starting at the byte offset of the file, and the offset to pass to
+    /// get the rows after them, e.g. to resume at a bookmarked position
+    /// without knowing the row index. A record that starts before the offset,
+    /// i.e. one with the offset in the middle of it, is skipped, so that rows
+    /// start at the next record boundary. Rows keep their record numbers,
+    /// which are found by scanning from the closest indexed position before
+    /// the offset rather than from the start of the file. If there are no
+    /// more rows, e.g. at the end of a file that is still being appended to,
+    /// no rows and an offset at or after the given one are returned.
+    #[allow(dead_code)]
+    pub fn get_rows_from_offset(&mut self, offset: u64, num_rows: u64) -> Result<(Vec<Row>, u64)> {
+        let pos_table = self.get_pos_table();
+        let pos = pos_table
+            .iter()
+            .take_while(|pos| pos.byte() <= offset)
+            .last()
+            .cloned()
+            .unwrap_or_else(Position::new);
+        self.reader.seek(pos)?;
+
+        let mut rows = vec![];
+        let mut next_offset = offset;
+        let mut records = self.reader.records();
+        while (rows.len() as u64) < num_rows {
+            let record_num = records.reader().position().record();
+            let record_start = records.reader().position().byte();
+            let r = match records.next() {
+                Some(r) => r,
+                None => break,
+            };
+            // record_num is 0 for the header when started from the beginning
+            if record_start < offset || record_num == 0 {
+                continue;
+            }
+            rows.push(Row {
+                record_num: record_num as usize,
+                fields: string_record_to_vec(&r?),
+            });
+            next_offset = records.reader().position().byte();
+        }
+        if rows.is_empty() {
+            next_offset = max(offset, records.reader().position().byte());
+        }
+        Ok((rows, next_offset))
+    }
+
+    fn get_rows_impl(&mut self, indices: &[u64], raw: bool) -> Result<(Vec<Row>, GetRowsStats)> {
         // stats for debugging and testing
         let mut stats = GetRowsStats::new();
 
+        // scanning below only works forward through the file
+        let mut sorted_indices;
+        let indices = if indices.windows(2).all(|w| w[0] < w[1]) {
+            indices
+        } else {
+            sorted_indices = indices.to_vec();
+            sorted_indices.sort_unstable();
+            sorted_indices.dedup();
+            &sorted_indices
+        };
+
         let pos = Position::new();
         self.reader.seek(pos)?;
 
@@ -104,6 +927,12 @@ impl CsvLensReader {
         let mut indices_iter = indices.iter();
 
         let mut res = Vec::new();
+        // Raw fields are read from the byte span of each record separately
+        let mut raw_file = if raw {
+            Some(Source::open(self.config.filename())?)
+        } else {
+            None
+        };
 
         let mut next_pos = pos_iter.next();
         let mut next_wanted = indices_iter.next();
@@ -135,6 +964,7 @@ impl CsvLensReader {
                 }
                 let wanted_index = *next_wanted.unwrap();
                 let record_num = records.reader().position().record();
+                let record_start = records.reader().position().byte();
                 if let Some(r) = records.next() {
                     stats.log_parsed_record();
                     // no effective pre-seeking happened, this is still the header
@@ -143,10 +973,15 @@ impl CsvLensReader {
                     }
                     if record_num - 1 == wanted_index {
                         let string_record = r?;
-                        let mut fields = Vec::new();
-                        for field in string_record.iter() {
-                            fields.push(String::from(field));
-                        }
+                        let fields = if let Some(file) = &mut raw_file {
+                            let record_end = records.reader().position().byte();
+                            let mut bytes = vec![0; (record_end - record_start) as usize];
+                            file.seek(SeekFrom::Start(record_start))?;
+                            file.read_exact(&mut bytes)?;
+                            split_raw_fields(&String::from_utf8_lossy(&bytes), &self.config)
+                        } else {
+                            string_record_to_vec(&string_record)
+                        };
                         let row = Row {
                             record_num: record_num as usize,
                             fields,
@@ -175,6 +1010,7 @@ impl CsvLensReader {
             }
         }
 
+        stats.num_skipped = (indices.len() - res.len()) as u64;
         Ok((res, stats))
     }
 
@@ -188,16 +1024,27 @@ impl CsvLensReader {
         res
     }
 
+    /// Whether all records have been read in the background
+    pub fn done(&self) -> bool {
+        self.internal.lock().unwrap().done
+    }
+
     pub fn get_pos_table(&self) -> Vec<Position> {
         let res = self.internal.lock().unwrap().pos_table.clone();
         res
     }
+
+    pub fn get_pos_table_len(&self) -> usize {
+        let res = self.internal.lock().unwrap().pos_table.len();
+        res
+    }
 }
 
 #[derive(Debug, PartialEq)]
 struct GetRowsStats {
     num_seek: u64,
     num_parsed_record: u64,
+    num_skipped: u64,
 }
 
 impl GetRowsStats {
@@ -205,6 +1052,7 @@ impl GetRowsStats {
         GetRowsStats {
             num_seek: 0,
             num_parsed_record: 0,
+            num_skipped: 0,
         }
     }
 
@@ -217,6 +1065,44 @@ impl GetRowsStats {
     }
 }
 
+/// Number of lines in the file, used as a quick estimate of the number of
+/// records before parsing is done. If `sample_bytes` is given, only lines in
+/// the first `sample_bytes` bytes are counted and the total is extrapolated by
+/// file size, assuming the sampled lines are of typical length. The relative
+/// error is then about the relative difference between the average line length
+/// in the sample and in the whole file. Either way, quoted fields spanning
+/// multiple lines are counted more than once, which parsing corrects later.
+fn approx_line_count(path: &str, sample_bytes: Option<u64>) -> Result<usize> {
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let mut reader = BufReader::new(file).take(sample_bytes.unwrap_or(u64::MAX));
+
+    let mut buf = [0; 64 * 1024];
+    let mut counts = LineEndingCounts::default();
+    let mut num_bytes = 0;
+    let mut last_byte = b'\n';
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        counts.update(&buf[..n]);
+        num_bytes += n as u64;
+        last_byte = buf[n - 1];
+    }
+    counts.finish();
+    let mut num_lines = counts.total();
+    if num_bytes < file_size {
+        let estimate = num_lines as f64 * file_size as f64 / num_bytes as f64;
+        return Ok(estimate.round() as usize);
+    }
+    // count the last line even without a trailing line ending
+    if last_byte != b'\n' && last_byte != b'\r' {
+        num_lines += 1;
+    }
+    Ok(num_lines)
+}
+
 struct ReaderInternalState {
     total_line_number: Option<usize>,
     total_line_number_approx: Option<usize>,
@@ -240,10 +1126,11 @@ impl ReaderInternalState {
             // quick line count
             let total_line_number_approx;
             {
-                let file = File::open(config.filename()).unwrap();
-                let buf_reader = BufReader::new(file);
                 // subtract 1 for headers
-                total_line_number_approx = buf_reader.lines().count().saturating_sub(1);
+                total_line_number_approx =
+                    approx_line_count(config.filename(), config.approx_sample_bytes)
+                        .unwrap()
+                        .saturating_sub(1);
 
                 let mut m = _m.lock().unwrap();
                 m.total_line_number_approx = Some(total_line_number_approx);
@@ -373,17 +1260,38 @@ mod tests {
         let mut r = CsvLensReader::new(config).unwrap();
         r.wait_internal();
         let indices = vec![5000];
-        let (rows, _stats) = r.get_rows_impl(&indices).unwrap();
+        let (rows, _stats) = r.get_rows_impl(&indices, false).unwrap();
         assert_eq!(rows, vec![]);
     }
 
+    #[test]
+    fn test_simple_get_rows_stale_indices() {
+        let config = Arc::new(CsvConfig::new("tests/data/simple.csv"));
+        let mut r = CsvLensReader::new(config).unwrap();
+        r.wait_internal();
+        let indices = vec![4999, 10, 10, 6000, 3, 5000];
+        let (rows, stats) = r.get_rows_impl(&indices, false).unwrap();
+        let expected = vec![
+            Row::new(4, vec!["A4", "B4"]),
+            Row::new(11, vec!["A11", "B11"]),
+            Row::new(5000, vec!["A5000", "B5000"]),
+        ];
+        assert_eq!(rows, expected);
+        assert_eq!(stats.num_skipped, 2);
+
+        assert_eq!(r.get_rows_for_indices(&indices).unwrap(), expected);
+        assert_eq!(r.num_skipped(), 2);
+        r.get_rows_for_indices(&[3]).unwrap();
+        assert_eq!(r.num_skipped(), 0);
+    }
+
     #[test]
     fn test_simple_get_rows_impl_1() {
         let config = Arc::new(CsvConfig::new("tests/data/simple.csv"));
         let mut r = CsvLensReader::new(config).unwrap();
         r.wait_internal();
         let indices = vec![1, 3, 5, 1234, 2345, 3456, 4999];
-        let (rows, stats) = r.get_rows_impl(&indices).unwrap();
+        let (rows, stats) = r.get_rows_impl(&indices, false).unwrap();
         let expected = vec![
             Row::new(2, vec!["A2", "B2"]),
             Row::new(4, vec!["A4", "B4"]),
@@ -397,6 +1305,7 @@ mod tests {
         let expected = GetRowsStats {
             num_seek: 49,
             num_parsed_record: 505,
+            num_skipped: 0,
         };
         assert_eq!(stats, expected);
     }
@@ -407,12 +1316,13 @@ mod tests {
         let mut r = CsvLensReader::new(config).unwrap();
         r.wait_internal();
         let indices = vec![1234];
-        let (rows, stats) = r.get_rows_impl(&indices).unwrap();
+        let (rows, stats) = r.get_rows_impl(&indices, false).unwrap();
         let expected = vec![Row::new(1235, vec!["A1235", "B1235"])];
         assert_eq!(rows, expected);
         let expected = GetRowsStats {
             num_seek: 12,
             num_parsed_record: 35,
+            num_skipped: 0,
         };
         assert_eq!(stats, expected);
     }
@@ -423,16 +1333,221 @@ mod tests {
         let mut r = CsvLensReader::new(config).unwrap();
         r.wait_internal();
         let indices = vec![2];
-        let (rows, stats) = r.get_rows_impl(&indices).unwrap();
+        let (rows, stats) = r.get_rows_impl(&indices, false).unwrap();
         let expected = vec![Row::new(3, vec!["A3", "B3"])];
         assert_eq!(rows, expected);
         let expected = GetRowsStats {
             num_seek: 0,
             num_parsed_record: 4, // 3 + 1 (including header)
+            num_skipped: 0,
         };
         assert_eq!(stats, expected);
     }
 
+    #[test]
+    fn test_get_rows_from_offset() {
+        let config = Arc::new(CsvConfig::new("tests/data/cities.csv"));
+        let mut r = CsvLensReader::new(config).unwrap();
+        r.wait_internal();
+
+        // Resuming from each returned offset gives the same rows as by index
+        let mut offset = 0;
+        let mut rows = vec![];
+        loop {
+            let (mut batch, next_offset) = r.get_rows_from_offset(offset, 7).unwrap();
+            if batch.is_empty() {
+                assert_eq!(next_offset, offset);
+                break;
+            }
+            rows.append(&mut batch);
+            offset = next_offset;
+        }
+        assert_eq!(rows, r.get_rows(0, 200).unwrap());
+
+        // The partial record at the offset is skipped
+        let config = Arc::new(CsvConfig::new("tests/data/simple.csv"));
+        let mut r = CsvLensReader::new(config).unwrap();
+        let (rows, next_offset) = r.get_rows_from_offset(0, 1).unwrap();
+        assert_eq!(rows, vec![Row::new(1, vec!["A1", "B1"])]);
+        let (rows, _) = r.get_rows_from_offset(next_offset + 1, 1).unwrap();
+        assert_eq!(rows, vec![Row::new(3, vec!["A3", "B3"])]);
+    }
+
+    #[test]
+    fn test_approx_line_count() {
+        let path = "tests/data/cities.csv";
+        assert_eq!(approx_line_count(path, None).unwrap(), 129);
+        assert_eq!(approx_line_count(path, Some(1 << 20)).unwrap(), 129);
+        let estimate = approx_line_count(path, Some(1000)).unwrap();
+        assert!((110..150).contains(&estimate), "{}", estimate);
+        assert_eq!(
+            approx_line_count("tests/data/simple.csv", None).unwrap(),
+            5001
+        );
+        assert_eq!(approx_line_count("tests/data/small.csv", None).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_line_endings() {
+        for path in ["tests/data/crlf.csv", "tests/data/cr.csv"] {
+            let config = Arc::new(CsvConfig::new(path));
+            let mut r = CsvLensReader::new(config).unwrap();
+            r.wait_internal();
+            assert_eq!(r.get_total_line_numbers(), Some(3), "{}", path);
+            // The line break within the quoted field is counted as a line
+            assert_eq!(approx_line_count(path, None).unwrap(), 5, "{}", path);
+            let rows = r.get_rows(0, 3).unwrap();
+            assert_eq!(rows[0], Row::new(1, vec!["1", "x"]));
+            assert_eq!(rows[2], Row::new(3, vec!["3", "w"]));
+            let rows = r.get_raw_rows_for_indices(&[0, 2]).unwrap();
+            assert_eq!(rows[0], Row::new(1, vec!["1", "x"]));
+            assert_eq!(rows[1], Row::new(3, vec!["3", "w"]));
+        }
+
+        let line_ending = |path| detect_line_ending(path).unwrap();
+        assert_eq!(line_ending("tests/data/crlf.csv"), Some(LineEnding::Crlf));
+        assert_eq!(line_ending("tests/data/cr.csv"), Some(LineEnding::Cr));
+        assert_eq!(line_ending("tests/data/cities.csv"), Some(LineEnding::Lf));
+        let mut mixed = tempfile::NamedTempFile::new().unwrap();
+        mixed.write_all(b"a,b\n1,x\r\n2,y").unwrap();
+        let path = mixed.path().to_str().unwrap();
+        assert_eq!(line_ending(path), Some(LineEnding::Mixed));
+        mixed.as_file().set_len(3).unwrap();
+        assert_eq!(line_ending(path), None);
+
+        // Preamble lines can end with lone carriage returns too
+        let mut output = vec![];
+        skip_lines(b"title\rnotes\r\na,b\r1,2".as_slice(), &mut output, 2).unwrap();
+        assert_eq!(output, b"a,b\r1,2");
+    }
+
+    #[test]
+    fn test_header_line() {
+        let config = CsvConfig::new("tests/data/cities.csv");
+        assert_eq!(detect_header_line(&config).unwrap(), 1);
+
+        // The blank line before the header is counted as part of it
+        let config = CsvConfig::new("tests/data/preamble.csv");
+        assert_eq!(detect_header_line(&config).unwrap(), 3);
+        let input = std::fs::read("tests/data/preamble.csv").unwrap();
+        let mut output = vec![];
+        skip_lines(input.as_slice(), &mut output, 2).unwrap();
+        assert_eq!(
+            String::from_utf8(output.clone()).unwrap(),
+            "\nregion,q1,q2,q3\nnorth,1,2,3\nsouth,4,5,6\neast,7,8,9\n"
+        );
+        let mut reader = ReaderBuilder::new().from_reader(output.as_slice());
+        assert_eq!(reader.headers().unwrap(), vec!["region", "q1", "q2", "q3"]);
+
+        assert_eq!("auto".parse::<HeaderLine>().unwrap(), HeaderLine::Auto);
+        assert_eq!("3".parse::<HeaderLine>().unwrap(), HeaderLine::Line(3));
+        assert!("0".parse::<HeaderLine>().is_err());
+    }
+
+    #[test]
+    fn test_suggest_delimiter() {
+        let mut config = CsvConfig::new("tests/data/semicolon.csv");
+        assert_eq!(suggest_delimiter(&config).unwrap(), Some(b';'));
+        config.delimiter = b';';
+        assert_eq!(suggest_delimiter(&config).unwrap(), None);
+
+        for path in ["tests/data/cities.csv", "tests/data/small.csv"] {
+            assert_eq!(suggest_delimiter(&CsvConfig::new(path)).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_fixed_width() {
+        // Leading spaces are part of the first column
+        let lines = ["  id  name  x", "   1  Ann   y", "  20  Bob"];
+        assert_eq!(guess_column_starts(&lines), vec![0, 6, 12]);
+        assert_eq!(
+            split_fixed_width(lines[1], &[0, 6, 12]),
+            vec!["1", "Ann", "y"]
+        );
+        assert_eq!(
+            split_fixed_width(lines[2], &[0, 6, 12]),
+            vec!["20", "Bob", ""]
+        );
+
+        assert_eq!(parse_column_starts("0, 12,26").unwrap(), vec![0, 12, 26]);
+        assert_eq!(parse_column_starts("12").unwrap(), vec![0, 12]);
+        assert!(parse_column_starts("12,5").is_err());
+        assert!(parse_column_starts("a").is_err());
+
+        let convert = |starts: Option<&[usize]>, skip_rows| {
+            let input = File::open("tests/data/fixed_width.txt").unwrap();
+            let mut output = vec![];
+            let starts = convert_fixed_width(input, &mut output, starts, skip_rows).unwrap();
+            (starts, String::from_utf8(output).unwrap())
+        };
+        // The space in Alice Wong is blank in the other lines too
+        assert_eq!(
+            convert(None, 0),
+            (
+                vec![0, 6, 12, 26],
+                "name,,city,amount\nAlice,Wong,New York,12\nBob,,Chicago,1200\nCarol,,Boston,7.5\n"
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            convert(Some(&[0, 12, 26]), 2).1,
+            "name,city,amount\nCarol,Boston,7.5\n"
+        );
+
+        let config = CsvConfig::new("tests/data/fixed_width.txt");
+        assert_eq!(
+            suggest_fixed_width(&config).unwrap(),
+            Some(vec![0, 6, 12, 26])
+        );
+        for path in ["tests/data/cities.csv", "tests/data/small.csv"] {
+            assert_eq!(suggest_fixed_width(&CsvConfig::new(path)).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_skip_records() {
+        let skipped = |path: &str, n: usize| {
+            let mut output = vec![];
+            skip_records(&CsvConfig::new(path), &mut output, n).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        // The line break in the quoted field doesn't count as a record
+        assert_eq!(skipped("tests/data/crlf.csv", 2), "a,b\r\n3,w\r\n");
+        assert_eq!(
+            skipped("tests/data/crlf.csv", 0),
+            "a,b\r\n1,x\r\n2,\"y\r\nz\"\r\n3,w\r\n"
+        );
+        assert_eq!(skipped("tests/data/crlf.csv", 10), "a,b\r\n");
+    }
+
+    #[test]
+    fn test_slice_records() {
+        let sliced = |slice: RecordSlice| {
+            let mut output = vec![];
+            slice_records(&CsvConfig::new("tests/data/crlf.csv"), &mut output, slice).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(
+            sliced(RecordSlice::Head(2)),
+            "a,b\r\n1,x\r\n2,\"y\r\nz\"\r\n"
+        );
+        assert_eq!(sliced(RecordSlice::Head(0)), "a,b\r\n");
+        assert_eq!(
+            sliced(RecordSlice::Head(3)),
+            "a,b\r\n1,x\r\n2,\"y\r\nz\"\r\n3,w\r\n"
+        );
+        assert_eq!(
+            sliced(RecordSlice::Tail(2)),
+            "a,b\r\n2,\"y\r\nz\"\r\n3,w\r\n"
+        );
+        assert_eq!(sliced(RecordSlice::Tail(1)), "a,b\r\n3,w\r\n");
+        assert_eq!(
+            sliced(RecordSlice::Tail(10)),
+            "a,b\r\n1,x\r\n2,\"y\r\nz\"\r\n3,w\r\n"
+        );
+    }
+
     #[test]
     fn test_small() {
         let config = Arc::new(CsvConfig::new("tests/data/small.csv"));
@@ -459,6 +1574,63 @@ mod tests {
         assert_eq!(rows, expected);
     }
 
+    #[test]
+    fn test_whitespace_delimited() {
+        let mut converted = tempfile::NamedTempFile::new().unwrap();
+        let input = File::open("tests/data/whitespace.txt").unwrap();
+        convert_whitespace_delimited(input, &mut converted).unwrap();
+        let config = Arc::new(CsvConfig::new(converted.path().to_str().unwrap()));
+        let mut r = CsvLensReader::new(config).unwrap();
+        assert_eq!(r.headers, vec!["PID", "TTY", "TIME", "CMD"]);
+        let rows = r.get_rows(0, 50).unwrap();
+        let expected = vec![
+            Row::new(1, vec!["1234", "pts/0", "00:00:00", "bash"]),
+            Row::new(2, vec!["5678", "pts/0", "00:00:01", "vim,nano"]),
+            Row::new(3, vec!["91011", "pts/1", "00:00:00", "ps"]),
+        ];
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn test_no_quoting() {
+        let mut config = CsvConfig::new("tests/data/good_double_quote.csv");
+        config.quoting = false;
+        let config = Arc::new(config);
+        let mut r = CsvLensReader::new(config).unwrap();
+        assert_eq!(r.headers, vec!["Column1", "\"column2\""]);
+        let rows = r.get_rows(0, 50).unwrap();
+        let expected = vec![
+            Row::new(1, vec!["1", "\"quote\""]),
+            Row::new(2, vec!["5", "\"Comma", " comma\""]),
+        ];
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn test_escape() {
+        let mut config = CsvConfig::new("tests/data/backslash_escape.csv");
+        config.escape = Some(b'\\');
+        let config = Arc::new(config);
+        let mut r = CsvLensReader::new(config).unwrap();
+        let rows = r.get_rows(0, 50).unwrap();
+        let expected = vec![
+            Row::new(1, vec!["1", "say \"hi\""]),
+            Row::new(2, vec!["2", "a, b"]),
+        ];
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn test_quote() {
+        let mut config = CsvConfig::new("tests/data/single_quote.csv");
+        config.quote = b'\'';
+        let config = Arc::new(config);
+        let mut r = CsvLensReader::new(config).unwrap();
+        let rows = r.get_rows(0, 50).unwrap();
+        let expected = vec![Row::new(1, vec!["1", "Comma, comma"])];
+        assert_eq!(rows, expected);
+    }
+
     #[test]
     fn test_irregular() {
         let config = Arc::new(CsvConfig::new("tests/data/irregular.csv"));
@@ -479,4 +1651,90 @@ mod tests {
         ];
         assert_eq!(rows, expected);
     }
+
+    #[test]
+    fn test_raw_fields() {
+        let config = Arc::new(CsvConfig::new("tests/data/good_double_quote.csv"));
+        let mut r = CsvLensReader::new(config).unwrap();
+        let rows = r.get_raw_rows_for_indices(&[0, 1]).unwrap();
+        let expected = vec![
+            Row::new(1, vec!["1", "\"quote\""]),
+            Row::new(2, vec!["5", "\"Comma, comma\""]),
+        ];
+        assert_eq!(rows, expected);
+
+        let mut config = CsvConfig::new("tests/data/backslash_escape.csv");
+        config.escape = Some(b'\\');
+        let mut r = CsvLensReader::new(Arc::new(config)).unwrap();
+        let rows = r.get_raw_rows_for_indices(&[0, 1]).unwrap();
+        let expected = vec![
+            Row::new(1, vec!["1", "\"say \\\"hi\\\"\""]),
+            Row::new(2, vec!["2", "\"a, b\""]),
+        ];
+        assert_eq!(rows, expected);
+
+        let config = CsvConfig::new("");
+        let split = |s| split_raw_fields(s, &config);
+        assert_eq!(
+            split("a,\"b \"\"c\"\"\",d\r\n"),
+            vec!["a", "\"b \"\"c\"\"\"", "d"]
+        );
+        assert_eq!(
+            split("a\"b,\"c\"d, \"e\""),
+            vec!["a\"b", "\"c\"d", " \"e\""]
+        );
+        assert_eq!(split(",\n"), vec!["", ""]);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_map() {
+        let file = File::open("tests/data/small.csv").unwrap();
+        let mmap = Source::map(&file).unwrap();
+        assert_eq!(&*mmap, &std::fs::read("tests/data/small.csv").unwrap()[..]);
+
+        let empty = tempfile::NamedTempFile::new().unwrap();
+        assert!(Source::map(empty.as_file()).is_none());
+    }
+
+    /// Compare getting rows with the file mapped to reading it as usual. Run
+    /// with `cargo test --release --features mmap -- --ignored --nocapture
+    /// bench_mmap`.
+    #[cfg(feature = "mmap")]
+    #[test]
+    #[ignore]
+    fn bench_mmap() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = io::BufWriter::new(&mut file);
+        writeln!(writer, "id,name,value,comment").unwrap();
+        for i in 0..2_000_000 {
+            writeln!(
+                writer,
+                "{},name {},{},\"some, quoted text\"",
+                i,
+                i % 97,
+                i * 7
+            )
+            .unwrap();
+        }
+        drop(writer);
+        let path = file.path().to_str().unwrap();
+
+        let config = Arc::new(CsvConfig::new(path));
+        let sources = [
+            ("read", Source::File(File::open(path).unwrap())),
+            ("mmap", Source::open(path).unwrap()),
+        ];
+        for (name, source) in sources {
+            let mut r = CsvLensReader::with_source(config.clone(), source).unwrap();
+            r.wait_internal();
+            let start = std::time::Instant::now();
+            for i in 0..1000 {
+                // Jump around the file as when scrolling to random rows
+                let row = (i * 7919 * 251) % 1_990_000;
+                assert_eq!(r.get_rows(row, 50).unwrap().len(), 50);
+            }
+            println!("{}: {:?}", name, start.elapsed());
+        }
+    }
 }
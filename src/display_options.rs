@@ -0,0 +1,208 @@
+use crate::cache::config_dir;
+use crate::ui::GutterMode;
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How the table looks as toggled while viewing, remembered for the next run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DisplayOptions {
+    pub show_whitespace: bool,
+    pub compact: bool,
+    pub grid: bool,
+    pub show_changes: bool,
+    pub show_ruler: bool,
+    pub hide_status: bool,
+    pub show_cell_position: bool,
+    pub show_cell_length: bool,
+    pub show_booleans: bool,
+    pub show_scientific: bool,
+    pub gutter_mode: GutterMode,
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("display"))
+}
+
+impl DisplayOptions {
+    /// Read the options from a file with one `name=value` per line. Options
+    /// that are missing or can't be read, e.g. from another version, are left
+    /// as the defaults.
+    pub fn load(path: &Path) -> DisplayOptions {
+        let mut options = DisplayOptions::default();
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) => return options,
+        };
+        for line in content.lines() {
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => continue,
+            };
+            match name {
+                "show_whitespace" => set_flag(&mut options.show_whitespace, value),
+                "compact" => set_flag(&mut options.compact, value),
+                "grid" => set_flag(&mut options.grid, value),
+                "show_changes" => set_flag(&mut options.show_changes, value),
+                "show_ruler" => set_flag(&mut options.show_ruler, value),
+                "hide_status" => set_flag(&mut options.hide_status, value),
+                "show_cell_position" => set_flag(&mut options.show_cell_position, value),
+                "show_cell_length" => set_flag(&mut options.show_cell_length, value),
+                "show_booleans" => set_flag(&mut options.show_booleans, value),
+                "show_scientific" => set_flag(&mut options.show_scientific, value),
+                "gutter_mode" => {
+                    if let Some(mode) = parse_gutter_mode(value) {
+                        options.gutter_mode = mode;
+                    }
+                }
+                _ => {}
+            }
+        }
+        options
+    }
+
+    /// Options to remember after viewing with these loaded: those toggled
+    /// since the start, and the loaded ones for the rest, so that options
+    /// turned on by flags for one run are not remembered
+    pub fn toggled(&self, start: &DisplayOptions, end: &DisplayOptions) -> DisplayOptions {
+        let pick = |loaded: bool, start: bool, end: bool| if start == end { loaded } else { end };
+        DisplayOptions {
+            show_whitespace: pick(
+                self.show_whitespace,
+                start.show_whitespace,
+                end.show_whitespace,
+            ),
+            compact: pick(self.compact, start.compact, end.compact),
+            grid: pick(self.grid, start.grid, end.grid),
+            show_changes: pick(self.show_changes, start.show_changes, end.show_changes),
+            show_ruler: pick(self.show_ruler, start.show_ruler, end.show_ruler),
+            hide_status: pick(self.hide_status, start.hide_status, end.hide_status),
+            show_cell_position: pick(
+                self.show_cell_position,
+                start.show_cell_position,
+                end.show_cell_position,
+            ),
+            show_cell_length: pick(
+                self.show_cell_length,
+                start.show_cell_length,
+                end.show_cell_length,
+            ),
+            show_booleans: pick(self.show_booleans, start.show_booleans, end.show_booleans),
+            show_scientific: pick(
+                self.show_scientific,
+                start.show_scientific,
+                end.show_scientific,
+            ),
+            gutter_mode: if start.gutter_mode == end.gutter_mode {
+                self.gutter_mode
+            } else {
+                end.gutter_mode
+            },
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .context(format!("Failed to create directory: {}", dir.display()))?;
+        }
+        let flags = [
+            ("show_whitespace", self.show_whitespace),
+            ("compact", self.compact),
+            ("grid", self.grid),
+            ("show_changes", self.show_changes),
+            ("show_ruler", self.show_ruler),
+            ("hide_status", self.hide_status),
+            ("show_cell_position", self.show_cell_position),
+            ("show_cell_length", self.show_cell_length),
+            ("show_booleans", self.show_booleans),
+            ("show_scientific", self.show_scientific),
+        ];
+        let mut content = String::new();
+        for (name, value) in flags {
+            content.push_str(&format!("{}={}\n", name, value));
+        }
+        content.push_str(&format!(
+            "gutter_mode={}\n",
+            gutter_mode_name(self.gutter_mode)
+        ));
+        fs::write(path, content).context(format!("Failed to write file: {}", path.display()))?;
+        Ok(())
+    }
+}
+
+fn set_flag(flag: &mut bool, value: &str) {
+    if let Ok(value) = value.parse() {
+        *flag = value;
+    }
+}
+
+fn gutter_mode_name(mode: GutterMode) -> &'static str {
+    match mode {
+        GutterMode::Original => "original",
+        GutterMode::Position => "position",
+        GutterMode::Relative => "relative",
+    }
+}
+
+fn parse_gutter_mode(name: &str) -> Option<GutterMode> {
+    match name {
+        "original" => Some(GutterMode::Original),
+        "position" => Some(GutterMode::Position),
+        "relative" => Some(GutterMode::Relative),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_and_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("csvlens").join("display");
+        assert_eq!(DisplayOptions::load(&path), DisplayOptions::default());
+
+        let options = DisplayOptions {
+            compact: true,
+            gutter_mode: GutterMode::Relative,
+            ..Default::default()
+        };
+        options.save(&path).unwrap();
+        assert_eq!(DisplayOptions::load(&path), options);
+
+        // Unknown names and values are ignored
+        fs::write(&path, "grid=true\nwrap=true\ncompact=yes\ngutter_mode=x\n").unwrap();
+        let expected = DisplayOptions {
+            grid: true,
+            ..Default::default()
+        };
+        assert_eq!(DisplayOptions::load(&path), expected);
+    }
+
+    #[test]
+    fn test_toggled() {
+        let loaded = DisplayOptions {
+            show_ruler: true,
+            ..Default::default()
+        };
+        // Grid turned on by a flag, then compact toggled on and the ruler off
+        let start = DisplayOptions {
+            grid: true,
+            ..loaded
+        };
+        let end = DisplayOptions {
+            compact: true,
+            show_ruler: false,
+            ..start
+        };
+        let expected = DisplayOptions {
+            compact: true,
+            ..Default::default()
+        };
+        assert_eq!(loaded.toggled(&start, &end), expected);
+        assert_eq!(loaded.toggled(&start, &start), loaded);
+    }
+}
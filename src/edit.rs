@@ -0,0 +1,143 @@
+use crate::csv::{self, CsvConfig};
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// Replace a field of a record in the file. Everything else is written back
+/// as it was, e.g. the quoting of the other fields and the line endings. The
+/// file is replaced by a new one written next to it, so that it is never
+/// left half written.
+pub fn write_field(
+    config: &CsvConfig,
+    record_num: usize,
+    column: usize,
+    value: &str,
+) -> Result<()> {
+    let path = config.filename();
+    let bytes = fs::read(path).context(format!("Failed to read file: {}", path))?;
+    let (start, end) = record_span(config, record_num)?;
+    let span = std::str::from_utf8(&bytes[start..end])
+        .context("Only records that are valid UTF-8 can be edited")?;
+
+    // Line breaks around the record are kept as they are
+    let record = span.trim_start_matches(['\r', '\n']);
+    let prefix = &span[..span.len() - record.len()];
+    let record = record.trim_end_matches(['\r', '\n']);
+    let suffix = &span[prefix.len() + record.len()..];
+
+    let mut fields = csv::split_raw_fields(record, config);
+    if fields.len() <= column {
+        fields.resize(column + 1, "".to_owned());
+    }
+    fields[column] = quote_field(value, config)?;
+    let delimiter = (config.delimiter as char).to_string();
+
+    let mut out = Vec::with_capacity(bytes.len() + value.len());
+    out.extend_from_slice(&bytes[..start]);
+    out.extend_from_slice(prefix.as_bytes());
+    out.extend_from_slice(fields.join(&delimiter).as_bytes());
+    out.extend_from_slice(suffix.as_bytes());
+    out.extend_from_slice(&bytes[end..]);
+    replace_file(path, &out)
+}
+
+/// Byte range of the record in the file, where record 0 is the header
+fn record_span(config: &CsvConfig, record_num: usize) -> Result<(usize, usize)> {
+    let mut reader = config.new_reader()?;
+    // Start from the header, which records() would skip otherwise
+    reader.seek(::csv::Position::new())?;
+    let mut records = reader.records();
+    loop {
+        let start = records.reader().position().byte();
+        let current = records.reader().position().record();
+        match records.next() {
+            Some(r) => {
+                r?;
+            }
+            None => bail!("Row {} not found, has the file changed?", record_num),
+        }
+        if current as usize == record_num {
+            let end = records.reader().position().byte();
+            return Ok((start as usize, end as usize));
+        }
+    }
+}
+
+/// Quote the value if needed, in the same way as other fields in the file
+fn quote_field(value: &str, config: &CsvConfig) -> Result<String> {
+    let (delimiter, quote) = (config.delimiter as char, config.quote as char);
+    let needs_quotes = value.contains([delimiter, quote, '\r', '\n']);
+    if !needs_quotes {
+        return Ok(value.to_owned());
+    }
+    if !config.quoting {
+        bail!("Values can't contain the delimiter or line breaks without quoting");
+    }
+    let escaped = match config.escape.map(|c| c as char) {
+        Some(escape) => value
+            .replace(escape, &format!("{}{}", escape, escape))
+            .replace(quote, &format!("{}{}", escape, quote)),
+        None => value.replace(quote, &format!("{}{}", quote, quote)),
+    };
+    Ok(format!("{}{}{}", quote, escaped, quote))
+}
+
+fn replace_file(path: &str, content: &[u8]) -> Result<()> {
+    let dir = match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let err = format!("Failed to write file: {}", path);
+    let mut file = NamedTempFile::new_in(dir).context(err.clone())?;
+    file.write_all(content).context(err.clone())?;
+    let permissions = fs::metadata(path).context(err.clone())?.permissions();
+    fs::set_permissions(file.path(), permissions).context(err.clone())?;
+    file.persist(path).context(err)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(content: &str, record_num: usize, column: usize, value: &str) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("edit.csv");
+        fs::write(&path, content).unwrap();
+        let config = CsvConfig::new(path.to_str().unwrap());
+        write_field(&config, record_num, column, value).unwrap();
+        fs::read_to_string(&path).unwrap()
+    }
+
+    #[test]
+    fn test_write_field() {
+        let content = "a,b\n\"x\",1\n\"y\ny\",2\n";
+        assert_eq!(edit(content, 1, 1, "10"), "a,b\n\"x\",10\n\"y\ny\",2\n");
+        assert_eq!(edit(content, 2, 0, "z"), "a,b\n\"x\",1\nz,2\n");
+        assert_eq!(edit(content, 0, 0, "c"), "c,b\n\"x\",1\n\"y\ny\",2\n");
+        assert_eq!(
+            edit(content, 1, 0, "say \"hi\", bye"),
+            "a,b\n\"say \"\"hi\"\", bye\",1\n\"y\ny\",2\n"
+        );
+        // Missing fields are added
+        assert_eq!(edit("a,b,c\n1\n", 1, 2, "3"), "a,b,c\n1,,3\n");
+        // Line endings are kept
+        assert_eq!(edit("a,b\r\n1,2\r\n3,4", 1, 0, "x"), "a,b\r\nx,2\r\n3,4");
+        assert_eq!(edit("a,b\r\n1,2\r\n3,4", 2, 1, "x"), "a,b\r\n1,2\r\n3,x");
+    }
+
+    #[test]
+    fn test_quote_field() {
+        let mut config = CsvConfig::new("");
+        assert_eq!(quote_field("abc", &config).unwrap(), "abc");
+        assert_eq!(quote_field("a\nb", &config).unwrap(), "\"a\nb\"");
+        config.escape = Some(b'\\');
+        assert_eq!(quote_field("a\"b", &config).unwrap(), "\"a\\\"b\"");
+        assert_eq!(quote_field("\\\"", &config).unwrap(), "\"\\\\\\\"\"");
+        config.quoting = false;
+        assert!(quote_field("a,b", &config).is_err());
+    }
+}
@@ -0,0 +1,465 @@
+use crate::csv::Row;
+
+use anyhow::{bail, Context, Result};
+use csv::WriterBuilder;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Tsv,
+    Json,
+    Markdown,
+}
+
+impl Format {
+    /// Guess the format from the file extension, defaulting to CSV
+    pub fn from_path(path: &str) -> Format {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("tsv") | Some("tab") => Format::Tsv,
+            Some("json") => Format::Json,
+            Some("md") | Some("markdown") => Format::Markdown,
+            _ => Format::Csv,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            "json" => Ok(Format::Json),
+            "markdown" | "md" => Ok(Format::Markdown),
+            _ => bail!(
+                "Unknown format: {} (should be one of csv, tsv, json, markdown)",
+                s
+            ),
+        }
+    }
+}
+
+/// How a cell is copied along with the name of its column, e.g. for writing
+/// a query
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellFormat {
+    /// `name=value`
+    KeyValue,
+    /// `{"name": "value"}`
+    Json,
+    /// `name = 'value'`, with the name quoted if it isn't a plain identifier
+    Sql,
+}
+
+impl CellFormat {
+    pub fn format(&self, name: &str, value: &str) -> String {
+        match self {
+            CellFormat::KeyValue => format!("{}={}", name, value),
+            CellFormat::Json => format!("{{{}: {}}}", json_string(name), json_string(value)),
+            CellFormat::Sql => {
+                let is_identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                let name = if is_identifier {
+                    name.to_owned()
+                } else {
+                    format!("\"{}\"", name.replace('"', "\"\""))
+                };
+                format!("{} = '{}'", name, value.replace('\'', "''"))
+            }
+        }
+    }
+}
+
+impl FromStr for CellFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "kv" => Ok(CellFormat::KeyValue),
+            "json" => Ok(CellFormat::Json),
+            "sql" => Ok(CellFormat::Sql),
+            _ => bail!(
+                "Unknown cell format: {} (should be one of kv, json, sql)",
+                s
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ExportOptions {
+    /// Format to use regardless of the file extension
+    pub format: Option<Format>,
+    /// Write numeric-looking values as JSON numbers instead of strings
+    pub json_numbers: bool,
+    /// Values written as empty instead, e.g. NA or NULL
+    pub null_tokens: Vec<String>,
+}
+
+/// Format rows as delimited lines, e.g. for copying to the clipboard
+pub fn to_csv_string(rows: &[Row], delimiter: u8) -> Result<String> {
+    let mut writer = WriterBuilder::new()
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_writer(vec![]);
+    for row in rows {
+        writer.write_record(&row.fields)?;
+    }
+    let bytes = writer.into_inner()?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Write rows to a file in the format given by the options or the file
+/// extension. Rows are written as CSV using the delimiter of the input.
+pub fn write(
+    path: &str,
+    headers: &[String],
+    rows: &[Row],
+    options: &ExportOptions,
+    delimiter: u8,
+) -> Result<()> {
+    let file = File::create(path).context(format!("Failed to create file: {}", path))?;
+    let mut out = BufWriter::new(file);
+    let format = options.format.unwrap_or_else(|| Format::from_path(path));
+    write_to(&mut out, format, headers, rows, options, delimiter)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Write rows in the given format, e.g. to stdout
+pub fn write_to<W: Write>(
+    out: W,
+    format: Format,
+    headers: &[String],
+    rows: &[Row],
+    options: &ExportOptions,
+    delimiter: u8,
+) -> Result<()> {
+    let mut writer = RowWriter::new(out, format, headers, options, delimiter)?;
+    for row in rows {
+        writer.write_row(&row.fields)?;
+    }
+    writer.finish()
+}
+
+/// Writer of rows one at a time in a format, so that they can be written as
+/// soon as they are read
+pub struct RowWriter<W: Write> {
+    inner: Inner<W>,
+    null_tokens: Vec<String>,
+}
+
+enum Inner<W: Write> {
+    // Boxed for the buffer it holds
+    Delimited(Box<csv::Writer<W>>),
+    Json {
+        out: W,
+        headers: Vec<String>,
+        json_numbers: bool,
+        num_rows: usize,
+    },
+    Markdown {
+        out: W,
+        num_columns: usize,
+    },
+}
+
+impl<W: Write> RowWriter<W> {
+    /// Start writing with the headers, using the delimiter for CSV
+    pub fn new(
+        mut out: W,
+        format: Format,
+        headers: &[String],
+        options: &ExportOptions,
+        delimiter: u8,
+    ) -> Result<RowWriter<W>> {
+        let delimited = |out: W, delimiter: u8| -> Result<Inner<W>> {
+            let mut writer = WriterBuilder::new()
+                .flexible(true)
+                .delimiter(delimiter)
+                .from_writer(out);
+            writer.write_record(headers)?;
+            Ok(Inner::Delimited(Box::new(writer)))
+        };
+        let inner = match format {
+            Format::Csv => delimited(out, delimiter)?,
+            Format::Tsv => delimited(out, b'\t')?,
+            // An array of objects keyed by header
+            Format::Json => {
+                writeln!(out, "[")?;
+                Inner::Json {
+                    out,
+                    headers: headers.to_vec(),
+                    json_numbers: options.json_numbers,
+                    num_rows: 0,
+                }
+            }
+            Format::Markdown => {
+                writeln!(out, "{}", markdown_row(headers, headers.len()))?;
+                let rule: Vec<&str> = headers.iter().map(|_| "---").collect();
+                writeln!(out, "| {} |", rule.join(" | "))?;
+                Inner::Markdown {
+                    out,
+                    num_columns: headers.len(),
+                }
+            }
+        };
+        Ok(RowWriter {
+            inner,
+            null_tokens: options.null_tokens.clone(),
+        })
+    }
+
+    pub fn write_row(&mut self, fields: &[String]) -> Result<()> {
+        let normalized: Vec<String>;
+        let fields = if self.null_tokens.is_empty() {
+            fields
+        } else {
+            normalized = fields
+                .iter()
+                .map(|field| {
+                    if self.null_tokens.contains(field) {
+                        "".to_owned()
+                    } else {
+                        field.clone()
+                    }
+                })
+                .collect();
+            normalized.as_slice()
+        };
+        match &mut self.inner {
+            Inner::Delimited(writer) => writer.write_record(fields)?,
+            // Fields without a header are left out
+            Inner::Json {
+                out,
+                headers,
+                json_numbers,
+                num_rows,
+            } => {
+                let members: Vec<String> = headers
+                    .iter()
+                    .zip(fields.iter())
+                    .map(|(header, field)| {
+                        let value = if *json_numbers && is_json_number(field) {
+                            field.clone()
+                        } else {
+                            json_string(field)
+                        };
+                        format!("{}: {}", json_string(header), value)
+                    })
+                    .collect();
+                // The separator goes before the row since the last one has none
+                if *num_rows > 0 {
+                    writeln!(out, ",")?;
+                }
+                write!(out, "  {{{}}}", members.join(", "))?;
+                *num_rows += 1;
+            }
+            Inner::Markdown { out, num_columns } => {
+                writeln!(out, "{}", markdown_row(fields, *num_columns))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        match self.inner {
+            Inner::Delimited(mut writer) => writer.flush()?,
+            Inner::Json {
+                mut out, num_rows, ..
+            } => {
+                if num_rows > 0 {
+                    writeln!(out)?;
+                }
+                writeln!(out, "]")?;
+                out.flush()?;
+            }
+            Inner::Markdown { mut out, .. } => out.flush()?,
+        }
+        Ok(())
+    }
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Whether the value is a valid JSON number as is, e.g. not `.5`, `1.` or `NaN`
+fn is_json_number(s: &str) -> bool {
+    let s = s.strip_prefix('-').unwrap_or(s);
+    let digits = |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    let n = digits(s);
+    if n == 0 || (n > 1 && s.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &s[n..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let n = digits(fraction);
+        if n == 0 {
+            return false;
+        }
+        rest = &fraction[n..];
+    }
+    if let Some(exponent) = rest.strip_prefix(|c| c == 'e' || c == 'E') {
+        let exponent = exponent
+            .strip_prefix(|c| c == '+' || c == '-')
+            .unwrap_or(exponent);
+        let n = digits(exponent);
+        if n == 0 {
+            return false;
+        }
+        rest = &exponent[n..];
+    }
+    rest.is_empty()
+}
+
+fn markdown_row(fields: &[String], num_columns: usize) -> String {
+    let cells: Vec<String> = (0..num_columns)
+        .map(|i| markdown_cell(fields.get(i).map(|x| x.as_str()).unwrap_or("")))
+        .collect();
+    format!("| {} |", cells.join(" | "))
+}
+
+fn markdown_cell(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(format: Format, json_numbers: bool) -> String {
+        let headers = vec!["name".to_owned(), "amount".to_owned()];
+        let rows = vec![
+            Row::new(1, vec!["a \"b\"", "10"]),
+            Row::new(2, vec!["c|d\ne", "-1.5e3"]),
+            Row::new(3, vec!["f", "1."]),
+        ];
+        let options = ExportOptions {
+            format: Some(format),
+            json_numbers,
+            ..Default::default()
+        };
+        let out = tempfile::NamedTempFile::new().unwrap();
+        let path = out.path().to_str().unwrap();
+        write(path, &headers, &rows, &options, b',').unwrap();
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::from_path("out.tsv"), Format::Tsv);
+        assert_eq!(Format::from_path("out.JSON"), Format::Json);
+        assert_eq!(Format::from_path("dir.md/out.markdown"), Format::Markdown);
+        assert_eq!(Format::from_path("out.txt"), Format::Csv);
+        assert_eq!(Format::from_path("out"), Format::Csv);
+        assert!("yaml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_tsv() {
+        assert_eq!(
+            export(Format::Tsv, false),
+            "name\tamount\n\"a \"\"b\"\"\"\t10\n\"c|d\ne\"\t-1.5e3\nf\t1.\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let expected = r#"[
+  {"name": "a \"b\"", "amount": "10"},
+  {"name": "c|d\ne", "amount": "-1.5e3"},
+  {"name": "f", "amount": "1."}
+]
+"#;
+        assert_eq!(export(Format::Json, false), expected);
+
+        let expected = r#"[
+  {"name": "a \"b\"", "amount": 10},
+  {"name": "c|d\ne", "amount": -1.5e3},
+  {"name": "f", "amount": "1."}
+]
+"#;
+        assert_eq!(export(Format::Json, true), expected);
+    }
+
+    #[test]
+    fn test_markdown() {
+        let expected = "| name | amount |
+| --- | --- |
+| a \"b\" | 10 |
+| c\\|d<br>e | -1.5e3 |
+| f | 1. |
+";
+        assert_eq!(export(Format::Markdown, false), expected);
+    }
+
+    #[test]
+    fn test_null_tokens() {
+        let headers = vec!["a".to_owned(), "b".to_owned()];
+        let rows = vec![
+            Row::new(1, vec!["NA", "\\N"]),
+            Row::new(2, vec!["NAN", "1"]),
+        ];
+        let options = ExportOptions {
+            null_tokens: vec!["NA".to_owned(), "\\N".to_owned()],
+            ..Default::default()
+        };
+        let mut out = vec![];
+        write_to(&mut out, Format::Csv, &headers, &rows, &options, b',').unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "a,b\n,\nNAN,1\n");
+    }
+
+    #[test]
+    fn test_cell_format() {
+        assert_eq!(CellFormat::KeyValue.format("City", "Yakima"), "City=Yakima");
+        assert_eq!(
+            CellFormat::Json.format("City", "say \"hi\""),
+            r#"{"City": "say \"hi\""}"#
+        );
+        assert_eq!(
+            CellFormat::Sql.format("city_2", "O'Neill"),
+            "city_2 = 'O''Neill'"
+        );
+        assert_eq!(CellFormat::Sql.format("Lat D", "41"), "\"Lat D\" = '41'");
+        assert_eq!("SQL".parse::<CellFormat>().unwrap(), CellFormat::Sql);
+        assert!("yaml".parse::<CellFormat>().is_err());
+    }
+
+    #[test]
+    fn test_is_json_number() {
+        for s in ["0", "-0", "10", "1.5", "1e10", "-2.5E-3"] {
+            assert!(is_json_number(s), "{}", s);
+        }
+        for s in ["", "-", "01", ".5", "1.", "+1", "1e", "NaN", "inf", "1 "] {
+            assert!(!is_json_number(s), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use crate::stats;
+
+use anyhow::{bail, Result};
+use csv::StringRecord;
+use regex::Regex;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareOp {
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    Eq,
+    Ne,
+}
+
+impl CompareOp {
+    // Longer operators first so that e.g. >= is not parsed as >
+    const ALL: [(&'static str, CompareOp); 6] = [
+        (">=", CompareOp::Ge),
+        ("<=", CompareOp::Le),
+        ("==", CompareOp::Eq),
+        ("!=", CompareOp::Ne),
+        (">", CompareOp::Gt),
+        ("<", CompareOp::Lt),
+    ];
+
+    fn apply<T: PartialOrd>(&self, a: T, b: T) -> bool {
+        match self {
+            CompareOp::Gt => a > b,
+            CompareOp::Lt => a < b,
+            CompareOp::Ge => a >= b,
+            CompareOp::Le => a <= b,
+            CompareOp::Eq => a == b,
+            CompareOp::Ne => a != b,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Op(CompareOp),
+    // ~ and !~, matching a field against a regex
+    Match { negated: bool },
+    And,
+    Or,
+    LParen,
+    RParen,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        if let Some((op_str, op)) = CompareOp::ALL.iter().find(|(x, _)| rest.starts_with(x)) {
+            tokens.push(Token::Op(*op));
+            rest = &rest[op_str.len()..];
+        } else if let Some(r) = rest.strip_prefix("!~") {
+            tokens.push(Token::Match { negated: true });
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix('~') {
+            tokens.push(Token::Match { negated: false });
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix('(') {
+            tokens.push(Token::LParen);
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix(')') {
+            tokens.push(Token::RParen);
+            rest = r;
+        } else if let Some(quoted) = rest.strip_prefix('"') {
+            // Quoted words can contain spaces and operator characters, and are
+            // never treated as keywords
+            match quoted.find('"') {
+                Some(end) => {
+                    tokens.push(Token::Word(quoted[..end].to_owned()));
+                    rest = &quoted[end + 1..];
+                }
+                None => bail!("Unterminated quote"),
+            }
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "\"<>=!~()".contains(c))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                bail!("Unexpected character: {}", &rest[..1]);
+            }
+            let word = &rest[..end];
+            let token = if word.eq_ignore_ascii_case("and") {
+                Token::And
+            } else if word.eq_ignore_ascii_case("or") {
+                Token::Or
+            } else {
+                Token::Word(word.to_owned())
+            };
+            tokens.push(token);
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Compare {
+        column: usize,
+        op: CompareOp,
+        value: String,
+        number: Option<f64>,
+    },
+    Match {
+        column: usize,
+        regex: Regex,
+        negated: bool,
+    },
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn is_match(&self, record: &StringRecord) -> bool {
+        match self {
+            Node::Compare {
+                column,
+                op,
+                value,
+                number,
+            } => {
+                let field = match record.get(*column) {
+                    Some(field) => field,
+                    None => return false,
+                };
+                if let Some(number) = number {
+                    // Compare numerically if the value looks numeric. Fields
+                    // that are not numbers are only considered different.
+                    match stats::parse_number(field) {
+                        Some(x) => op.apply(x, *number),
+                        None => *op == CompareOp::Ne,
+                    }
+                } else {
+                    op.apply(field, value.as_str())
+                }
+            }
+            Node::Match {
+                column,
+                regex,
+                negated,
+            } => match record.get(*column) {
+                Some(field) => regex.is_match(field) != *negated,
+                None => false,
+            },
+            Node::And(a, b) => a.is_match(record) && b.is_match(record),
+            Node::Or(a, b) => a.is_match(record) || b.is_match(record),
+        }
+    }
+
+    fn column_indices(&self, out: &mut Vec<usize>) {
+        match self {
+            Node::Compare { column, .. } | Node::Match { column, .. } => {
+                if !out.contains(column) {
+                    out.push(*column)
+                }
+            }
+            Node::And(a, b) | Node::Or(a, b) => {
+                a.column_indices(out);
+                b.column_indices(out);
+            }
+        }
+    }
+}
+
+/// Recursive descent parser over tokens, with AND binding tighter than OR:
+///
+///   or      := and (OR and)*
+///   and     := primary (AND primary)*
+///   primary := "(" or ")" | word (op | ~ | !~) word
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    headers: &'a [String],
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Node> {
+        let mut node = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            node = Node::Or(Box::new(node), Box::new(self.parse_and()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_and(&mut self) -> Result<Node> {
+        let mut node = self.parse_primary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            node = Node::And(Box::new(node), Box::new(self.parse_primary()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_primary(&mut self) -> Result<Node> {
+        match self.next() {
+            Some(Token::LParen) => {
+                let node = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(node),
+                    _ => bail!("Expected )"),
+                }
+            }
+            Some(Token::Word(column_name)) => {
+                let column = match self.headers.iter().position(|h| h == column_name) {
+                    Some(i) => i,
+                    None => bail!("Unknown column: {}", column_name),
+                };
+                match (self.next(), self.next()) {
+                    (Some(Token::Op(op)), Some(Token::Word(value))) => Ok(Node::Compare {
+                        column,
+                        op: *op,
+                        value: value.clone(),
+                        number: value.parse::<f64>().ok(),
+                    }),
+                    (Some(Token::Match { negated }), Some(Token::Word(pattern))) => {
+                        let regex = match Regex::new(pattern) {
+                            Ok(regex) => regex,
+                            Err(_) => bail!("Invalid regex: {}", pattern),
+                        };
+                        Ok(Node::Match {
+                            column,
+                            regex,
+                            negated: *negated,
+                        })
+                    }
+                    _ => bail!("Expected a comparison like: column > 10"),
+                }
+            }
+            _ => bail!("Expected a comparison like: column > 10"),
+        }
+    }
+}
+
+/// A predicate on the fields of a row, e.g. `amount > 1000`. Comparisons can
+/// be combined with AND, OR and parentheses, and `~` matches a field against
+/// a regex. Columns are referred to by their header names, which can be
+/// quoted if they contain spaces or operator characters.
+#[derive(Clone, Debug)]
+pub struct Expr {
+    source: String,
+    root: Node,
+}
+
+impl Expr {
+    pub fn parse(s: &str, headers: &[String]) -> Result<Expr> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            headers,
+        };
+        let root = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            match token {
+                Token::RParen => bail!("Unmatched )"),
+                _ => bail!("Expected AND or OR"),
+            }
+        }
+        Ok(Expr {
+            source: s.trim().to_owned(),
+            root,
+        })
+    }
+
+    pub fn is_match(&self, record: &StringRecord) -> bool {
+        self.root.is_match(record)
+    }
+
+    /// Indices of columns referred to in the expression
+    pub fn column_indices(&self) -> Vec<usize> {
+        let mut out = vec![];
+        self.root.column_indices(&mut out);
+        out
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> Vec<String> {
+        vec!["name".into(), "amount".into(), "unit price".into()]
+    }
+
+    fn is_match(s: &str, fields: Vec<&str>) -> bool {
+        let expr = Expr::parse(s, &headers()).unwrap();
+        expr.is_match(&StringRecord::from(fields))
+    }
+
+    #[test]
+    fn test_numeric() {
+        assert!(is_match("amount > 1000", vec!["a", "1500", "1"]));
+        assert!(!is_match("amount > 1000", vec!["a", "999.5", "1"]));
+        assert!(is_match("amount>=1000", vec!["a", " 1000 ", "1"]));
+        assert!(is_match("amount <= 1e3", vec!["a", "1000", "1"]));
+        assert!(is_match("amount == 10", vec!["a", "10.0", "1"]));
+        assert!(!is_match("amount < 10", vec!["a", "", "1"]));
+        assert!(is_match("amount != 10", vec!["a", "", "1"]));
+        assert!(is_match("\"unit price\" < 2", vec!["a", "", "1"]));
+    }
+
+    #[test]
+    fn test_text() {
+        assert!(is_match("name == bob", vec!["bob", "1", "1"]));
+        assert!(is_match("name != bob", vec!["alice", "1", "1"]));
+        assert!(is_match(
+            "name == \"bob smith\"",
+            vec!["bob smith", "1", "1"]
+        ));
+        assert!(is_match("name < bob", vec!["alice", "1", "1"]));
+    }
+
+    #[test]
+    fn test_regex() {
+        assert!(is_match("name ~ bo", vec!["bob", "1", "1"]));
+        assert!(is_match("name ~ \"^a.*e$\"", vec!["alice", "1", "1"]));
+        assert!(!is_match("name !~ bo", vec!["bob", "1", "1"]));
+        assert!(is_match("name!~bo", vec!["alice", "1", "1"]));
+    }
+
+    #[test]
+    fn test_compound() {
+        let s = "name == bob AND amount > 100";
+        assert!(is_match(s, vec!["bob", "150", "1"]));
+        assert!(!is_match(s, vec!["bob", "50", "1"]));
+        assert!(!is_match(s, vec!["alice", "150", "1"]));
+
+        // AND binds tighter than OR
+        let s = "name == bob or name == alice and amount > 100";
+        assert!(is_match(s, vec!["bob", "50", "1"]));
+        assert!(!is_match(s, vec!["alice", "50", "1"]));
+
+        let s = "(name == bob or name == alice) and amount > 100";
+        assert!(!is_match(s, vec!["bob", "50", "1"]));
+        assert!(is_match(s, vec!["alice", "150", "1"]));
+
+        // Quoted keywords are plain words
+        assert!(is_match("name == \"and\"", vec!["and", "1", "1"]));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Expr::parse("price > 10", &headers()).is_err());
+        assert!(Expr::parse("amount >", &headers()).is_err());
+        assert!(Expr::parse("amount > \"10", &headers()).is_err());
+        assert!(Expr::parse("amount 10", &headers()).is_err());
+        assert!(Expr::parse("(amount > 10", &headers()).is_err());
+        assert!(Expr::parse("amount > 10)", &headers()).is_err());
+        assert!(Expr::parse("amount > 10 and", &headers()).is_err());
+        assert!(Expr::parse("amount > 10 name == a", &headers()).is_err());
+        assert!(Expr::parse("name ~ \"(\"", &headers()).is_err());
+        assert!(Expr::parse("", &headers()).is_err());
+    }
+
+    #[test]
+    fn test_column_indices() {
+        let expr = Expr::parse("\"unit price\" > 10", &headers()).unwrap();
+        assert_eq!(expr.column_indices(), vec![2]);
+        assert_eq!(expr.to_string(), "\"unit price\" > 10");
+        let expr = Expr::parse("amount > 1 or (name ~ a and amount < 5)", &headers()).unwrap();
+        assert_eq!(expr.column_indices(), vec![1, 0]);
+    }
+}
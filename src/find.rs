@@ -1,16 +1,219 @@
 use crate::csv;
-use anyhow::Result;
+use crate::expr::Expr;
+use anyhow::{anyhow, Result};
 use regex::Regex;
 use std::cmp::min;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::thread::{self};
 use std::time::Instant;
 
+/// What to look for in rows: either a regex matched against every field, an
+/// expression evaluated on the whole row, or rows that are duplicated
+#[derive(Clone, Debug)]
+pub enum Target {
+    Regex(Regex),
+    /// Regex matched only against one column, e.g. `status:active`
+    Column(ColumnRegex),
+    Expr(Expr),
+    Duplicates(Duplicates),
+}
+
+#[derive(Clone, Debug)]
+pub struct ColumnRegex {
+    column: usize,
+    name: String,
+    regex: Regex,
+}
+
+impl ColumnRegex {
+    /// Parse `column:regex` if the part before the first `:` is a column name
+    fn parse(s: &str, headers: &[String]) -> Option<Result<ColumnRegex>> {
+        let (name, pattern) = s.split_once(':')?;
+        let column = headers.iter().position(|h| h == name)?;
+        let column = Regex::new(pattern)
+            .map(|regex| ColumnRegex {
+                column,
+                name: name.to_owned(),
+                regex,
+            })
+            .map_err(|_| anyhow!("Invalid regex: {}", pattern));
+        Some(column)
+    }
+}
+
+/// Rows with the same values as another row, in all columns or in the key
+/// column if any
+#[derive(Clone, Debug)]
+pub struct Duplicates {
+    column: Option<(usize, String)>,
+}
+
+impl Duplicates {
+    /// Parse `dup()` for duplicates of whole rows, or `dup(column)` for
+    /// duplicates in a key column
+    fn parse(s: &str, headers: &[String]) -> Option<Result<Duplicates>> {
+        let arg = s.trim().strip_prefix("dup(")?.strip_suffix(')')?.trim();
+        if arg.is_empty() {
+            return Some(Ok(Duplicates { column: None }));
+        }
+        let name = arg.trim_matches('"');
+        let column = match headers.iter().position(|h| h == name) {
+            Some(i) => Ok(Duplicates {
+                column: Some((i, name.to_owned())),
+            }),
+            None => Err(anyhow!("Unknown column: {}", name)),
+        };
+        Some(column)
+    }
+
+    /// Hash of the values compared to find duplicates. Rows with different
+    /// values may collide, but that is very unlikely with 64-bit hashes.
+    pub fn key<'a, I: IntoIterator<Item = &'a str>>(&self, fields: I) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        match self.column {
+            Some((i, _)) => fields.into_iter().nth(i).hash(&mut hasher),
+            None => fields.into_iter().for_each(|f| f.hash(&mut hasher)),
+        }
+        hasher.finish()
+    }
+
+    fn column_indices(&self, num_fields: usize) -> Vec<usize> {
+        match self.column {
+            Some((i, _)) => vec![i],
+            None => (0..num_fields).collect(),
+        }
+    }
+}
+
+impl Target {
+    /// Parse a regex, or an expression (e.g. amount > 1000) if prefixed with :
+    /// where :dup() and :dup(column) find duplicates. A regex prefixed with a
+    /// column name and : only matches in that column.
+    pub fn parse(s: &str, headers: &[String]) -> Result<Target> {
+        if let Some(expr) = s.strip_prefix(':') {
+            if let Some(duplicates) = Duplicates::parse(expr, headers) {
+                return duplicates.map(Target::Duplicates);
+            }
+            Expr::parse(expr, headers)
+                .map(Target::Expr)
+                .map_err(|e| anyhow!("Invalid expression: {}", e))
+        } else if let Some(column) = ColumnRegex::parse(s, headers) {
+            column.map(Target::Column)
+        } else {
+            Regex::new(s)
+                .map(Target::Regex)
+                .map_err(|_| anyhow!("Invalid regex: {}", s))
+        }
+    }
+
+    /// Whether the row matches, which for duplicates is decided by the caller
+    /// since it depends on the other rows
+    pub fn is_match(&self, record: &::csv::StringRecord) -> bool {
+        match self {
+            Target::Regex(re) => record.iter().any(|field| re.is_match(field)),
+            Target::Column(c) => record.get(c.column).is_some_and(|f| c.regex.is_match(f)),
+            Target::Expr(expr) => expr.is_match(record),
+            Target::Duplicates(_) => true,
+        }
+    }
+
+    /// Regex for highlighting matches within fields, if applicable
+    pub fn regex(&self) -> Option<&Regex> {
+        match self {
+            Target::Regex(re) => Some(re),
+            Target::Column(c) => Some(&c.regex),
+            _ => None,
+        }
+    }
+
+    /// Index and name of the only column matched against, if any
+    pub fn column(&self) -> Option<(usize, &str)> {
+        match self {
+            Target::Column(c) => Some((c.column, c.name.as_str())),
+            _ => None,
+        }
+    }
+
+    /// Pattern that parses to the target again
+    pub fn pattern(&self) -> String {
+        match self {
+            Target::Regex(re) => re.to_string(),
+            Target::Column(c) => format!("{}:{}", c.name, c.regex),
+            Target::Expr(expr) => format!(":{}", expr),
+            Target::Duplicates(Duplicates { column: None }) => ":dup()".to_owned(),
+            Target::Duplicates(Duplicates {
+                column: Some((_, name)),
+            }) => format!(":dup({})", name),
+        }
+    }
+
+    pub fn is_duplicates(&self) -> bool {
+        matches!(self, Target::Duplicates(_))
+    }
+
+    /// Indices of the matched columns, only looking at the given columns if
+    /// any. Expressions and column regexes are evaluated on the whole row
+    /// regardless since they name their columns anyway.
+    fn matched_column_indices(
+        &self,
+        record: &::csv::StringRecord,
+        columns: Option<&[usize]>,
+    ) -> Vec<usize> {
+        match (self, columns) {
+            (Target::Regex(re), Some(columns)) => columns
+                .iter()
+                .copied()
+                .filter(|&i| record.get(i).is_some_and(|field| re.is_match(field)))
+                .collect(),
+            (Target::Regex(re), None) => record
+                .iter()
+                .enumerate()
+                .filter(|(_, field)| re.is_match(field))
+                .map(|(i, _)| i)
+                .collect(),
+            (Target::Column(c), _) => {
+                if self.is_match(record) {
+                    vec![c.column]
+                } else {
+                    vec![]
+                }
+            }
+            (Target::Expr(expr), _) => {
+                if expr.is_match(record) {
+                    expr.column_indices()
+                } else {
+                    vec![]
+                }
+            }
+            // Needs counts of all rows, see FinderInternalState::init
+            (Target::Duplicates(_), _) => vec![],
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Regex(re) => write!(f, "{}", re),
+            Target::Column(c) => write!(f, "{}:{}", c.name, c.regex),
+            Target::Expr(expr) => write!(f, "{}", expr),
+            Target::Duplicates(Duplicates { column: None }) => write!(f, "duplicate rows"),
+            Target::Duplicates(Duplicates {
+                column: Some((_, name)),
+            }) => write!(f, "duplicates of {}", name),
+        }
+    }
+}
+
 pub struct Finder {
     internal: Arc<Mutex<FinderInternalState>>,
     cursor: Option<usize>,
     row_hint: usize,
-    target: Regex,
+    target: Target,
 }
 
 #[derive(Clone, Debug)]
@@ -28,14 +231,24 @@ impl FoundRecord {
         &self.column_indices
     }
 
-    pub fn first_column(&self) -> usize {
-        *self.column_indices.first().unwrap()
+    /// The record with its column indices converted, e.g. to indices among
+    /// the shown columns, leaving out those that can't be
+    pub fn map_column_indices<F: Fn(usize) -> Option<usize>>(&self, f: F) -> FoundRecord {
+        FoundRecord {
+            row_index: self.row_index,
+            column_indices: self.column_indices.iter().filter_map(|&i| f(i)).collect(),
+        }
     }
 }
 
 impl Finder {
-    pub fn new(config: Arc<csv::CsvConfig>, target: Regex) -> Result<Self> {
-        let internal = FinderInternalState::init(config, target.clone());
+    /// Find rows matching the target, only looking at the given columns if any
+    pub fn new(
+        config: Arc<csv::CsvConfig>,
+        target: Target,
+        columns: Option<Vec<usize>>,
+    ) -> Result<Self> {
+        let internal = FinderInternalState::init(config, target.clone(), columns);
         let finder = Finder {
             internal,
             cursor: None,
@@ -63,7 +276,7 @@ impl Finder {
             .map(|x| x.row_index())
     }
 
-    pub fn target(&self) -> Regex {
+    pub fn target(&self) -> Target {
         self.target.clone()
     }
 
@@ -142,6 +355,18 @@ impl Finder {
             .collect();
         indices
     }
+
+    /// Row indices found between first and last, inclusive
+    pub fn found_between(&self, first: usize, last: usize) -> Vec<usize> {
+        let m_guard = self.internal.lock().unwrap();
+        let founds = &m_guard.founds;
+        let start = founds.partition_point(|x| x.row_index() < first);
+        founds[start..]
+            .iter()
+            .map(|x| x.row_index())
+            .take_while(|&i| i <= last)
+            .collect()
+    }
 }
 
 impl Drop for Finder {
@@ -159,7 +384,11 @@ struct FinderInternalState {
 }
 
 impl FinderInternalState {
-    pub fn init(config: Arc<csv::CsvConfig>, target: Regex) -> Arc<Mutex<FinderInternalState>> {
+    pub fn init(
+        config: Arc<csv::CsvConfig>,
+        target: Target,
+        columns: Option<Vec<usize>>,
+    ) -> Arc<Mutex<FinderInternalState>> {
         let internal = FinderInternalState {
             count: 0,
             founds: vec![],
@@ -174,36 +403,60 @@ impl FinderInternalState {
         let _filename = config.filename().to_owned();
 
         let _handle = thread::spawn(move || {
+            let start = Instant::now();
+
+            // Duplicates are only known after counting all rows once
+            let counts = match &target {
+                Target::Duplicates(duplicates) => {
+                    match Self::count_keys(&config, duplicates, &_m) {
+                        Some(counts) => Some(counts),
+                        None => return,
+                    }
+                }
+                _ => None,
+            };
+
             let mut bg_reader = config.new_reader().unwrap();
 
             // note that records() exludes header
             let records = bg_reader.records();
 
-            let start = Instant::now();
+            // Found rows are added in batches so that the lock shared with
+            // the viewer isn't taken for every row
+            let batch_rows = config.find_batch_rows.max(1);
+            let mut batch = vec![];
             for (row_index, r) in records.enumerate() {
                 let mut column_indices = vec![];
                 if let Ok(valid_record) = r {
-                    for (column_index, field) in valid_record.iter().enumerate() {
-                        if target.is_match(field) {
-                            column_indices.push(column_index);
+                    column_indices = match (&target, &counts) {
+                        (Target::Duplicates(duplicates), Some(counts)) => {
+                            let key = duplicates.key(&valid_record);
+                            if counts.get(&key).copied().unwrap_or(0) > 1 {
+                                duplicates.column_indices(valid_record.len())
+                            } else {
+                                vec![]
+                            }
                         }
-                    }
+                        _ => target.matched_column_indices(&valid_record, columns.as_deref()),
+                    };
                 }
                 if !column_indices.is_empty() {
-                    let found = FoundRecord {
+                    batch.push(FoundRecord {
                         row_index,
                         column_indices,
-                    };
-                    let mut m = _m.lock().unwrap();
-                    (*m).found_one(found);
+                    });
                 }
-                let m = _m.lock().unwrap();
-                if m.should_terminate {
-                    break;
+                if (row_index + 1) % batch_rows == 0 {
+                    let mut m = _m.lock().unwrap();
+                    (*m).found_batch(&mut batch);
+                    if m.should_terminate {
+                        break;
+                    }
                 }
             }
 
             let mut m = _m.lock().unwrap();
+            (*m).found_batch(&mut batch);
             m.done = true;
             m.elapsed = Some(start.elapsed().as_micros());
         });
@@ -211,9 +464,28 @@ impl FinderInternalState {
         m_state
     }
 
-    fn found_one(&mut self, found: FoundRecord) {
-        self.founds.push(found);
-        self.count += 1;
+    /// Number of rows with each key, or None if terminated meanwhile
+    fn count_keys(
+        config: &csv::CsvConfig,
+        duplicates: &Duplicates,
+        m_state: &Mutex<FinderInternalState>,
+    ) -> Option<HashMap<u64, usize>> {
+        let mut bg_reader = config.new_reader().unwrap();
+        let mut counts = HashMap::new();
+        for (i, r) in bg_reader.records().enumerate() {
+            if let Ok(valid_record) = r {
+                *counts.entry(duplicates.key(&valid_record)).or_insert(0) += 1;
+            }
+            if i % config.find_batch_rows.max(1) == 0 && m_state.lock().unwrap().should_terminate {
+                return None;
+            }
+        }
+        Some(counts)
+    }
+
+    fn found_batch(&mut self, batch: &mut Vec<FoundRecord>) {
+        self.count += batch.len();
+        self.founds.append(batch);
     }
 
     fn next_from(&self, row_hint: usize) -> usize {
@@ -241,3 +513,31 @@ impl FinderInternalState {
         self.elapsed
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time;
+
+    fn found_rows(batch_rows: usize) -> Vec<usize> {
+        let mut config = csv::CsvConfig::new("tests/data/simple.csv");
+        config.find_batch_rows = batch_rows;
+        let target = Target::parse("A49", &[]).unwrap();
+        let finder = Finder::new(Arc::new(config), target, None).unwrap();
+        while !finder.done() {
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        finder.found_between(0, usize::MAX)
+    }
+
+    #[test]
+    fn test_find_batch_rows() {
+        let expected = found_rows(1000);
+        assert_eq!(expected.len(), 111);
+        assert_eq!(expected[..2], [48, 489]);
+        // Batches that don't divide the rows evenly still find all of them
+        assert_eq!(found_rows(7), expected);
+        assert_eq!(found_rows(1), expected);
+        assert_eq!(found_rows(100_000), expected);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::csv::CsvConfig;
+use crate::export::{ExportOptions, Format, RowWriter};
+use crate::find::Target;
+use crate::view::ColumnsFilter;
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Write the rows of a file matching the filter, or all rows if no filter
+/// is given, without showing anything interactive. Only columns with names
+/// matching the columns regex are written, unless none match. Rows are
+/// written as they are read, after counting them first for duplicates.
+pub fn print<W: Write>(
+    out: W,
+    config: &CsvConfig,
+    filter: Option<&str>,
+    columns: Option<&str>,
+    format: Format,
+    options: &ExportOptions,
+) -> Result<()> {
+    let mut reader = config.new_reader()?;
+    let headers: Vec<String> = reader.headers()?.iter().map(|s| s.to_owned()).collect();
+    let target = match filter {
+        Some(s) => Some(Target::parse(s, &headers)?),
+        None => None,
+    };
+
+    let duplicate_counts = match &target {
+        Some(Target::Duplicates(duplicates)) => {
+            let mut counts = HashMap::new();
+            for record in config.new_reader()?.records() {
+                *counts.entry(duplicates.key(record?.iter())).or_insert(0) += 1;
+            }
+            Some((duplicates, counts))
+        }
+        _ => None,
+    };
+
+    let mut selected_columns = None;
+    if let Some(s) = columns {
+        let columns_filter = ColumnsFilter::parse(s, &headers)?;
+        if !columns_filter.disabled_because_no_match() {
+            selected_columns = Some(columns_filter.indices().to_vec());
+        }
+    }
+    let select = |fields: Vec<String>| -> Vec<String> {
+        match &selected_columns {
+            Some(indices) => indices
+                .iter()
+                .filter_map(|&i| fields.get(i).cloned())
+                .collect(),
+            None => fields,
+        }
+    };
+
+    let mut writer = RowWriter::new(
+        out,
+        format,
+        &select(headers.clone()),
+        options,
+        config.delimiter,
+    )?;
+    for record in reader.records() {
+        let record = record?;
+        let is_match = match (&duplicate_counts, &target) {
+            (Some((duplicates, counts)), _) => counts[&duplicates.key(record.iter())] > 1,
+            (None, Some(target)) => target.is_match(&record),
+            (None, None) => true,
+        };
+        if is_match {
+            writer.write_row(&select(record.iter().map(|s| s.to_owned()).collect()))?;
+        }
+    }
+    writer.finish()
+}
+
+/// Whether writing failed because the reader went away, e.g. `head` exiting
+/// after enough lines, which is no reason to report an error
+pub fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        let io_error = match cause.downcast_ref::<::csv::Error>() {
+            Some(csv_error) => match csv_error.kind() {
+                ::csv::ErrorKind::Io(e) => Some(e),
+                _ => None,
+            },
+            None => cause.downcast_ref::<io::Error>(),
+        };
+        matches!(io_error, Some(e) if e.kind() == io::ErrorKind::BrokenPipe)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn print_to_string(filter: Option<&str>, format: Format) -> String {
+        print_columns_to_string(filter, None, format)
+    }
+
+    fn print_columns_to_string(
+        filter: Option<&str>,
+        columns: Option<&str>,
+        format: Format,
+    ) -> String {
+        let config = CsvConfig::new("tests/data/cities.csv");
+        let mut out = vec![];
+        let options = ExportOptions::default();
+        print(&mut out, &config, filter, columns, format, &options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_print() {
+        let out = print_to_string(None, Format::Csv);
+        assert_eq!(
+            out,
+            std::fs::read_to_string("tests/data/cities.csv").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_print_filtered() {
+        let out = print_to_string(Some("Youngstown"), Format::Csv);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].contains("Youngstown"));
+
+        let out = print_to_string(Some(":State == KS"), Format::Tsv);
+        assert!(out.lines().count() > 2);
+        assert!(out.lines().skip(1).all(|line| line.ends_with("\tKS")));
+
+        let out = print_to_string(Some(":dup(State)"), Format::Csv);
+        assert!(out.lines().skip(1).all(|line| !line.ends_with(",AZ")));
+        assert!(out.lines().skip(1).any(|line| line.ends_with(",KS")));
+
+        let config = CsvConfig::new("tests/data/cities.csv");
+        let options = ExportOptions::default();
+        let result = print(
+            vec![],
+            &config,
+            Some(":Nope = 1"),
+            None,
+            Format::Csv,
+            &options,
+        );
+        assert!(result.is_err());
+    }
+
+    /// Writer of a pipe closed after some bytes, like head exiting
+    struct ClosingWriter {
+        written: usize,
+        limit: usize,
+    }
+
+    impl Write for ClosingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written >= self.limit {
+                return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+            }
+            let n = buf.len().min(self.limit - self.written);
+            self.written += n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_print_broken_pipe() {
+        let config = CsvConfig::new("tests/data/simple.csv");
+        let options = ExportOptions::default();
+        for format in [Format::Csv, Format::Json] {
+            let mut out = ClosingWriter {
+                written: 0,
+                limit: 100,
+            };
+            let error = print(&mut out, &config, None, None, format, &options).unwrap_err();
+            assert!(is_broken_pipe(&error), "{:?}", error);
+            assert_eq!(out.written, 100);
+        }
+
+        let error = print(
+            &mut vec![],
+            &config,
+            Some(":Nope = 1"),
+            None,
+            Format::Csv,
+            &options,
+        )
+        .unwrap_err();
+        assert!(!is_broken_pipe(&error));
+    }
+
+    #[test]
+    fn test_print_columns() {
+        let out = print_columns_to_string(Some("Youngstown"), Some("City|State"), Format::Csv);
+        assert_eq!(out, "City,State\nYoungstown,OH\n");
+
+        let out = print_columns_to_string(Some("Youngstown"), Some("!^L|NS|EW"), Format::Csv);
+        assert_eq!(out, "City,State\nYoungstown,OH\n");
+
+        // All columns if none match
+        let out = print_columns_to_string(None, Some("Nope"), Format::Csv);
+        assert_eq!(out, print_to_string(None, Format::Csv));
+    }
+}
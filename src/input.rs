@@ -1,7 +1,10 @@
 use crate::util::events::{CsvlensEvent, CsvlensEvents};
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use anyhow::{bail, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use std::collections::HashMap;
+use std::str::FromStr;
 
+#[derive(Debug, PartialEq)]
 pub enum Control {
     ScrollUp,
     ScrollDown,
@@ -13,12 +16,78 @@ pub enum Control {
     ScrollPageDown,
     ScrollPageLeft,
     ScrollPageRight,
+    CenterColumn,
     ScrollTo(usize),
+    ScrollToPercent(u8),
     ScrollToNextFound,
     ScrollToPrevFound,
     Find(String),
     Filter(String),
+    Count(String),
+    ClearCount,
     FilterColumns(String),
+    ReplacePreview(String),
+    ClearReplacePreview,
+    ReopenAsFixedWidth,
+    MoveColumnStart(isize),
+    SetColumnStarts(String),
+    ToggleFindAllColumns,
+    ToggleTruncateSide,
+    AutofitColumn,
+    AutofitColumns,
+    ResetColumnWidths,
+    CycleAlignment,
+    ResetColumnAlignments,
+    ToggleDetail,
+    ToggleExpandRow,
+    ToggleDetailRaw,
+    ToggleRawFields,
+    EditCell,
+    SetCell(String),
+    Sort,
+    // Clicked at the column and row of the terminal
+    MouseClick(u16, u16),
+    FreezePanes,
+    ToggleWhitespace,
+    ToggleCompact,
+    ToggleGrid,
+    ToggleChanges,
+    ToggleRuler,
+    ToggleStatus,
+    ToggleCellPosition,
+    ToggleCellLength,
+    ToggleBooleans,
+    ToggleScientific,
+    Pipe,
+    CycleGutterMode,
+    ResetDisplayOptions,
+    SelectUp,
+    SelectDown,
+    ClearSelection,
+    CopySelection,
+    CopyColumnName,
+    CopyCell,
+    CopyCommandLine,
+    ToggleMark,
+    ClearMarks,
+    MarkAll,
+    InvertMarks,
+    Refresh,
+    ExportMarked(String),
+    ClearFilter,
+    ReapplyFilter,
+    ShowEmptyInColumn,
+    NextValueChange,
+    PrevValueChange,
+    FirstSameValue,
+    LastSameValue,
+    ShowNonEmptyInColumn,
+    ClearColumnsFilter,
+    ReopenWithSuggestedDelimiter,
+    NextColumnGroup,
+    NextTab,
+    PrevTab,
+    CloseTab,
     Quit,
     BufferContent(String),
     BufferReset,
@@ -31,6 +100,248 @@ impl Control {
     }
 }
 
+/// Action that can be run from the command palette
+pub struct Action {
+    pub name: &'static str,
+    pub key: &'static str,
+    kind: ActionKind,
+}
+
+enum ActionKind {
+    Control(fn() -> Control),
+    // Asks for input first, e.g. a regex
+    Prompt(InputMode),
+}
+
+const fn action(name: &'static str, key: &'static str, control: fn() -> Control) -> Action {
+    Action {
+        name,
+        key,
+        kind: ActionKind::Control(control),
+    }
+}
+
+const fn prompt(name: &'static str, key: &'static str, mode: InputMode) -> Action {
+    Action {
+        name,
+        key,
+        kind: ActionKind::Prompt(mode),
+    }
+}
+
+pub const ACTIONS: &[Action] = &[
+    action("Scroll down", "j", || Control::ScrollDown),
+    action("Scroll up", "k", || Control::ScrollUp),
+    action("Scroll left", "h", || Control::ScrollLeft),
+    action("Scroll right", "l", || Control::ScrollRight),
+    action("Scroll to top", "g", || Control::ScrollTop),
+    action("Scroll to bottom", "G", || Control::ScrollBottom),
+    action("Page down", "Ctrl-f", || Control::ScrollPageDown),
+    action("Page up", "Ctrl-b", || Control::ScrollPageUp),
+    action("Page left", "Ctrl-h", || Control::ScrollPageLeft),
+    action("Page right", "Ctrl-l", || Control::ScrollPageRight),
+    action("Center current column", "zh", || Control::CenterColumn),
+    action("Next row with another value in column", "}", || {
+        Control::NextValueChange
+    }),
+    action("Previous row with another value in column", "{", || {
+        Control::PrevValueChange
+    }),
+    action("First row with the same value in column", "(", || {
+        Control::FirstSameValue
+    }),
+    action("Last row with the same value in column", ")", || {
+        Control::LastSameValue
+    }),
+    prompt("Go to line", "<n>", InputMode::GotoLine),
+    prompt("Find", "/", InputMode::Find),
+    prompt("Filter rows", "&", InputMode::Filter),
+    prompt("Filter columns", "*", InputMode::FilterColumns),
+    prompt("Count matching rows", "=", InputMode::Count),
+    prompt(
+        "Preview replacing in column",
+        "P",
+        InputMode::ReplacePreview,
+    ),
+    action("Clear replacement preview", "", || {
+        Control::ClearReplacePreview
+    }),
+    action("Clear rows filter", "", || Control::ClearFilter),
+    action("Filter rows again with the last filter", "R", || {
+        Control::ReapplyFilter
+    }),
+    action("Clear columns filter", "", || Control::ClearColumnsFilter),
+    action("Filter rows where the current cell is blank", "", || {
+        Control::ShowEmptyInColumn
+    }),
+    action("Filter rows where the current cell isn't blank", "", || {
+        Control::ShowNonEmptyInColumn
+    }),
+    action("Next column group", "v", || Control::NextColumnGroup),
+    action("Next match", "n", || Control::ScrollToNextFound),
+    action("Previous match", "N", || Control::ScrollToPrevFound),
+    action("Toggle finding in all columns", "A", || {
+        Control::ToggleFindAllColumns
+    }),
+    action("Toggle truncating on the left", "t", || {
+        Control::ToggleTruncateSide
+    }),
+    action("Fit column to the values shown", "+", || {
+        Control::AutofitColumn
+    }),
+    action("Fit all columns to the values shown", "", || {
+        Control::AutofitColumns
+    }),
+    action("Reset column widths", "", || Control::ResetColumnWidths),
+    action("Align column left, right or center", "L", || {
+        Control::CycleAlignment
+    }),
+    action("Reset column alignments", "", || {
+        Control::ResetColumnAlignments
+    }),
+    action("Toggle row details", "Enter", || Control::ToggleDetail),
+    action("Toggle expanding the selected row", "o", || {
+        Control::ToggleExpandRow
+    }),
+    action("Toggle fields as in the file", "Q", || {
+        Control::ToggleRawFields
+    }),
+    action("Edit cell", "e", || Control::EditCell),
+    action("Sort by column", "S", || Control::Sort),
+    action("Freeze first column", "F", || Control::FreezePanes),
+    action("Toggle marking whitespace around values", "W", || {
+        Control::ToggleWhitespace
+    }),
+    action("Toggle compact columns", "c", || Control::ToggleCompact),
+    action("Toggle lines between columns", "B", || Control::ToggleGrid),
+    action(
+        "Toggle highlighting changes from the row above",
+        "D",
+        || Control::ToggleChanges,
+    ),
+    action("Toggle column numbers above the headers", "I", || {
+        Control::ToggleRuler
+    }),
+    action("Toggle the status bar", "Z", || Control::ToggleStatus),
+    action("Toggle the cell position in the status bar", "", || {
+        Control::ToggleCellPosition
+    }),
+    action("Toggle the cell length in the status bar", "U", || {
+        Control::ToggleCellLength
+    }),
+    action("Toggle check marks for true and false", "T", || {
+        Control::ToggleBooleans
+    }),
+    action(
+        "Toggle scientific notation for large and small numbers",
+        "^",
+        || Control::ToggleScientific,
+    ),
+    action("Pipe to command", "|", || Control::Pipe),
+    action("Cycle row numbers", "#", || Control::CycleGutterMode),
+    action("Reset display options", "", || Control::ResetDisplayOptions),
+    action("Select down", "J", || Control::SelectDown),
+    action("Select up", "K", || Control::SelectUp),
+    action("Clear selection", "Esc", || Control::ClearSelection),
+    action("Copy selected rows", "y", || Control::CopySelection),
+    action("Copy column name", "Y", || Control::CopyColumnName),
+    action("Copy cell with column name", "V", || Control::CopyCell),
+    action("Copy command line", "C", || Control::CopyCommandLine),
+    action("Toggle mark", "m", || Control::ToggleMark),
+    action("Clear marks", "M", || Control::ClearMarks),
+    action("Mark all rows", "Ctrl-a", || Control::MarkAll),
+    action("Invert marks", "~", || Control::InvertMarks),
+    prompt("Export marked rows", "E", InputMode::ExportMarked),
+    action("Next tab", "Tab", || Control::NextTab),
+    action("Previous tab", "Shift-Tab", || Control::PrevTab),
+    action("Close tab", "X", || Control::CloseTab),
+    action("Reopen with the suggested delimiter", "", || {
+        Control::ReopenWithSuggestedDelimiter
+    }),
+    action("Reopen as fixed-width columns", "", || {
+        Control::ReopenAsFixedWidth
+    }),
+    action("Move column start left", "[", || {
+        Control::MoveColumnStart(-1)
+    }),
+    action("Move column start right", "]", || {
+        Control::MoveColumnStart(1)
+    }),
+    prompt("Set fixed-width column starts", "", InputMode::ColumnStarts),
+    action("Redraw the screen", "Ctrl-r", || Control::Refresh),
+    action("Quit", "q", || Control::Quit),
+];
+
+/// Actions with names containing the query, followed by those containing the
+/// characters of the query in order, ignoring case
+pub fn matching_actions(query: &str) -> Vec<&'static Action> {
+    let query = query.to_lowercase();
+    let is_subsequence = |name: &str| {
+        let mut chars = name.chars();
+        query.chars().all(|c| chars.any(|n| n == c))
+    };
+    let (mut contained, mut others) = (vec![], vec![]);
+    for action in ACTIONS {
+        let name = action.name.to_lowercase();
+        if name.contains(&query) {
+            contained.push(action);
+        } else if is_subsequence(&name) {
+            others.push(action);
+        }
+    }
+    contained.append(&mut others);
+    contained
+}
+
+/// Key that can be bound to an action, e.g. `q`, `Esc` or `Ctrl-c`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    ctrl: bool,
+}
+
+impl Key {
+    fn matches(&self, key_event: &KeyEvent) -> bool {
+        // SHIFT is part of capitalised characters already
+        let modifiers = key_event.modifiers - KeyModifiers::SHIFT;
+        let expected = if self.ctrl {
+            KeyModifiers::CONTROL
+        } else {
+            KeyModifiers::NONE
+        };
+        key_event.code == self.code && modifiers == expected
+    }
+}
+
+impl FromStr for Key {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (rest, ctrl) = match s.strip_prefix("Ctrl-").or_else(|| s.strip_prefix("ctrl-")) {
+            Some(rest) => (rest, true),
+            None => (s, false),
+        };
+        let code = match rest.to_ascii_lowercase().as_str() {
+            "esc" => KeyCode::Esc,
+            "enter" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if ctrl => KeyCode::Char(c.to_ascii_lowercase()),
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => bail!(
+                        "Unknown key: {} (should be a character, Esc, Enter, Tab or Backspace, optionally prefixed with Ctrl-)",
+                        s
+                    ),
+                }
+            }
+        };
+        Ok(Key { code, ctrl })
+    }
+}
+
 enum BufferState {
     Active(String),
     Inactive,
@@ -43,6 +354,14 @@ pub enum InputMode {
     Find,
     Filter,
     FilterColumns,
+    Count,
+    ReplacePreview,
+    ColumnStarts,
+    ExportMarked,
+    Palette,
+    ConfirmQuit,
+    EditCell,
+    ConfirmEdit,
 }
 
 pub struct BufferHistory {
@@ -70,6 +389,13 @@ pub struct InputHandler {
     mode: InputMode,
     buffer_state: BufferState,
     buffer_history: BufferHistory,
+    quit_keys: Vec<Key>,
+    confirm_quit: bool,
+    // First key of a two key command, e.g. z of zh
+    pending_prefix: Option<char>,
+    // Value to write once the first edit is confirmed
+    pending_edit: Option<String>,
+    edit_confirmed: bool,
 }
 
 impl InputHandler {
@@ -79,26 +405,75 @@ impl InputHandler {
             mode: InputMode::Default,
             buffer_state: BufferState::Inactive,
             buffer_history: BufferHistory::new(),
+            quit_keys: vec![Key {
+                code: KeyCode::Char('q'),
+                ctrl: false,
+            }],
+            confirm_quit: false,
+            pending_prefix: None,
+            pending_edit: None,
+            edit_confirmed: false,
         }
     }
 
+    /// Keys quitting instead of q, which then no longer do what they did
+    pub fn set_quit_keys(&mut self, keys: Vec<Key>) {
+        self.quit_keys = keys;
+    }
+
+    /// Ask before quitting, e.g. so as not to lose filters by accident
+    pub fn set_confirm_quit(&mut self, confirm_quit: bool) {
+        self.confirm_quit = confirm_quit;
+    }
+
+    /// Stop reading keys, e.g. while running a command reading them instead
+    pub fn set_paused(&self, paused: bool) {
+        self.events.set_paused(paused);
+    }
+
     pub fn next(&mut self) -> Control {
-        if let CsvlensEvent::Input(key) = self.events.next().unwrap() {
-            if self.is_input_buffering() {
-                return self.handler_buffering(key);
-            } else {
-                return self.handler_default(key);
+        match self.events.next().unwrap() {
+            CsvlensEvent::Input(key) => {
+                if self.is_input_buffering() {
+                    self.handler_buffering(key)
+                } else {
+                    self.handler_default(key)
+                }
+            }
+            CsvlensEvent::Mouse(mouse_event) if !self.is_input_buffering() => {
+                self.handler_mouse(mouse_event)
             }
+            // tick event, no need to distinguish it for now
+            _ => Control::Nothing,
+        }
+    }
+
+    fn handler_mouse(&mut self, mouse_event: MouseEvent) -> Control {
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                Control::MouseClick(mouse_event.column, mouse_event.row)
+            }
+            MouseEventKind::ScrollDown => Control::ScrollDown,
+            MouseEventKind::ScrollUp => Control::ScrollUp,
+            _ => Control::Nothing,
         }
-        // tick event, no need to distinguish it for now
-        Control::Nothing
     }
 
     fn handler_default(&mut self, key_event: KeyEvent) -> Control {
+        // Other keys after a prefix do what they do on their own
+        if let Some(prefix) = self.pending_prefix.take() {
+            if let ('z', KeyCode::Char('h')) = (prefix, key_event.code) {
+                return Control::CenterColumn;
+            }
+        }
+        if self.is_quit_key(&key_event) {
+            return self.quit_control();
+        }
         match key_event.modifiers {
             // SHIFT needed to capture capitalised characters
             KeyModifiers::NONE | KeyModifiers::SHIFT => match key_event.code {
-                KeyCode::Char('q') => Control::Quit,
+                KeyCode::Down if key_event.modifiers == KeyModifiers::SHIFT => Control::SelectDown,
+                KeyCode::Up if key_event.modifiers == KeyModifiers::SHIFT => Control::SelectUp,
                 KeyCode::Char('j') | KeyCode::Down => Control::ScrollDown,
                 KeyCode::Char('k') | KeyCode::Up => Control::ScrollUp,
                 KeyCode::Char('l') | KeyCode::Right => Control::ScrollRight,
@@ -107,6 +482,57 @@ impl InputHandler {
                 KeyCode::Char('G') => Control::ScrollBottom,
                 KeyCode::Char('n') => Control::ScrollToNextFound,
                 KeyCode::Char('N') => Control::ScrollToPrevFound,
+                KeyCode::Char('t') => Control::ToggleTruncateSide,
+                KeyCode::Char('+') => Control::AutofitColumn,
+                KeyCode::Char('L') => Control::CycleAlignment,
+                KeyCode::Char('}') => Control::NextValueChange,
+                KeyCode::Char('{') => Control::PrevValueChange,
+                KeyCode::Char('(') => Control::FirstSameValue,
+                KeyCode::Char(')') => Control::LastSameValue,
+                KeyCode::Enter => Control::ToggleDetail,
+                KeyCode::Char('o') => Control::ToggleExpandRow,
+                KeyCode::Char('r') => Control::ToggleDetailRaw,
+                KeyCode::Char('Q') => Control::ToggleRawFields,
+                KeyCode::Char('e') => Control::EditCell,
+                KeyCode::Char('z') => {
+                    self.pending_prefix = Some('z');
+                    Control::Nothing
+                }
+                KeyCode::Char('S') => Control::Sort,
+                KeyCode::Char('F') => Control::FreezePanes,
+                KeyCode::Char('W') => Control::ToggleWhitespace,
+                KeyCode::Char('c') => Control::ToggleCompact,
+                KeyCode::Char('B') => Control::ToggleGrid,
+                KeyCode::Char('D') => Control::ToggleChanges,
+                KeyCode::Char('I') => Control::ToggleRuler,
+                KeyCode::Char('Z') => Control::ToggleStatus,
+                KeyCode::Char('U') => Control::ToggleCellLength,
+                KeyCode::Char('T') => Control::ToggleBooleans,
+                KeyCode::Char('^') => Control::ToggleScientific,
+                KeyCode::Char('R') => Control::ReapplyFilter,
+                KeyCode::Char('|') => Control::Pipe,
+                KeyCode::Char('v') => Control::NextColumnGroup,
+                KeyCode::Char('#') => Control::CycleGutterMode,
+                KeyCode::Char('A') => Control::ToggleFindAllColumns,
+                KeyCode::Char('J') => Control::SelectDown,
+                KeyCode::Char('K') => Control::SelectUp,
+                KeyCode::Char('y') => Control::CopySelection,
+                KeyCode::Char('Y') => Control::CopyColumnName,
+                KeyCode::Char('V') => Control::CopyCell,
+                KeyCode::Char('C') => Control::CopyCommandLine,
+                KeyCode::Esc => Control::ClearSelection,
+                KeyCode::Char('m') => Control::ToggleMark,
+                KeyCode::Char('M') => Control::ClearMarks,
+                KeyCode::Char('~') => Control::InvertMarks,
+                KeyCode::Char('[') => Control::MoveColumnStart(-1),
+                KeyCode::Char(']') => Control::MoveColumnStart(1),
+                KeyCode::Tab => Control::NextTab,
+                KeyCode::BackTab => Control::PrevTab,
+                KeyCode::Char('X') => Control::CloseTab,
+                KeyCode::Char('E') => {
+                    self.init_buffer(InputMode::ExportMarked);
+                    Control::empty_buffer()
+                }
                 KeyCode::PageDown => Control::ScrollPageDown,
                 KeyCode::PageUp => Control::ScrollPageUp,
                 KeyCode::Char(x) if "0123456789".contains(x.to_string().as_str()) => {
@@ -126,9 +552,27 @@ impl InputHandler {
                     self.init_buffer(InputMode::FilterColumns);
                     Control::empty_buffer()
                 }
+                KeyCode::Char('=') => {
+                    self.init_buffer(InputMode::Count);
+                    Control::empty_buffer()
+                }
+                KeyCode::Char('P') => {
+                    self.init_buffer(InputMode::ReplacePreview);
+                    Control::empty_buffer()
+                }
+                KeyCode::Char(':') => {
+                    self.init_buffer(InputMode::Palette);
+                    Control::empty_buffer()
+                }
                 _ => Control::Nothing,
             },
             KeyModifiers::CONTROL => match key_event.code {
+                KeyCode::Char('p') => {
+                    self.init_buffer(InputMode::Palette);
+                    Control::empty_buffer()
+                }
+                KeyCode::Char('a') => Control::MarkAll,
+                KeyCode::Char('r') => Control::Refresh,
                 KeyCode::Char('f') => Control::ScrollPageDown,
                 KeyCode::Char('b') => Control::ScrollPageUp,
                 KeyCode::Char('h') | KeyCode::Left => Control::ScrollPageLeft,
@@ -145,13 +589,20 @@ impl InputHandler {
             _ => "",
         };
         // SHIFT needed to capture capitalised characters
+        if self.mode == InputMode::ConfirmQuit {
+            return self.handler_confirm_quit(key_event);
+        }
+        if self.mode == InputMode::ConfirmEdit {
+            return self.handler_confirm_edit(key_event);
+        }
         if key_event.modifiers != KeyModifiers::NONE && key_event.modifiers != KeyModifiers::SHIFT {
             return Control::Nothing;
         }
         match key_event.code {
             KeyCode::Esc => {
+                let control = self.clear_control();
                 self.reset_buffer();
-                Control::BufferReset
+                control
             }
             KeyCode::Backspace => {
                 let new_buffer = match &self.buffer_state {
@@ -180,6 +631,17 @@ impl InputHandler {
                 self.reset_buffer();
                 res
             }
+            KeyCode::Char('%') if self.mode == InputMode::GotoLine => {
+                let percent = match &self.buffer_state {
+                    BufferState::Active(buf) => buf.parse::<u8>().ok().filter(|&n| n <= 100),
+                    _ => None,
+                };
+                self.reset_buffer();
+                match percent {
+                    Some(n) => Control::ScrollToPercent(n),
+                    None => Control::BufferReset,
+                }
+            }
             KeyCode::Up => {
                 let mode = match self.mode {
                     InputMode::Filter => InputMode::Find,
@@ -192,16 +654,57 @@ impl InputHandler {
                     Control::Nothing
                 }
             }
+            // Unlike other inputs, an empty value is a value too
+            KeyCode::Enter if self.mode == InputMode::EditCell => {
+                let value = cur_buffer.to_owned();
+                self.buffer_history.set(self.mode, cur_buffer);
+                if self.edit_confirmed {
+                    self.reset_buffer();
+                    Control::SetCell(value)
+                } else {
+                    self.pending_edit = Some(value);
+                    self.init_buffer(InputMode::ConfirmEdit);
+                    Control::empty_buffer()
+                }
+            }
+            KeyCode::Enter if self.mode == InputMode::Palette => {
+                let action = if cur_buffer.is_empty() {
+                    None
+                } else {
+                    matching_actions(cur_buffer).into_iter().next()
+                };
+                self.buffer_history.set(self.mode, cur_buffer);
+                self.reset_buffer();
+                match action.map(|action| &action.kind) {
+                    Some(ActionKind::Control(control)) => match control() {
+                        Control::Quit => self.quit_control(),
+                        control => control,
+                    },
+                    Some(ActionKind::Prompt(mode)) => {
+                        self.init_buffer(*mode);
+                        Control::empty_buffer()
+                    }
+                    None => Control::BufferReset,
+                }
+            }
             KeyCode::Enter => {
                 let control;
                 if cur_buffer.is_empty() {
-                    control = Control::BufferReset;
+                    control = self.clear_control();
                 } else if self.mode == InputMode::Find {
                     control = Control::Find(cur_buffer.to_string());
                 } else if self.mode == InputMode::Filter {
                     control = Control::Filter(cur_buffer.to_string());
                 } else if self.mode == InputMode::FilterColumns {
                     control = Control::FilterColumns(cur_buffer.to_string());
+                } else if self.mode == InputMode::Count {
+                    control = Control::Count(cur_buffer.to_string());
+                } else if self.mode == InputMode::ReplacePreview {
+                    control = Control::ReplacePreview(cur_buffer.to_string());
+                } else if self.mode == InputMode::ColumnStarts {
+                    control = Control::SetColumnStarts(cur_buffer.to_string());
+                } else if self.mode == InputMode::ExportMarked {
+                    control = Control::ExportMarked(cur_buffer.to_string());
                 } else {
                     control = Control::BufferReset;
                 }
@@ -214,7 +717,9 @@ impl InputHandler {
                 self.reset_buffer();
                 control
             }
-            KeyCode::Char('/') => {
+            // Switches from finding to filtering, and is typed as is in the
+            // other inputs, e.g. in paths
+            KeyCode::Char('/') if matches!(self.mode, InputMode::Find | InputMode::Filter) => {
                 if cur_buffer.is_empty() && self.mode == InputMode::Find {
                     self.mode = InputMode::Filter;
                 }
@@ -232,6 +737,48 @@ impl InputHandler {
         }
     }
 
+    /// Either y or the quit key again quits, anything else cancels
+    fn handler_confirm_quit(&mut self, key_event: KeyEvent) -> Control {
+        self.reset_buffer();
+        match key_event.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => Control::Quit,
+            _ if self.is_quit_key(&key_event) => Control::Quit,
+            _ => Control::BufferReset,
+        }
+    }
+
+    /// The first edit is only written to the file after answering y
+    fn handler_confirm_edit(&mut self, key_event: KeyEvent) -> Control {
+        self.reset_buffer();
+        let value = self.pending_edit.take();
+        match (key_event.code, value) {
+            (KeyCode::Char('y') | KeyCode::Char('Y'), Some(value)) => {
+                self.edit_confirmed = true;
+                Control::SetCell(value)
+            }
+            _ => Control::BufferReset,
+        }
+    }
+
+    /// Start editing the value of a cell
+    pub fn edit(&mut self, value: &str) {
+        self.buffer_state = BufferState::Active(value.to_owned());
+        self.mode = InputMode::EditCell;
+    }
+
+    fn is_quit_key(&self, key_event: &KeyEvent) -> bool {
+        self.quit_keys.iter().any(|key| key.matches(key_event))
+    }
+
+    fn quit_control(&mut self) -> Control {
+        if self.confirm_quit {
+            self.init_buffer(InputMode::ConfirmQuit);
+            Control::empty_buffer()
+        } else {
+            Control::Quit
+        }
+    }
+
     fn is_input_buffering(&self) -> bool {
         matches!(self.buffer_state, BufferState::Active(_))
     }
@@ -241,6 +788,18 @@ impl InputHandler {
         self.mode = mode;
     }
 
+    /// Control for cancelling or submitting an empty buffer, which clears only
+    /// the filter being edited so that the other filter is kept
+    fn clear_control(&self) -> Control {
+        match self.mode {
+            InputMode::Find | InputMode::Filter => Control::ClearFilter,
+            InputMode::FilterColumns => Control::ClearColumnsFilter,
+            InputMode::Count => Control::ClearCount,
+            InputMode::ReplacePreview => Control::ClearReplacePreview,
+            _ => Control::BufferReset,
+        }
+    }
+
     fn reset_buffer(&mut self) {
         self.buffer_state = BufferState::Inactive;
         self.mode = InputMode::Default;
@@ -250,3 +809,130 @@ impl InputHandler {
         self.mode
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matching_actions() {
+        let names = |query| -> Vec<&str> {
+            matching_actions(query)
+                .iter()
+                .map(|action| action.name)
+                .collect()
+        };
+        assert_eq!(
+            names("copy"),
+            vec![
+                "Copy selected rows",
+                "Copy column name",
+                "Copy cell with column name",
+                "Copy command line"
+            ]
+        );
+        assert_eq!(names("CLR MARKS"), vec!["Clear marks"]);
+        assert!(names("xyz").is_empty());
+        assert_eq!(names("filter")[..2], ["Filter rows", "Filter columns"]);
+        assert_eq!(names("").len(), ACTIONS.len());
+    }
+
+    #[test]
+    fn test_key() {
+        let event = |code, modifiers| KeyEvent { code, modifiers };
+        let key: Key = "q".parse().unwrap();
+        assert!(key.matches(&event(KeyCode::Char('q'), KeyModifiers::NONE)));
+        assert!(!key.matches(&event(KeyCode::Char('q'), KeyModifiers::CONTROL)));
+
+        let key: Key = "Q".parse().unwrap();
+        assert!(key.matches(&event(KeyCode::Char('Q'), KeyModifiers::SHIFT)));
+
+        let key: Key = "Ctrl-C".parse().unwrap();
+        assert!(key.matches(&event(KeyCode::Char('c'), KeyModifiers::CONTROL)));
+        assert!(!key.matches(&event(KeyCode::Char('c'), KeyModifiers::NONE)));
+
+        let key: Key = "esc".parse().unwrap();
+        assert!(key.matches(&event(KeyCode::Esc, KeyModifiers::NONE)));
+
+        assert!("".parse::<Key>().is_err());
+        assert!("Ctrl-".parse::<Key>().is_err());
+        assert!("qq".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn test_action_keys() {
+        // The keys shown in the command palette do the same as the action
+        for action in ACTIONS.iter().filter(|action| !action.key.is_empty()) {
+            let key_events: Vec<KeyEvent> = match action.key {
+                // A count typed before the keys
+                "<n>" => continue,
+                "Shift-Tab" => vec![KeyEvent {
+                    code: KeyCode::BackTab,
+                    modifiers: KeyModifiers::SHIFT,
+                }],
+                label => match label.parse::<Key>() {
+                    Ok(key) => vec![KeyEvent {
+                        code: key.code,
+                        modifiers: if key.ctrl {
+                            KeyModifiers::CONTROL
+                        } else {
+                            KeyModifiers::NONE
+                        },
+                    }],
+                    // Keys pressed one after the other
+                    Err(_) => label
+                        .chars()
+                        .map(|c| KeyEvent {
+                            code: KeyCode::Char(c),
+                            modifiers: KeyModifiers::NONE,
+                        })
+                        .collect(),
+                },
+            };
+            let mut input_handler = InputHandler::new();
+            let control = key_events
+                .into_iter()
+                .map(|key_event| input_handler.handler_default(key_event))
+                .last()
+                .unwrap();
+            match &action.kind {
+                ActionKind::Control(f) => assert_eq!(control, f(), "{}", action.name),
+                ActionKind::Prompt(mode) => {
+                    assert!(input_handler.mode() == *mode, "{}", action.name)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_slash() {
+        let mut input_handler = InputHandler::new();
+        let type_keys = |input_handler: &mut InputHandler, keys: &str| {
+            keys.chars()
+                .map(|c| {
+                    let key_event = KeyEvent {
+                        code: KeyCode::Char(c),
+                        modifiers: KeyModifiers::NONE,
+                    };
+                    if input_handler.is_input_buffering() {
+                        input_handler.handler_buffering(key_event)
+                    } else {
+                        input_handler.handler_default(key_event)
+                    }
+                })
+                .last()
+        };
+
+        // Typed as part of the path to export to
+        type_keys(&mut input_handler, "E");
+        assert_eq!(
+            type_keys(&mut input_handler, "out/marked.csv"),
+            Some(Control::BufferContent("out/marked.csv".to_owned()))
+        );
+        input_handler.reset_buffer();
+
+        // Switches from finding to filtering instead
+        type_keys(&mut input_handler, "//");
+        assert!(input_handler.mode() == InputMode::Filter);
+    }
+}
@@ -0,0 +1,73 @@
+use crossterm::execute;
+use crossterm::style::Print;
+use crossterm::terminal::{Clear, ClearType};
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const FRAME_INTERVAL: Duration = Duration::from_millis(80);
+
+// Files that open quickly shouldn't flash a message
+const DELAY: Duration = Duration::from_millis(200);
+
+/// Message with a spinner shown on the current line while files are being
+/// opened, e.g. when reading a large input from stdin. The line is cleared
+/// when dropped.
+pub struct Spinner {
+    message: Arc<Mutex<String>>,
+    // Dropped to stop the spinner
+    stop: Option<mpsc::Sender<()>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Spinner {
+    pub fn start(message: &str) -> Spinner {
+        let message = Arc::new(Mutex::new(message.to_owned()));
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let m = message.clone();
+        let handle = thread::spawn(move || {
+            if stopped.recv_timeout(DELAY) != Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+            let mut stdout = io::stdout();
+            for frame in FRAMES.iter().cycle() {
+                let line = format!("{} {}", frame, m.lock().unwrap());
+                // Nothing to do if the terminal can't be written to
+                execute!(
+                    stdout,
+                    Print('\r'),
+                    Print(line),
+                    Clear(ClearType::UntilNewLine)
+                )
+                .ok();
+                if stopped.recv_timeout(FRAME_INTERVAL) != Err(RecvTimeoutError::Timeout) {
+                    break;
+                }
+            }
+            execute!(stdout, Print('\r'), Clear(ClearType::CurrentLine)).ok();
+        });
+
+        Spinner {
+            message,
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+
+    pub fn set_message(&self, message: &str) {
+        *self.message.lock().unwrap() = message.to_owned();
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
@@ -1,21 +1,39 @@
 mod app;
+mod cache;
+mod clipboard;
+mod column_groups;
 mod csv;
+mod display_options;
+mod edit;
+mod export;
+mod expr;
 mod find;
+mod headless;
 mod input;
+mod loading;
+mod pipe;
+mod sort;
+mod stats;
 mod ui;
 #[allow(dead_code)]
 mod util;
 mod view;
 use crate::app::App;
+use crate::cache::{DelimiterCache, Position, PositionCache};
+use crate::display_options::DisplayOptions;
+use crate::loading::Spinner;
+use crate::ui::{FileStats, HeaderMode, TruncateSide};
 
 extern crate csv as sushi_csv;
 
 use anyhow::{bail, Context, Result};
 use clap::Parser;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
 use crossterm::execute;
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
+use crossterm::tty::IsTty;
 use std::convert::TryInto;
 use std::fs::File;
 use std::io::{self, Read, Seek, SeekFrom, Write};
@@ -26,19 +44,25 @@ use tui::Terminal;
 struct SeekableFile {
     filename: Option<String>,
     inner_file: Option<NamedTempFile>,
+    // Fixed-width text converted to inner_file, if it had to be copied too
+    source_file: Option<NamedTempFile>,
 }
 
 impl SeekableFile {
-    fn new(maybe_filename: &Option<String>) -> Result<SeekableFile> {
+    fn new(maybe_filename: &Option<String>, whitespace: bool) -> Result<SeekableFile> {
         let mut inner_file = NamedTempFile::new()?;
         let inner_file_res;
 
         if let Some(filename) = maybe_filename {
             let err = format!("Failed to open file: {}", filename);
             let mut f = File::open(filename).context(err)?;
+            if whitespace {
+                // Always need a converted copy of the data
+                csv::convert_whitespace_delimited(f, &mut inner_file)?;
+                inner_file_res = Some(inner_file);
             // If not seekable, it most likely is due to process substitution using
             // pipe - write out to a temp file to make it seekable
-            if f.seek(SeekFrom::Start(0)).is_err() {
+            } else if f.seek(SeekFrom::Start(0)).is_err() {
                 let mut buffer: Vec<u8> = vec![];
                 // TODO: could have read by chunks, yolo for now
                 f.read_to_end(&mut buffer)?;
@@ -50,18 +74,67 @@ impl SeekableFile {
         } else {
             // Handle input from stdin
             let mut stdin = std::io::stdin();
-            let mut buffer: Vec<u8> = vec![];
-            stdin.read_to_end(&mut buffer)?;
-            inner_file.write_all(&buffer)?;
+            if whitespace {
+                csv::convert_whitespace_delimited(stdin, &mut inner_file)?;
+            } else {
+                let mut buffer: Vec<u8> = vec![];
+                stdin.read_to_end(&mut buffer)?;
+                inner_file.write_all(&buffer)?;
+            }
             inner_file_res = Some(inner_file);
         }
 
         Ok(SeekableFile {
             filename: maybe_filename.clone(),
             inner_file: inner_file_res,
+            source_file: None,
         })
     }
 
+    /// Drop the first `n` lines, e.g. preamble before the header
+    fn skip_lines(&mut self, n: usize) -> Result<()> {
+        let mut inner_file = NamedTempFile::new()?;
+        let f = File::open(self.filename())?;
+        csv::skip_lines(f, &mut inner_file, n)?;
+        self.inner_file = Some(inner_file);
+        Ok(())
+    }
+
+    /// Convert fixed-width text to CSV, keeping the text to convert it again
+    /// with other columns
+    fn convert_fixed_width(
+        &mut self,
+        starts: Option<&[usize]>,
+        skip_rows: usize,
+    ) -> Result<csv::FixedWidth> {
+        let source = self.filename().to_owned();
+        let mut inner_file = NamedTempFile::new()?;
+        let f = File::open(&source)?;
+        let starts = csv::convert_fixed_width(f, &mut inner_file, starts, skip_rows)?;
+        self.source_file = self.inner_file.replace(inner_file);
+        Ok(csv::FixedWidth {
+            source,
+            starts,
+            skip_rows,
+        })
+    }
+
+    /// Keep only the first or last records after the header
+    fn slice_records(&mut self, config: &csv::CsvConfig, slice: csv::RecordSlice) -> Result<()> {
+        let mut inner_file = NamedTempFile::new()?;
+        csv::slice_records(config, &mut inner_file, slice)?;
+        self.inner_file = Some(inner_file);
+        Ok(())
+    }
+
+    /// Drop the first `n` records after the header
+    fn skip_records(&mut self, config: &csv::CsvConfig, n: usize) -> Result<()> {
+        let mut inner_file = NamedTempFile::new()?;
+        csv::skip_records(config, &mut inner_file, n)?;
+        self.inner_file = Some(inner_file);
+        Ok(())
+    }
+
     fn filename(&self) -> &str {
         if let Some(f) = &self.inner_file {
             f.path().to_str().unwrap()
@@ -70,24 +143,301 @@ impl SeekableFile {
             self.filename.as_ref().unwrap()
         }
     }
+
+    fn stats(&self) -> FileStats {
+        // Size of the data on disk, which is the temp file if one had to be made
+        let size = std::fs::metadata(self.filename())
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let temp_size = self.inner_file.as_ref().map(|_| size);
+        let line_ending = csv::detect_line_ending(self.filename()).ok().flatten();
+        FileStats {
+            size,
+            temp_size,
+            line_ending,
+        }
+    }
 }
 
+// Fixed-width text is converted to CSV that is read as written
+const FIXED_WIDTH_CONFLICTS: &[&str] = &["delimiter", "whitespace", "raw", "quote", "escape"];
+const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\ncsv: ",
+    env!("CSVLENS_CSV_VERSION"),
+    "\nfeatures: ",
+    env!("CSVLENS_FEATURES"),
+    "\ntarget: ",
+    env!("CSVLENS_TARGET"),
+);
+
 #[derive(Parser, Debug)]
+#[clap(version, long_version = LONG_VERSION)]
 struct Args {
-    /// CSV filename
-    filename: Option<String>,
+    /// CSV filenames, each opened in a tab. Reads from stdin if not given.
+    filenames: Vec<String>,
 
-    /// Delimiter character (comma by default)
+    /// Delimiter character (comma by default), or one of the names tab, space,
+    /// pipe, semicolon and comma. The delimiter is remembered for the file and
+    /// used by default the next time it is opened.
     #[clap(short, long)]
     delimiter: Option<String>,
 
-    /// Show stats for debugging
+    /// Do not remember or reuse the delimiter last used for the file
+    #[clap(long)]
+    no_cache: bool,
+
+    /// Treat quotes as literal characters and split fields purely on the
+    /// delimiter, useful for finding where a stray quote breaks parsing
+    #[clap(long)]
+    raw: bool,
+
+    /// Quote character (double quote by default)
+    #[clap(long)]
+    quote: Option<String>,
+
+    /// Escape character for quotes inside quoted fields, e.g. \ for
+    /// backslash escaping. By default quotes are escaped by doubling them.
+    /// Has no effect with --raw since quotes are not handled at all then.
+    #[clap(long)]
+    escape: Option<String>,
+
+    /// Line number of the header, for skipping title or other lines before
+    /// it, or auto to guess it from the number of fields in each line
+    #[clap(long, value_name = "N|auto")]
+    header_line: Option<csv::HeaderLine>,
+
+    /// Number of rows after the header to skip, e.g. for units or notes
+    /// below it. Rows are numbered from the first row shown.
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    skip_rows: usize,
+
+    /// Only read the first N rows, e.g. for a quick look at a large file.
+    /// Applied after --skip-rows. Fixed-width text isn't sliced, since it is
+    /// converted again from the whole file when its columns change.
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with_all = &["tail", "fixed-width", "column-starts"]
+    )]
+    head: Option<usize>,
+
+    /// Only show the last N rows. All rows are still read to find them, but
+    /// not kept.
+    #[clap(long, value_name = "N", conflicts_with_all = &["fixed-width", "column-starts"])]
+    tail: Option<usize>,
+
+    /// Treat runs of spaces or tabs as the delimiter, e.g. for output of ps or df
+    #[clap(long, conflicts_with = "delimiter")]
+    whitespace: bool,
+
+    /// Read text with columns at fixed character positions instead of
+    /// delimited values, guessing the columns from how the first lines line
+    /// up
+    #[clap(long, conflicts_with_all = FIXED_WIDTH_CONFLICTS)]
+    fixed_width: bool,
+
+    /// Character positions where the columns of fixed-width text start,
+    /// e.g. 0,8,20, instead of guessing them. Implies --fixed-width.
+    #[clap(long, value_name = "POSITIONS", conflicts_with_all = FIXED_WIDTH_CONFLICTS)]
+    column_starts: Option<String>,
+
+    /// Number of rows to keep visible above and below the selected row when
+    /// scrolling or going to a row, also allowing to scroll past the last row
+    /// by as many rows
+    #[clap(long, default_value_t = 0)]
+    scrolloff: u64,
+
+    /// Number of rows from the previous page to keep in view when scrolling
+    /// by a page
+    #[clap(long, default_value_t = 0)]
+    page_overlap: u64,
+
+    /// Minimum width of each column, so that narrow columns are padded
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    min_column_width: u16,
+
+    /// Show a placeholder for empty cells (∅) and whitespace-only cells (·)
+    #[clap(long)]
+    show_empty: bool,
+
+    /// Mark leading and trailing whitespace in cells (·), which can also be
+    /// toggled with W
+    #[clap(long)]
+    show_whitespace: bool,
+
+    /// Leave less space between columns to fit more of them, which can also
+    /// be toggled with c
+    #[clap(long)]
+    compact: bool,
+
+    /// Draw lines between columns, which can also be toggled with B
+    #[clap(long)]
+    grid: bool,
+
+    /// Draw borders and markers with ASCII characters only, for terminals
+    /// that show box drawing characters as garbage
+    #[clap(long)]
+    ascii: bool,
+
+    /// Show tabs in values as N spaces instead of as a tab symbol, e.g. to
+    /// keep text indented with tabs aligned
+    #[clap(long, value_name = "N")]
+    tab_width: Option<u16>,
+
+    /// Shell command to pipe the current cell to with |, e.g. "jq .", or the
+    /// rows selected with J and K along with the header
+    #[clap(long, value_name = "COMMAND")]
+    exec: Option<String>,
+
+    /// Truncate long values on the left instead of the right, e.g. for file
+    /// paths or URLs
+    #[clap(long)]
+    truncate_left: bool,
+
+    /// How to show headers that are longer than their column, one of truncate
+    /// (the default), wrap (across two lines) and abbreviate (by dropping
+    /// vowels and then characters in the middle)
+    #[clap(long, default_value = "truncate")]
+    long_headers: HeaderMode,
+
+    /// Format of exported rows, one of csv, tsv, json and markdown. By default
+    /// the format is chosen by the extension of the exported file.
+    #[clap(long)]
+    format: Option<export::Format>,
+
+    /// Write numeric-looking values as numbers instead of strings when
+    /// exporting to JSON
+    #[clap(long)]
+    json_numbers: bool,
+
+    /// Values that stand for a missing value, separated by commas (e.g.
+    /// "NA,NULL,\N"). They are dimmed, counted as empty and match the
+    /// filters for blank cells.
+    #[clap(long, value_name = "TOKENS", use_value_delimiter = true)]
+    null_tokens: Vec<String>,
+
+    /// Write the values given by --null-tokens as empty when exporting
+    #[clap(long)]
+    export_nulls_as_empty: bool,
+
+    /// How V copies the current cell along with its column name, one of kv
+    /// (name=value, the default), json and sql (name = 'value')
+    #[clap(long, default_value = "kv")]
+    cell_format: export::CellFormat,
+
+    /// Estimate the number of rows from only the first N bytes (e.g. 10M)
+    /// until the file is fully parsed, which is faster for huge files but
+    /// rougher. All lines are counted by default.
+    #[clap(long, value_name = "BYTES")]
+    approx_sample: Option<String>,
+
+    /// Number of rows to search before showing the rows found among them.
+    /// More is faster to search through huge files, fewer shows the first
+    /// rows found sooner. 1000 by default.
+    #[clap(long, value_name = "ROWS")]
+    find_batch_rows: Option<usize>,
+
+    /// Only show rows matching the regex, or the expression if prefixed
+    /// with : (e.g. ":amount > 1000")
+    #[clap(long)]
+    filter: Option<String>,
+
+    /// Open at the first row matching the regex, or the expression if
+    /// prefixed with :, without filtering the others
+    #[clap(long, value_name = "REGEX")]
+    find: Option<String>,
+
+    /// Only show columns with names matching the regex, or hide them instead
+    /// if prefixed with !
+    #[clap(long, value_name = "REGEX")]
+    columns: Option<String>,
+
+    /// Row to scroll to, counting only the rows matching --filter if given
+    #[clap(long, value_name = "ROW")]
+    goto: Option<usize>,
+
+    /// Number of the first row, e.g. 0 to count rows from 0
+    #[clap(long, value_name = "N", default_value = "1")]
+    row_base: u64,
+
+    /// Count the header as the first row, so that rows are numbered as the
+    /// lines of the file
+    #[clap(long)]
+    count_header: bool,
+
+    /// Print the rows instead of showing them interactively, which is the
+    /// default when the output is not a terminal
+    #[clap(long)]
+    headless: bool,
+
+    /// Write the rows to a file instead of showing them interactively, in the
+    /// format given by --format or the file extension
+    #[clap(long, short, value_name = "FILE")]
+    output: Option<String>,
+
+    /// Write stats of each file as JSON instead of showing it interactively,
+    /// e.g. the number of rows and the type of each column. Writes to stdout
+    /// with -.
+    #[clap(long, value_name = "FILE|-")]
+    stats_json: Option<String>,
+
+    /// Do not scroll to where the file was left off the last time it was
+    /// viewed
+    #[clap(long)]
+    no_restore: bool,
+
+    /// Allow editing the current cell with e, writing the changes back to
+    /// the file
+    #[clap(long)]
+    edit: bool,
+
+    /// Key to quit with instead of q, e.g. Esc or Ctrl-c. Can be given
+    /// multiple times.
+    #[clap(long = "quit-key", value_name = "KEY", multiple_occurrences = true)]
+    quit_keys: Vec<input::Key>,
+
+    /// Ask before quitting, e.g. so as not to lose filters by accident
+    #[clap(long)]
+    confirm_quit: bool,
+
+    /// Scroll to the first column when scrolling right past the last one,
+    /// and to the last column when scrolling left past the first one
+    #[clap(long)]
+    wrap_columns: bool,
+
+    /// Start without a selected row, so that scrolling only moves the rows
+    /// without highlighting one
+    #[clap(long)]
+    no_select: bool,
+
+    /// Capture the mouse, e.g. to sort by clicking a header, instead of
+    /// selecting text with it as usual
+    #[clap(long)]
+    mouse: bool,
+
+    /// Show stats for debugging, including how long reading rows, picking
+    /// the shown columns, rendering and finding took
     #[clap(long)]
     debug: bool,
 }
 
+const DELIMITER_ALIASES: [(&str, u8); 5] = [
+    ("tab", b'\t'),
+    ("space", b' '),
+    ("pipe", b'|'),
+    ("semicolon", b';'),
+    ("comma", b','),
+];
+
 fn parse_delimiter(args: &Args) -> Result<Option<u8>> {
     if let Some(s) = &args.delimiter {
+        if let Some((_, d)) = DELIMITER_ALIASES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        {
+            return Ok(Some(*d));
+        }
         let mut chars = s.chars();
         let c = chars.next().context("Delimiter should not be empty")?;
         if !c.is_ascii() {
@@ -97,7 +447,12 @@ fn parse_delimiter(args: &Args) -> Result<Option<u8>> {
             );
         }
         if chars.next().is_some() {
-            bail!("Delimiter should be exactly one character, got {}", s);
+            let names: Vec<&str> = DELIMITER_ALIASES.iter().map(|(name, _)| *name).collect();
+            bail!(
+                "Delimiter should be exactly one character or one of {}, got {}",
+                names.join(", "),
+                s
+            );
         }
         Ok(Some(c.try_into()?))
     } else {
@@ -105,36 +460,372 @@ fn parse_delimiter(args: &Args) -> Result<Option<u8>> {
     }
 }
 
+fn parse_ascii_char(s: &str, name: &str) -> Result<u8> {
+    let mut chars = s.chars();
+    let c = chars
+        .next()
+        .context(format!("{} should not be empty", name))?;
+    if !c.is_ascii() {
+        bail!(
+            "{} should be within the ASCII range: {} is too fancy",
+            name,
+            c
+        );
+    }
+    if chars.next().is_some() {
+        bail!("{} should be exactly one character, got {}", name, s);
+    }
+    Ok(c.try_into()?)
+}
+
+/// Parse a number of bytes with an optional K, M or G suffix (powers of 1024)
+fn parse_size(s: &str) -> Result<u64> {
+    let (digits, multiplier) = match s.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&s[..s.len() - 1], 1 << 10),
+        Some('M') => (&s[..s.len() - 1], 1 << 20),
+        Some('G') => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    match digits.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(n.saturating_mul(multiplier)),
+        _ => bail!(
+            "Size should be a positive number of bytes like 500K or 10M, got {}",
+            s
+        ),
+    }
+}
+
 fn run_csvlens() -> Result<()> {
     let args = Args::parse();
 
     let show_stats = args.debug;
     let delimiter = parse_delimiter(&args)?;
 
-    let file = SeekableFile::new(&args.filename)?;
-    let filename = file.filename();
+    let quote = match &args.quote {
+        Some(s) => Some(parse_ascii_char(s, "Quote")?),
+        None => None,
+    };
+    let escape = match &args.escape {
+        Some(s) => Some(parse_ascii_char(s, "Escape")?),
+        None => None,
+    };
+    if args.find_batch_rows == Some(0) {
+        bail!("--find-batch-rows should be at least 1");
+    }
+    let approx_sample_bytes = match &args.approx_sample {
+        Some(s) => Some(parse_size(s)?),
+        None => None,
+    };
+
+    let column_starts = match &args.column_starts {
+        Some(s) => Some(csv::parse_column_starts(s)?),
+        None => None,
+    };
+    let fixed_width = args.fixed_width || column_starts.is_some();
+
+    // Converted whitespace delimited data always uses the default delimiter
+    let mut cache = match DelimiterCache::default_path() {
+        Some(path) if !args.no_cache && !args.whitespace && !fixed_width => {
+            Some(DelimiterCache::load(&path))
+        }
+        _ => None,
+    };
+
+    // Read from stdin if no files are given
+    let filenames: Vec<Option<String>> = if args.filenames.is_empty() {
+        vec![None]
+    } else {
+        args.filenames.iter().cloned().map(Some).collect()
+    };
+
+    let headless = args.headless
+        || args.output.is_some()
+        || args.stats_json.is_some()
+        || !io::stdout().is_tty();
+    let spinner = if headless {
+        None
+    } else {
+        Some(Spinner::start("Loading..."))
+    };
+
+    // Files must be kept around while the app is running
+    let mut files = vec![];
+    let mut configs = vec![];
+    for original_filename in filenames {
+        if let Some(spinner) = &spinner {
+            let name = original_filename.as_deref().unwrap_or("stdin");
+            spinner.set_message(&format!("Loading {}...", name));
+        }
+        let mut file = SeekableFile::new(&original_filename, args.whitespace)?;
+        let mut config = csv::CsvConfig::new(file.filename());
+        if let (Some(cache), Some(f)) = (&mut cache, &original_filename) {
+            if let Some(d) = delimiter {
+                cache.set(f, d);
+            } else if let Some(d) = cache.get(f) {
+                config.delimiter = d;
+            }
+        }
+        if let Some(d) = delimiter {
+            config.delimiter = d;
+        }
+        config.quoting = !args.raw;
+        if let Some(q) = quote {
+            config.quote = q;
+        }
+        config.escape = escape;
+        config.approx_sample_bytes = approx_sample_bytes;
+        config.null_tokens = args.null_tokens.clone();
+        if let Some(n) = args.find_batch_rows {
+            config.find_batch_rows = n;
+        }
+        let header_line = match args.header_line {
+            Some(csv::HeaderLine::Auto) => csv::detect_header_line(&config)?,
+            Some(csv::HeaderLine::Line(n)) => n,
+            None => 1,
+        };
+        if header_line > 1 {
+            file.skip_lines(header_line - 1)?;
+            config.set_path(file.filename());
+        }
+        if fixed_width {
+            // Rows are lines when fixed-width, so they are skipped as such
+            let converted = file.convert_fixed_width(column_starts.as_deref(), args.skip_rows)?;
+            config.set_path(file.filename());
+            config.fixed_width = Some(converted);
+        } else if args.skip_rows > 0 {
+            file.skip_records(&config, args.skip_rows)?;
+            config.set_path(file.filename());
+        }
+        let slice = match (args.head, args.tail) {
+            (Some(n), _) => Some(csv::RecordSlice::Head(n)),
+            (_, Some(n)) => Some(csv::RecordSlice::Tail(n)),
+            _ => None,
+        };
+        if let Some(slice) = slice {
+            file.slice_records(&config, slice)?;
+            config.set_path(file.filename());
+        }
+        files.push(file);
+        configs.push((config, original_filename));
+    }
+    if let (Some(cache), Some(_)) = (&cache, delimiter) {
+        // Failing to remember the delimiter is not worth failing for
+        cache.save().ok();
+    }
+
+    let export_options = export::ExportOptions {
+        format: args.format,
+        json_numbers: args.json_numbers,
+        null_tokens: if args.export_nulls_as_empty {
+            args.null_tokens.clone()
+        } else {
+            vec![]
+        },
+    };
+
+    if let Some(path) = &args.stats_json {
+        let stats = configs
+            .iter()
+            .map(|(config, original_filename)| stats::scan(config, original_filename.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        if path == "-" {
+            stats::write_json(io::stdout(), &stats)?;
+        } else {
+            let file = File::create(path).context(format!("Failed to create file: {}", path))?;
+            let mut out = io::BufWriter::new(file);
+            stats::write_json(&mut out, &stats)?;
+            out.flush()?;
+        }
+        return Ok(());
+    }
 
+    if headless {
+        let (mut out, format): (Box<dyn Write>, export::Format) = match &args.output {
+            Some(path) => {
+                let file =
+                    File::create(path).context(format!("Failed to create file: {}", path))?;
+                let format = args
+                    .format
+                    .unwrap_or_else(|| export::Format::from_path(path));
+                (Box::new(io::BufWriter::new(file)), format)
+            }
+            None => (
+                Box::new(io::stdout()),
+                args.format.unwrap_or(export::Format::Csv),
+            ),
+        };
+        // Files are written one after another
+        for (config, _) in &configs {
+            headless::print(
+                &mut out,
+                config,
+                args.filter.as_deref(),
+                args.columns.as_deref(),
+                format,
+                &export_options,
+            )?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    let mut configs = configs.into_iter();
+    let (config, original_filename) = configs.next().unwrap();
     let mut app =
-        App::new(filename, delimiter, args.filename, show_stats).context("Failed creating app")?;
+        App::with_config(config, original_filename, show_stats).context("Failed creating app")?;
+    for (config, original_filename) in configs {
+        app.add_tab(config, original_filename)
+            .context("Failed creating app")?;
+    }
+    app.set_file_stats(files.iter().map(|f| f.stats()).collect());
+    if args.no_select {
+        app.disable_selection();
+    }
+    app.set_wrap_columns(args.wrap_columns);
+    app.set_scrolloff(args.scrolloff);
+    app.set_page_overlap(args.page_overlap);
+    app.set_min_column_width(args.min_column_width);
+    app.set_show_empty_cells(args.show_empty);
+    // Options are as toggled the last time, except for those turned on by flags
+    let display_path = display_options::default_path();
+    let loaded_display_options = display_path.as_deref().map(DisplayOptions::load);
+    if let Some(options) = loaded_display_options {
+        app.set_display_options(options);
+    }
+    if args.show_whitespace {
+        app.set_show_whitespace(true);
+    }
+    if args.compact {
+        app.set_compact(true);
+    }
+    if args.grid {
+        app.set_grid(true);
+    }
+    let start_display_options = app.display_options();
+    app.set_ascii(args.ascii);
+    app.set_tab_width(args.tab_width);
+    app.set_source_args(source_args(&args));
+    if let Some(command) = &args.exec {
+        app.set_pipe(pipe::Pipe::new(command, args.mouse));
+    }
+    if let Some(path) = column_groups::default_path() {
+        app.set_column_groups(column_groups::load(&path)?);
+    }
+    if args.truncate_left {
+        app.set_truncate_side(TruncateSide::Left);
+    }
+    app.set_header_mode(args.long_headers);
+    app.set_export_options(export_options);
+    app.set_cell_format(args.cell_format);
+    if !args.quit_keys.is_empty() {
+        app.set_quit_keys(args.quit_keys.clone());
+    }
+    app.set_confirm_quit(args.confirm_quit);
+    app.set_editable(args.edit);
+    app.set_row_numbering(args.row_base, args.count_header);
+    if let Some(columns) = &args.columns {
+        app.set_columns_filter(columns)?;
+    }
+    if let Some(filter) = &args.filter {
+        app.set_filter(filter)?;
+    }
+    if let Some(pattern) = &args.find {
+        app.set_find(pattern)?;
+    }
+    if let Some(n) = args.goto {
+        app.set_goto(n);
+    }
+
+    // Going elsewhere takes precedence over where the file was left off
+    let mut positions = PositionCache::default_path().map(|path| PositionCache::load(&path));
+    let restore =
+        !args.no_restore && args.goto.is_none() && args.filter.is_none() && args.find.is_none();
+    if let (Some(positions), true) = (&positions, restore) {
+        app.restore_positions(|filename| {
+            positions
+                .get(filename)
+                .map(|position| (position.rows_from, position.selected))
+        });
+    }
+
+    drop(spinner);
 
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
+    if args.mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     let result = app.main_loop(&mut terminal);
 
+    if let Some(positions) = &mut positions {
+        for (filename, (rows_from, selected)) in app.positions() {
+            positions.set(
+                &filename,
+                Position {
+                    rows_from,
+                    selected,
+                },
+            );
+        }
+        // Failing to remember the position is not worth failing for
+        positions.save().ok();
+    }
+    if let (Some(path), Some(loaded)) = (&display_path, loaded_display_options) {
+        let options = loaded.toggled(&start_display_options, &app.display_options());
+        if options != loaded {
+            options.save(path).ok();
+        }
+    }
+
     // restore terminal
     disable_raw_mode()?;
+    if args.mouse {
+        execute!(io::stdout(), DisableMouseCapture)?;
+    }
     execute!(io::stdout(), LeaveAlternateScreen)?;
 
     result
 }
 
+/// Options for how files are read, as given
+fn source_args(args: &Args) -> Vec<String> {
+    let mut source_args = vec![];
+    if args.whitespace {
+        source_args.push("--whitespace".to_owned());
+    }
+    if let Some(header_line) = &args.header_line {
+        source_args.push("--header-line".to_owned());
+        source_args.push(match header_line {
+            csv::HeaderLine::Auto => "auto".to_owned(),
+            csv::HeaderLine::Line(n) => n.to_string(),
+        });
+    }
+    if args.skip_rows > 0 {
+        source_args.push("--skip-rows".to_owned());
+        source_args.push(args.skip_rows.to_string());
+    }
+    if let Some(n) = args.head {
+        source_args.push("--head".to_owned());
+        source_args.push(n.to_string());
+    }
+    if let Some(n) = args.tail {
+        source_args.push("--tail".to_owned());
+        source_args.push(n.to_string());
+    }
+    source_args
+}
+
 fn main() {
     if let Err(e) = run_csvlens() {
+        // E.g. piped to head, which has all it wanted
+        if headless::is_broken_pipe(&e) {
+            return;
+        }
         println!("{}", e);
         std::process::exit(1);
     }
@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use crossterm::event::{read, DisableMouseCapture, EnableMouseCapture, Event};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use std::io::{self, Write};
+use std::process::{Command, ExitStatus, Stdio};
+
+/// Shell command to pipe values to, e.g. `jq .` for a JSON value or `less`
+pub struct Pipe {
+    command: String,
+    mouse_capture: bool,
+}
+
+impl Pipe {
+    pub fn new(command: &str, mouse_capture: bool) -> Self {
+        Pipe {
+            command: command.to_owned(),
+            mouse_capture,
+        }
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Run the command with the input on stdin outside of the alternate
+    /// screen, waiting for a key before going back to it so that the output
+    /// can be read
+    pub fn run(&self, input: &str) -> Result<ExitStatus> {
+        let mut stdout = io::stdout();
+        disable_raw_mode()?;
+        if self.mouse_capture {
+            execute!(stdout, DisableMouseCapture)?;
+        }
+        execute!(stdout, LeaveAlternateScreen, crossterm::cursor::Show)?;
+
+        let result = self.spawn(input);
+
+        print!("\nPress any key to return to csvlens");
+        stdout.flush()?;
+        enable_raw_mode()?;
+        while !matches!(read()?, Event::Key(_)) {}
+
+        execute!(stdout, EnterAlternateScreen)?;
+        if self.mouse_capture {
+            execute!(stdout, EnableMouseCapture)?;
+        }
+        result
+    }
+
+    fn spawn(&self, input: &str) -> Result<ExitStatus> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::piped())
+            .spawn()
+            .context(format!("Failed to run: {}", self.command))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // Commands may exit without reading all of the input, e.g. head
+            stdin.write_all(input.as_bytes()).ok();
+        }
+        Ok(child.wait()?)
+    }
+}
@@ -0,0 +1,192 @@
+use crate::csv;
+use crate::stats;
+
+use std::cmp::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+/// Order of the rows sorted by a column, i.e. the row index at each position
+/// and the position of each row index
+#[derive(Debug)]
+pub struct SortedRows {
+    order: Vec<u64>,
+    positions: Vec<u64>,
+}
+
+impl SortedRows {
+    fn new(order: Vec<u64>) -> Self {
+        let mut positions = vec![0; order.len()];
+        for (position, &row_index) in order.iter().enumerate() {
+            positions[row_index as usize] = position as u64;
+        }
+        SortedRows { order, positions }
+    }
+
+    /// Row indices at the positions from the given one
+    pub fn row_indices(&self, position: u64, num_rows: u64) -> &[u64] {
+        let start = std::cmp::min(position as usize, self.order.len());
+        let end = std::cmp::min(start.saturating_add(num_rows as usize), self.order.len());
+        &self.order[start..end]
+    }
+
+    /// Position of the row index, or the row index itself if not known, e.g.
+    /// for rows added after sorting
+    pub fn position(&self, row_index: u64) -> u64 {
+        self.positions
+            .get(row_index as usize)
+            .copied()
+            .unwrap_or(row_index)
+    }
+}
+
+/// Sort rows by a column in the background
+pub struct Sorter {
+    column: usize,
+    order: SortOrder,
+    internal: Arc<Mutex<SorterInternalState>>,
+}
+
+impl Sorter {
+    pub fn new(config: Arc<csv::CsvConfig>, column: usize, order: SortOrder) -> Self {
+        let internal = SorterInternalState::init(config, column, order);
+        Sorter {
+            column,
+            order,
+            internal,
+        }
+    }
+
+    /// Index of the column among all columns
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn order(&self) -> SortOrder {
+        self.order
+    }
+
+    /// Sorted rows once done
+    pub fn sorted_rows(&self) -> Option<Arc<SortedRows>> {
+        self.internal.lock().unwrap().sorted_rows.clone()
+    }
+}
+
+impl Drop for Sorter {
+    fn drop(&mut self) {
+        self.internal.lock().unwrap().should_terminate = true;
+    }
+}
+
+struct SorterInternalState {
+    sorted_rows: Option<Arc<SortedRows>>,
+    should_terminate: bool,
+}
+
+impl SorterInternalState {
+    fn init(
+        config: Arc<csv::CsvConfig>,
+        column: usize,
+        order: SortOrder,
+    ) -> Arc<Mutex<SorterInternalState>> {
+        let internal = SorterInternalState {
+            sorted_rows: None,
+            should_terminate: false,
+        };
+
+        let m_state = Arc::new(Mutex::new(internal));
+
+        let state = m_state.clone();
+        let _handle = thread::spawn(move || {
+            let mut bg_reader = match config.new_reader() {
+                Ok(reader) => reader,
+                Err(_) => return,
+            };
+
+            // Numbers are parsed once instead of at each comparison
+            let mut keys = vec![];
+            for (i, r) in bg_reader.records().enumerate() {
+                let value = match &r {
+                    Ok(record) => record.get(column).unwrap_or(""),
+                    Err(_) => "",
+                };
+                keys.push((stats::parse_number(value), value.to_owned()));
+                if i % 1000 == 0 && state.lock().unwrap().should_terminate {
+                    return;
+                }
+            }
+
+            let mut indices: Vec<u64> = (0..keys.len() as u64).collect();
+            // Stable, so that rows with the same value stay in file order
+            indices.sort_by(|&a, &b| {
+                let ordering = compare(&keys[a as usize], &keys[b as usize]);
+                match order {
+                    SortOrder::Ascending => ordering,
+                    SortOrder::Descending => ordering.reverse(),
+                }
+            });
+
+            let mut m = state.lock().unwrap();
+            m.sorted_rows = Some(Arc::new(SortedRows::new(indices)));
+        });
+
+        m_state
+    }
+}
+
+/// Numbers are compared as numbers and come before other values, which are
+/// compared as text
+fn compare(a: &(Option<f64>, String), b: &(Option<f64>, String)) -> Ordering {
+    match (a.0, b.0) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.1.cmp(&b.1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn sorted(column: usize, order: SortOrder) -> Arc<SortedRows> {
+        let config = Arc::new(csv::CsvConfig::new("tests/data/cities.csv"));
+        let sorter = Sorter::new(config, column, order);
+        loop {
+            if let Some(sorted_rows) = sorter.sorted_rows() {
+                return sorted_rows;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_sort() {
+        // LatD, where rows 16 and 124 have the smallest and largest latitude
+        let sorted_rows = sorted(0, SortOrder::Ascending);
+        assert_eq!(sorted_rows.row_indices(0, 1), [16]);
+        assert_eq!(sorted_rows.row_indices(127, 10), [124]);
+        assert_eq!(sorted_rows.position(16), 0);
+        assert_eq!(sorted_rows.position(124), 127);
+
+        // City, where Youngstown comes first
+
+        let sorted_rows = sorted(8, SortOrder::Descending);
+        assert_eq!(sorted_rows.row_indices(0, 1), [0]);
+        assert_eq!(sorted_rows.row_indices(128, 10), [] as [u64; 0]);
+    }
+
+    #[test]
+    fn test_compare() {
+        let key = |s: &str| (stats::parse_number(s), s.to_owned());
+        assert_eq!(compare(&key("9"), &key("10")), Ordering::Less);
+        assert_eq!(compare(&key("10"), &key("abc")), Ordering::Less);
+        assert_eq!(compare(&key("abd"), &key("abc")), Ordering::Greater);
+    }
+}
@@ -0,0 +1,263 @@
+use crate::csv::CsvConfig;
+use crate::export::json_string;
+
+use anyhow::Result;
+use std::io::Write;
+use std::time::Instant;
+
+/// Narrowest type that all non-empty values of a column fit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    /// No values at all, or only whitespace
+    Empty,
+    /// Booleans all written the same way, e.g. yes and no
+    Boolean(BooleanKind),
+    Integer,
+    Number,
+    Text,
+}
+
+/// How booleans are written, ignoring case
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BooleanKind {
+    TrueFalse,
+    YesNo,
+}
+
+impl ColumnType {
+    fn name(&self) -> &'static str {
+        match self {
+            ColumnType::Empty => "empty",
+            ColumnType::Boolean(_) => "boolean",
+            ColumnType::Integer => "integer",
+            ColumnType::Number => "number",
+            ColumnType::Text => "text",
+        }
+    }
+
+    pub fn of(value: &str) -> ColumnType {
+        let value = value.trim();
+        if value.is_empty() {
+            ColumnType::Empty
+        } else if value.parse::<i64>().is_ok() {
+            // Before booleans so that columns of 1 and 0 are integers
+            ColumnType::Integer
+        } else if let Some((kind, _)) = parse_boolean(value) {
+            ColumnType::Boolean(kind)
+        } else if parse_number(value).is_some() {
+            ColumnType::Number
+        } else {
+            ColumnType::Text
+        }
+    }
+
+    pub fn widen(self, other: ColumnType) -> ColumnType {
+        use ColumnType::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Empty, t) | (t, Empty) => t,
+            (Integer, Number) | (Number, Integer) => Number,
+            _ => Text,
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, ColumnType::Integer | ColumnType::Number)
+    }
+}
+
+/// Value as a number, except for words that parse as one such as inf
+pub fn parse_number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if !value.contains(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    value.parse::<f64>().ok()
+}
+
+/// Kind of boolean the value is and whether it is true
+pub fn parse_boolean(value: &str) -> Option<(BooleanKind, bool)> {
+    const KINDS: [(BooleanKind, &str, &str); 2] = [
+        (BooleanKind::TrueFalse, "true", "false"),
+        (BooleanKind::YesNo, "yes", "no"),
+    ];
+    let value = value.trim();
+    KINDS.iter().find_map(|&(kind, t, f)| {
+        if value.eq_ignore_ascii_case(t) {
+            Some((kind, true))
+        } else if value.eq_ignore_ascii_case(f) {
+            Some((kind, false))
+        } else {
+            None
+        }
+    })
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ColumnStats {
+    pub name: String,
+    pub column_type: ColumnType,
+    /// Number of rows where the value is missing or only whitespace
+    pub empty: usize,
+}
+
+#[derive(Debug)]
+pub struct TableStats {
+    /// Name given on the command line, or none for stdin
+    pub filename: Option<String>,
+    pub rows: usize,
+    pub columns: Vec<ColumnStats>,
+    /// Time taken to read all rows in milliseconds
+    pub scan_elapsed: f64,
+}
+
+/// Read all rows of a file to count them and infer the type of each column
+pub fn scan(config: &CsvConfig, filename: Option<String>) -> Result<TableStats> {
+    let start = Instant::now();
+    let mut reader = config.new_reader()?;
+    let mut columns: Vec<ColumnStats> = reader
+        .headers()?
+        .iter()
+        .map(|name| ColumnStats {
+            name: name.to_owned(),
+            column_type: ColumnType::Empty,
+            empty: 0,
+        })
+        .collect();
+
+    let mut rows = 0;
+    for record in reader.records() {
+        let record = record?;
+        rows += 1;
+        for (i, column) in columns.iter_mut().enumerate() {
+            let value = record.get(i).unwrap_or("");
+            let value_type = if config.is_null(value) {
+                ColumnType::Empty
+            } else {
+                ColumnType::of(value)
+            };
+            if value_type == ColumnType::Empty {
+                column.empty += 1;
+            }
+            column.column_type = column.column_type.widen(value_type);
+        }
+    }
+
+    Ok(TableStats {
+        filename,
+        rows,
+        columns,
+        scan_elapsed: start.elapsed().as_micros() as f64 / 1000.0,
+    })
+}
+
+/// Write the stats of each file as a JSON array, one object per file
+pub fn write_json<W: Write>(mut out: W, stats: &[TableStats]) -> Result<()> {
+    writeln!(out, "[")?;
+    for (i, table) in stats.iter().enumerate() {
+        let filename = match &table.filename {
+            Some(f) => json_string(f),
+            None => "null".to_owned(),
+        };
+        writeln!(out, "  {{")?;
+        writeln!(out, "    \"filename\": {},", filename)?;
+        writeln!(out, "    \"rows\": {},", table.rows)?;
+        writeln!(out, "    \"columns\": [")?;
+        for (j, column) in table.columns.iter().enumerate() {
+            let separator = if j + 1 < table.columns.len() { "," } else { "" };
+            writeln!(
+                out,
+                "      {{\"name\": {}, \"type\": \"{}\", \"empty\": {}}}{}",
+                json_string(&column.name),
+                column.column_type.name(),
+                column.empty,
+                separator
+            )?;
+        }
+        writeln!(out, "    ],")?;
+        writeln!(
+            out,
+            "    \"timings_ms\": {{\"scan\": {:.3}}}",
+            table.scan_elapsed
+        )?;
+        let separator = if i + 1 < stats.len() { "," } else { "" };
+        writeln!(out, "  }}{}", separator)?;
+    }
+    writeln!(out, "]")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let config = CsvConfig::new("tests/data/empty_cells.csv");
+        let stats = scan(&config, None).unwrap();
+        assert_eq!(stats.rows, 3);
+        let columns: Vec<(&str, ColumnType, usize)> = stats
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.column_type, c.empty))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("a", ColumnType::Integer, 0),
+                ("b", ColumnType::Text, 2),
+                ("c", ColumnType::Text, 1),
+            ]
+        );
+
+        let mut config = CsvConfig::new("tests/data/empty_cells.csv");
+        config.null_tokens = vec!["z".to_owned()];
+        let stats = scan(&config, None).unwrap();
+        assert_eq!(stats.columns[1].column_type, ColumnType::Empty);
+        assert_eq!(stats.columns[1].empty, 3);
+
+        assert_eq!(ColumnType::of(" 1.5 "), ColumnType::Number);
+        // Not the words parsed as numbers
+        assert_eq!(ColumnType::of("inf"), ColumnType::Text);
+        assert_eq!(
+            ColumnType::Integer.widen(ColumnType::Number),
+            ColumnType::Number
+        );
+        assert_eq!(ColumnType::Text.widen(ColumnType::Empty), ColumnType::Text);
+
+        assert_eq!(
+            ColumnType::of("Yes"),
+            ColumnType::Boolean(BooleanKind::YesNo)
+        );
+        // Integers rather than booleans
+        assert_eq!(ColumnType::of("0"), ColumnType::Integer);
+        assert_eq!(
+            ColumnType::of("true").widen(ColumnType::of("1")),
+            ColumnType::Text
+        );
+        assert_eq!(parse_number(" 1e3 "), Some(1000.0));
+        assert_eq!(parse_number("nan"), None);
+    }
+
+    #[test]
+    fn test_write_json() {
+        let config = CsvConfig::new("tests/data/simple.csv");
+        let mut stats = scan(&config, Some("simple.csv".to_owned())).unwrap();
+        stats.scan_elapsed = 1.5;
+        let mut out = vec![];
+        write_json(&mut out, &[stats]).unwrap();
+        let expected = r#"[
+  {
+    "filename": "simple.csv",
+    "rows": 5000,
+    "columns": [
+      {"name": "a", "type": "text", "empty": 0},
+      {"name": "b", "type": "text", "empty": 0}
+    ],
+    "timings_ms": {"scan": 1.500}
+  }
+]
+"#;
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
@@ -1,17 +1,104 @@
-use crate::csv::Row;
+use crate::csv::{self, Row};
 use crate::find;
-use crate::input::InputMode;
+use crate::input::{self, InputMode};
+use crate::sort::SortOrder;
+use crate::stats::{self, ColumnType};
 use crate::view;
 use regex::Regex;
 use tui::buffer::Buffer;
-use tui::layout::Rect;
+use tui::layout::{Alignment, Rect};
 use tui::style::{Color, Modifier, Style};
 use tui::symbols::line;
 use tui::text::{Span, Spans};
 use tui::widgets::Widget;
-use tui::widgets::{Block, Borders, StatefulWidget};
+use tui::widgets::{Block, Borders, Clear, Paragraph, StatefulWidget, Wrap};
+
+use std::borrow::Cow;
+use std::cmp::{max, min};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::time::Instant;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Narrowest area where at least the row numbers and part of a column fit
+const MIN_WIDTH: u16 = 10;
+
+/// Replace control characters with visible glyphs so that cell content can't
+/// mess up the terminal (e.g. escape sequences). Tabs are expanded to spaces
+/// instead if given a tab width.
+fn sanitize(s: &str, tab_width: Option<u16>) -> Cow<'_, str> {
+    if !s.chars().any(char::is_control) {
+        return Cow::Borrowed(s);
+    }
+    let s = match tab_width {
+        Some(n) => Cow::Owned(s.replace('\t', &" ".repeat(n as usize))),
+        None => Cow::Borrowed(s),
+    };
+    let sanitized = s
+        .chars()
+        .map(|c| match c as u32 {
+            // Unicode control pictures, e.g. ␛ for escape
+            n @ 0x00..=0x1f => char::from_u32(0x2400 + n).unwrap(),
+            0x7f => '\u{2421}',
+            _ if c.is_control() => '\u{fffd}',
+            _ => c,
+        })
+        .collect();
+    Cow::Owned(sanitized)
+}
+
+/// Split the value into lines of at most the given length, and also at line
+/// breaks
+fn wrap_value(value: &str, max_length: usize, tab_width: Option<u16>) -> Vec<String> {
+    let mut lines = vec![];
+    for line in value.lines() {
+        let line = sanitize(line, tab_width);
+        let graphemes: Vec<&str> = line.graphemes(true).collect();
+        if graphemes.is_empty() {
+            lines.push("".to_owned());
+        }
+        for chunk in graphemes.chunks(max(max_length, 1)) {
+            lines.push(chunk.concat());
+        }
+    }
+    if lines.is_empty() {
+        lines.push("".to_owned());
+    }
+    lines
+}
 
-use std::cmp::min;
+/// x of the first column after the row numbers, as laid out by
+/// render_row_numbers
+fn first_column_x(row_nums: &[String], compact: bool) -> u16 {
+    let width = row_nums.iter().map(|x| x.len()).max().unwrap_or(1) as u16;
+    width + if compact { 3 } else { 5 }
+}
+
+/// Length of the value once sanitized, in bytes like the widths of columns
+fn displayed_len(s: &str, tab_width: Option<u16>) -> usize {
+    match tab_width {
+        Some(n) => s.len() + s.matches('\t').count() * (n as usize).saturating_sub(1),
+        None => s.len(),
+    }
+}
+
+/// Show control characters as escape sequences, e.g. \x1b
+fn escape_control(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            _ if c.is_control() && (c as u32) < 0x100 => {
+                out.push_str(format!("\\x{:02x}", c as u32).as_str())
+            }
+            _ if c.is_control() => out.push_str(c.escape_unicode().to_string().as_str()),
+            _ => out.push(c),
+        }
+    }
+    out
+}
 
 #[derive(Debug)]
 pub struct CsvTable<'a> {
@@ -30,10 +117,46 @@ impl<'a> CsvTable<'a> {
 }
 
 impl<'a> CsvTable<'a> {
-    fn get_column_widths(&self, area_width: u16) -> Vec<u16> {
+    /// Widths fitting the values shown (with some space reserved before the
+    /// next column), at least the minimum width but at most 80% of the area
+    /// unless fitted to the values with autofit
+    fn get_column_widths(&self, area_width: u16, state: &CsvTableState) -> Vec<u16> {
+        let mut column_widths = self.get_content_widths(state);
+        for (i, w) in column_widths.iter_mut().enumerate() {
+            let fitted = self
+                .header
+                .get(i)
+                .and_then(|h| state.column_width_overrides.get(h));
+            match fitted {
+                Some(&fitted) => {
+                    *w = max(fitted, state.min_column_width) + state.column_padding();
+                    *w = min(*w, area_width);
+                }
+                None => {
+                    *w = max(*w, state.min_column_width) + state.column_padding();
+                    *w = min(*w, (area_width as f32 * 0.8) as u16);
+                }
+            }
+        }
+        column_widths
+    }
+
+    /// Width of the longest header or value shown in each column
+    fn get_content_widths(&self, state: &CsvTableState) -> Vec<u16> {
         let mut column_widths = Vec::new();
-        for s in self.header.iter() {
-            column_widths.push(s.len() as u16);
+        for (i, s) in self.header.iter().enumerate() {
+            // Room for the arrow after the header of the sorted column
+            let arrow_width = if state.sorted_column(i).is_some() {
+                2
+            } else {
+                0
+            };
+            let header_width = match state.header_mode {
+                HeaderMode::Truncate => s.len(),
+                HeaderMode::Wrap => wrapped_width(s),
+                HeaderMode::Abbreviate => without_vowels(s).len(),
+            };
+            column_widths.push(header_width as u16 + arrow_width);
         }
         for row in self.rows.iter() {
             for (i, value) in row.fields.iter().enumerate() {
@@ -41,44 +164,78 @@ impl<'a> CsvTable<'a> {
                     continue;
                 }
                 let v = column_widths.get_mut(i).unwrap();
-                let value_len = value.len() as u16;
+                let scientific_value = if state.scientific_columns.get(i) == Some(&true) {
+                    scientific(value)
+                } else {
+                    None
+                };
+                let value = scientific_value.as_deref().unwrap_or(value);
+                let value_len = displayed_len(value, state.tab_width) as u16;
                 if *v < value_len {
                     *v = value_len;
                 }
             }
         }
-        for w in column_widths.iter_mut() {
-            *w += 4;
-            *w = min(*w, (area_width as f32 * 0.8) as u16);
-        }
         column_widths
     }
 
+    fn row_numbers(&self, state: &CsvTableState) -> Vec<String> {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| match state.gutter_mode {
+                GutterMode::Original => state.row_number(row.record_num).to_string(),
+                GutterMode::Position => (state.rows_offset + i as u64 + state.row_base).to_string(),
+                GutterMode::Relative => match state.selected {
+                    Some(selected) if (i as u64) > selected => {
+                        format!("+{}", i as u64 - selected)
+                    }
+                    Some(selected) if (i as u64) < selected => {
+                        format!("-{}", selected - i as u64)
+                    }
+                    _ => "0".to_owned(),
+                },
+            })
+            .collect()
+    }
+
     fn render_row_numbers(
         &self,
         buf: &mut Buffer,
         state: &mut CsvTableState,
         area: Rect,
-        rows: &[Row],
+        row_nums: Vec<String>,
+        // Row taking more than one line and the number of lines after the first
+        expanded: Option<(usize, u16)>,
     ) -> u16 {
         // TODO: better to derminte width from total number of records, so this is always fixed
-        let max_row_num = rows.iter().map(|x| x.record_num).max().unwrap_or(0);
-        let mut section_width = format!("{}", max_row_num).len() as u16;
+        let mut section_width = row_nums.iter().map(|x| x.len()).max().unwrap_or(1) as u16;
 
         // Render line numbers
         let y_first_record = area.y;
         let mut y = area.y;
-        for row in rows.iter() {
-            let row_num_formatted = row.record_num.to_string();
-            let style = Style::default().fg(Color::Rgb(64, 64, 64));
+        for (i, (row, row_num_formatted)) in self.rows.iter().zip(row_nums).enumerate() {
+            let style = if state.marked_rows.contains(&row.record_num) {
+                Style::default()
+                    .fg(Color::Rgb(0, 150, 255))
+                    .add_modifier(Modifier::BOLD)
+            } else if state.duplicate_rows.contains(&row.record_num) {
+                Style::default().fg(Color::Rgb(200, 120, 0))
+            } else {
+                Style::default().fg(Color::Rgb(64, 64, 64))
+            };
             let span = Span::styled(row_num_formatted, style);
             buf.set_span(0, y, &span, section_width);
             y += 1;
+            if let Some((_, extra_lines)) = expanded.filter(|&(j, _)| j == i) {
+                y += extra_lines;
+            }
             if y >= area.bottom() {
                 break;
             }
         }
-        section_width = section_width + 2 + 1; // one char reserved for line; add one for symmetry
+        // one char reserved for line; add one for symmetry
+        section_width += if state.compact { 2 } else { 3 };
 
         state.borders_state = Some(BordersState {
             x_row_separator: section_width,
@@ -86,20 +243,57 @@ impl<'a> CsvTable<'a> {
         });
 
         // Add more space before starting first column
-        section_width += 2;
+        section_width += if state.compact { 1 } else { 2 };
 
         section_width
     }
 
-    fn render_header_borders(&self, buf: &mut Buffer, area: Rect) -> (u16, u16) {
-        let block = Block::default()
+    fn render_header_borders(
+        &self,
+        buf: &mut Buffer,
+        area: Rect,
+        state: &CsvTableState,
+    ) -> (u16, u16) {
+        let mut block = Block::default()
             .borders(Borders::TOP | Borders::BOTTOM)
             .border_style(Style::default().fg(Color::Rgb(64, 64, 64)));
-        let height = 3;
+        // Tab bar on the top border
+        if !state.tab_names.is_empty() {
+            let mut spans = vec![];
+            for (i, name) in state.tab_names.iter().enumerate() {
+                let style = if i == state.active_tab {
+                    Style::default()
+                        .fg(Color::Rgb(255, 200, 0))
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(Color::Rgb(128, 128, 128))
+                };
+                spans.push(Span::styled(format!(" {}:{} ", i + 1, name), style));
+            }
+            block = block.title(Spans::from(spans));
+        }
+        let height = 2 + state.header_lines();
         let area = Rect::new(0, 0, area.width, height);
         block.render(area, buf);
-        // y pos of header text and next line
-        (height.saturating_sub(2), height)
+        state.glyphs().replace_outline(area, buf);
+        // y pos of header text and line after the header
+        (1 + state.show_ruler as u16, height)
+    }
+
+    /// Column numbers above the headers as last rendered, as counted in the
+    /// status bar
+    fn render_ruler(&self, buf: &mut Buffer, state: &CsvTableState) {
+        let style = Style::default()
+            .fg(Color::Rgb(100, 100, 100))
+            .add_modifier(Modifier::DIM);
+        for &(start, end, col_index) in &state.header_columns {
+            // Columns past the right edge take no space
+            if end <= start {
+                continue;
+            }
+            let width = end.saturating_sub(start) as usize;
+            buf.set_stringn(start, 1, (col_index + 1).to_string(), width, style);
+        }
     }
 
     fn render_other_borders(&self, buf: &mut Buffer, area: Rect, state: &CsvTableState) {
@@ -118,10 +312,12 @@ impl<'a> CsvTable<'a> {
             .border_style(Style::default().fg(Color::Rgb(64, 64, 64)));
         let line_number_area = Rect::new(0, y_first_record, section_width, area.height);
         line_number_block.render(line_number_area, buf);
+        let glyphs = state.glyphs();
+        glyphs.replace_outline(line_number_area, buf);
 
         // Intersection with header separator
         buf.get_mut(section_width - 1, y_first_record - 1)
-            .set_symbol(line::HORIZONTAL_DOWN);
+            .set_symbol(glyphs.horizontal_down);
 
         // Status separator at the bottom (rendered here first for the interesection)
         let block = Block::default()
@@ -129,31 +325,94 @@ impl<'a> CsvTable<'a> {
             .border_style(Style::default().fg(Color::Rgb(64, 64, 64)));
         let status_separator_area = Rect::new(0, y_first_record + area.height, area.width, 1);
         block.render(status_separator_area, buf);
+        glyphs.replace_outline(status_separator_area, buf);
 
         // Intersection with bottom separator
         buf.get_mut(section_width - 1, y_first_record + area.height)
-            .set_symbol(line::HORIZONTAL_UP);
+            .set_symbol(glyphs.horizontal_up);
+
+        // Lines between columns
+        let style = Style::default().fg(Color::Rgb(64, 64, 64));
+        for &x in state
+            .column_separators
+            .iter()
+            .filter(|&&x| x < area.right())
+        {
+            buf.get_mut(x, y_first_record.saturating_sub(1))
+                .set_style(style)
+                .set_symbol(glyphs.horizontal_down);
+            for y in y_first_record..y_first_record + area.height {
+                buf.get_mut(x, y)
+                    .set_style(style)
+                    .set_symbol(glyphs.vertical);
+            }
+            buf.get_mut(x, y_first_record + area.height)
+                .set_style(style)
+                .set_symbol(glyphs.horizontal_up);
+        }
 
         // Vertical line after last rendered column
         // TODO: refactor
         let col_ending_pos_x = state.col_ending_pos_x;
-        if !state.has_more_cols_to_show() && col_ending_pos_x < area.right() {
+        if !state.has_more_cols_to_show() && !state.compact && col_ending_pos_x < area.right() {
             buf.get_mut(col_ending_pos_x, y_first_record.saturating_sub(1))
                 .set_style(Style::default().fg(Color::Rgb(64, 64, 64)))
-                .set_symbol(line::HORIZONTAL_DOWN);
+                .set_symbol(glyphs.horizontal_down);
 
             for y in y_first_record..y_first_record + area.height {
                 buf.get_mut(col_ending_pos_x, y)
                     .set_style(Style::default().fg(Color::Rgb(64, 64, 64)))
-                    .set_symbol(line::VERTICAL);
+                    .set_symbol(glyphs.vertical);
             }
 
             buf.get_mut(col_ending_pos_x, y_first_record + area.height)
                 .set_style(Style::default().fg(Color::Rgb(64, 64, 64)))
-                .set_symbol(line::HORIZONTAL_UP);
+                .set_symbol(glyphs.horizontal_up);
         }
     }
 
+    fn render_scrollbars(&self, buf: &mut Buffer, area: Rect, state: &CsvTableState) {
+        let style = Style::default().fg(Color::Rgb(100, 100, 100));
+        let glyphs = state.glyphs();
+
+        // Vertical: thumb on the right edge of the rows, relative to the
+        // filtered rows if filtering
+        let total_rows = state.num_filtered_rows.or(state.total_line_number);
+        if let Some(total) = total_rows {
+            let visible = min(self.rows.len(), area.height as usize);
+            if let Some((start, len)) =
+                scrollbar_thumb(area.height, state.rows_offset as usize, visible, total)
+            {
+                let x = area.right().saturating_sub(1);
+                for y in area.y + start..area.y + start + len {
+                    buf.get_mut(x, y)
+                        .set_style(style)
+                        .set_symbol(glyphs.vertical_thumb);
+                }
+            }
+        }
+
+        // Horizontal: thumb on the separator above the status line
+        if let Some(borders_state) = &state.borders_state {
+            let x_first = borders_state.x_row_separator;
+            let width = area.width.saturating_sub(x_first);
+            if let Some((start, len)) = scrollbar_thumb(
+                width,
+                state.cols_offset as usize,
+                state.num_cols_rendered as usize,
+                state.total_cols,
+            ) {
+                let y = area.bottom();
+                for x in x_first + start..x_first + start + len {
+                    buf.get_mut(x, y)
+                        .set_style(style)
+                        .set_symbol(glyphs.horizontal_thumb);
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn render_row(
         &self,
         buf: &mut Buffer,
@@ -163,9 +422,14 @@ impl<'a> CsvTable<'a> {
         x: u16,
         y: u16,
         is_header: bool,
-        row: &'a [String],
+        row: &[String],
         row_index: Option<usize>,
+        // Row shown above, if any, for showing the changes from it
+        previous: Option<&[String]>,
         is_selected: bool,
+        // Line after the first of a row taking more than one line, where
+        // values that already ended are left blank
+        is_continuation: bool,
     ) {
         let mut x_offset_header = x;
         let mut remaining_width = area.width.saturating_sub(x);
@@ -175,13 +439,52 @@ impl<'a> CsvTable<'a> {
         let mut col_ending_pos_x = 0;
         let mut num_cols_rendered = 0;
         for (col_index, (hname, &hlen)) in row.iter().zip(column_widths).enumerate() {
-            if col_index < cols_offset {
+            if col_index < cols_offset && !(state.freeze_panes && col_index == 0) {
                 continue;
             }
             let effective_width = min(remaining_width, hlen);
+            let truncate_side = self
+                .header
+                .get(col_index)
+                .map_or(state.truncate_side, |h| state.truncate_side_for(h));
+            let alignment = self
+                .header
+                .get(col_index)
+                .map_or(Alignment::Left, |h| state.alignment_for(h));
             let mut style = Style::default();
             if is_header {
                 style = style.add_modifier(Modifier::BOLD);
+                // The current column, e.g. for copying its name
+                if col_index as u64 == state.current_column() {
+                    style = style
+                        .fg(Color::Rgb(0, 150, 255))
+                        .add_modifier(Modifier::UNDERLINED);
+                }
+            }
+            if let Some(previous) = previous.filter(|_| state.show_changes) {
+                style = if previous.get(col_index) == Some(hname) {
+                    style
+                        .fg(Color::Rgb(100, 100, 100))
+                        .add_modifier(Modifier::DIM)
+                } else {
+                    style
+                        .fg(Color::Rgb(0, 200, 100))
+                        .add_modifier(Modifier::BOLD)
+                };
+            }
+            let is_null = !is_header && state.null_tokens.contains(hname);
+            let scientific_value = if !is_header
+                && !is_continuation
+                && state.scientific_columns.get(col_index) == Some(&true)
+            {
+                scientific(hname)
+            } else {
+                None
+            };
+            if is_null {
+                style = style
+                    .fg(Color::Rgb(100, 100, 100))
+                    .add_modifier(Modifier::DIM);
             }
             if is_selected {
                 style = style
@@ -189,10 +492,37 @@ impl<'a> CsvTable<'a> {
                     .add_modifier(Modifier::BOLD);
             }
             match &state.finder_state {
+                _ if state.show_empty_cells
+                    && !is_header
+                    && !is_continuation
+                    && (is_null || hname.trim().is_empty()) =>
+                {
+                    // Display-only placeholder so that empty cells are not easily missed
+                    let glyphs = state.glyphs();
+                    let placeholder = if is_null || hname.is_empty() {
+                        glyphs.empty.to_owned()
+                    } else {
+                        glyphs.space.repeat(hname.chars().count())
+                    };
+                    let placeholder_style = style
+                        .fg(Color::Rgb(100, 100, 100))
+                        .add_modifier(Modifier::DIM);
+                    let span = Span::styled(placeholder, placeholder_style);
+                    self.set_spans(
+                        buf,
+                        &[span],
+                        x_offset_header,
+                        y,
+                        effective_width,
+                        truncate_side,
+                        alignment,
+                        state,
+                    );
+                }
                 // TODO: seems like doing a bit too much of heavy lifting of
                 // checking for matches (finder's work)
                 FinderState::FinderActive(active)
-                    if active.target.is_match(hname) && !is_header =>
+                    if active.is_match(col_index, hname) && !is_header =>
                 {
                     let mut highlight_style = style.fg(Color::Rgb(200, 0, 0));
                     if let Some(hl) = &active.found_record {
@@ -206,13 +536,129 @@ impl<'a> CsvTable<'a> {
                         }
                     }
                     let spans = Self::get_highlighted_spans(active, hname, style, highlight_style);
-                    self.set_spans(buf, &spans, x_offset_header, y, effective_width);
+                    self.set_spans(
+                        buf,
+                        &spans,
+                        x_offset_header,
+                        y,
+                        effective_width,
+                        truncate_side,
+                        alignment,
+                        state,
+                    );
+                }
+                _ if is_header => {
+                    let label = match state.sorted_column(col_index) {
+                        Some(order) => format!("{} {}", hname, state.glyphs().arrow(order)),
+                        None => hname.to_owned(),
+                    };
+                    // Same as the space left for the content in set_spans
+                    let max_length =
+                        effective_width.saturating_sub(state.column_padding()) as usize;
+                    let lines = match state.header_mode {
+                        HeaderMode::Truncate => vec![label.clone()],
+                        HeaderMode::Wrap => {
+                            let (first, second) = wrap_header(&label, max_length);
+                            vec![first, second]
+                        }
+                        HeaderMode::Abbreviate => {
+                            vec![abbreviate(&label, max_length, state.glyphs().ellipsis)]
+                        }
+                    };
+                    if col_index as u64 == state.current_column() {
+                        let shown: String = lines.concat();
+                        state.current_header_shortened = lines
+                            .iter()
+                            .any(|line| line.graphemes(true).count() > max_length)
+                            || shown.replace(' ', "") != label.replace(' ', "");
+                    }
+                    for (i, line) in lines.into_iter().enumerate() {
+                        let span = Span::styled(line, style);
+                        self.set_spans(
+                            buf,
+                            &[span],
+                            x_offset_header,
+                            y + i as u16,
+                            effective_width,
+                            truncate_side,
+                            alignment,
+                            state,
+                        );
+                    }
+                }
+                _ if state.boolean_columns.get(col_index) == Some(&true) => {
+                    let glyphs = state.glyphs();
+                    let shown = match stats::parse_boolean(hname) {
+                        Some((_, true)) => glyphs.check,
+                        Some((_, false)) => glyphs.cross,
+                        None => hname.as_str(),
+                    };
+                    let span = Span::styled(shown, style);
+                    self.set_spans(
+                        buf,
+                        &[span],
+                        x_offset_header,
+                        y,
+                        effective_width,
+                        truncate_side,
+                        alignment,
+                        state,
+                    );
+                }
+                _ if scientific_value.is_some() => {
+                    let span = Span::styled(scientific_value.unwrap_or_default(), style);
+                    self.set_spans(
+                        buf,
+                        &[span],
+                        x_offset_header,
+                        y,
+                        effective_width,
+                        truncate_side,
+                        alignment,
+                        state,
+                    );
+                }
+                _ if state.show_whitespace => {
+                    let spans = whitespace_spans(hname, style, state.glyphs().space);
+                    self.set_spans(
+                        buf,
+                        &spans,
+                        x_offset_header,
+                        y,
+                        effective_width,
+                        truncate_side,
+                        alignment,
+                        state,
+                    );
                 }
                 _ => {
                     let span = Span::styled((*hname).as_str(), style);
-                    self.set_spans(buf, &[span], x_offset_header, y, effective_width);
+                    self.set_spans(
+                        buf,
+                        &[span],
+                        x_offset_header,
+                        y,
+                        effective_width,
+                        truncate_side,
+                        alignment,
+                        state,
+                    );
                 }
             };
+            if is_header {
+                state.header_columns.push((
+                    x_offset_header,
+                    x_offset_header + effective_width,
+                    col_index,
+                ));
+                // Between columns shown in full, the last one has the line
+                // after the last column instead
+                if state.grid && remaining_width >= hlen && col_index + 1 < row.len() {
+                    let padding = state.column_padding();
+                    let x = x_offset_header + hlen - 1 - (padding - 1) / 2;
+                    state.column_separators.push(x);
+                }
+            }
             x_offset_header += hlen;
             col_ending_pos_x = x_offset_header;
             num_cols_rendered += 1;
@@ -227,16 +673,20 @@ impl<'a> CsvTable<'a> {
         state.col_ending_pos_x = col_ending_pos_x;
     }
 
-    fn get_highlighted_spans(
+    fn get_highlighted_spans<'b>(
         active: &FinderActiveState,
-        hname: &'a str,
+        hname: &'b str,
         style: Style,
         highlight_style: Style,
-    ) -> Vec<Span<'a>> {
+    ) -> Vec<Span<'b>> {
         // Each span can only have one style, hence split content into matches and non-matches and
         // set styles accordingly
-        let mut matches = active.target.find_iter(hname);
-        let non_matches = active.target.split(hname);
+        let target = match active.target.regex() {
+            Some(target) => target,
+            None => return vec![Span::styled(hname, style)],
+        };
+        let mut matches = target.find_iter(hname);
+        let non_matches = target.split(hname);
         let mut spans = vec![];
         for part in non_matches {
             let span = Span::styled(part, style);
@@ -253,34 +703,98 @@ impl<'a> CsvTable<'a> {
         spans
     }
 
-    fn set_spans(&self, buf: &mut Buffer, spans: &[Span], x: u16, y: u16, width: u16) {
-        // TODO: make constant?
-        let suffix = "…";
+    #[allow(clippy::too_many_arguments)]
+    fn set_spans(
+        &self,
+        buf: &mut Buffer,
+        spans: &[Span],
+        x: u16,
+        y: u16,
+        width: u16,
+        truncate_side: TruncateSide,
+        alignment: Alignment,
+        state: &CsvTableState,
+    ) {
+        let suffix = state.glyphs().ellipsis;
         let suffix_len = suffix.chars().count();
 
         // Reserve some space before the next column (same number used in get_column_widths)
-        let mut remaining_width = width.saturating_sub(4);
+        let max_length = width.saturating_sub(state.column_padding()) as usize;
+
+        let spans: Vec<Span> = spans
+            .iter()
+            .map(|span| {
+                let content = sanitize(&span.content, state.tab_width).into_owned();
+                Span::styled(content, span.style)
+            })
+            .collect();
+        let spans = spans.as_slice();
+
+        let total_length: usize = spans
+            .iter()
+            .map(|span| span.content.graphemes(true).count())
+            .sum();
+
+        let cur_spans = if total_length <= max_length {
+            spans.to_vec()
+        } else {
+            let max_content_length = max_length.saturating_sub(suffix_len);
+            match truncate_side {
+                TruncateSide::Right => {
+                    let mut cur_spans = Self::take_graphemes(spans, max_content_length, false);
+                    cur_spans.push(Span::raw(suffix));
+                    cur_spans
+                }
+                TruncateSide::Left => {
+                    let mut cur_spans = Self::take_graphemes(spans, max_content_length, true);
+                    cur_spans.push(Span::raw(suffix));
+                    cur_spans.reverse();
+                    cur_spans
+                }
+            }
+        };
+
+        let length: usize = cur_spans
+            .iter()
+            .map(|span| span.content.graphemes(true).count())
+            .sum();
+        let space = max_length.saturating_sub(length) as u16;
+        let indent = match alignment {
+            Alignment::Left => 0,
+            Alignment::Center => space / 2,
+            Alignment::Right => space,
+        };
+        let spans = Spans::from(cur_spans);
+        buf.set_spans(x + indent, y, &spans, width - indent);
+    }
 
-        // Pack as many spans as possible until hitting width limit
+    /// Pack as many graphemes of the spans as possible until hitting the
+    /// length limit, either from the start or from the end. In the latter case
+    /// the returned spans are in reverse order.
+    fn take_graphemes<'b>(spans: &[Span<'b>], max_length: usize, from_end: bool) -> Vec<Span<'b>> {
+        let ordered: Vec<&Span> = if from_end {
+            spans.iter().rev().collect()
+        } else {
+            spans.iter().collect()
+        };
+        let mut remaining_length = max_length;
         let mut cur_spans = vec![];
-        for span in spans {
-            if span.content.len() <= remaining_width.into() {
+        for span in ordered {
+            let graphemes: Vec<&str> = span.content.graphemes(true).collect();
+            if graphemes.len() <= remaining_length {
                 cur_spans.push(span.clone());
-                remaining_width = remaining_width.saturating_sub(span.content.len() as u16);
+                remaining_length -= graphemes.len();
             } else {
-                let max_content_length = remaining_width.saturating_sub(suffix_len as u16) as usize;
-                let truncated_content: String =
-                    span.content.chars().take(max_content_length).collect();
-                let truncated_span = Span::styled(truncated_content, span.style);
-                cur_spans.push(truncated_span);
-                cur_spans.push(Span::raw(suffix));
-                // TODO: handle breaking into multiple lines, for now don't care about remaining_width
+                let truncated_content = if from_end {
+                    graphemes[graphemes.len() - remaining_length..].concat()
+                } else {
+                    graphemes[..remaining_length].concat()
+                };
+                cur_spans.push(Span::styled(truncated_content, span.style));
                 break;
             }
         }
-
-        let spans = Spans::from(cur_spans);
-        buf.set_spans(x, y, &spans, width);
+        cur_spans
     }
 
     fn render_status(&self, area: Rect, buf: &mut Buffer, state: &mut CsvTableState) {
@@ -289,9 +803,12 @@ impl<'a> CsvTable<'a> {
         let mut content: String;
         if let Some(error) = &state.user_error {
             content = error.to_owned();
+        } else if let Some(message) = &state.user_message {
+            content = message.to_owned();
         } else if let BufferState::Enabled(buffer_mode, buf) = &state.buffer_content {
             content = buf.to_owned();
-            let format_buffer = |prefix: &str| format!("{}: {}█", prefix, content);
+            let cursor = state.glyphs().cursor;
+            let format_buffer = |prefix: &str| format!("{}: {}{}", prefix, content, cursor);
             match buffer_mode {
                 InputMode::GotoLine => {
                     content = format_buffer("Go to line");
@@ -305,6 +822,28 @@ impl<'a> CsvTable<'a> {
                 InputMode::FilterColumns => {
                     content = format_buffer("Columns regex");
                 }
+                InputMode::Count => {
+                    content = format_buffer("Count");
+                }
+                InputMode::ReplacePreview => {
+                    content = format_buffer("Replace (regex/replacement)");
+                }
+                InputMode::ColumnStarts => {
+                    content = format_buffer("Column starts");
+                }
+                InputMode::ExportMarked => {
+                    content = format_buffer("Export marked rows to");
+                }
+                InputMode::Palette => {
+                    content = format_buffer("Command");
+                }
+                InputMode::EditCell => {
+                    content = format_buffer("Edit");
+                }
+                InputMode::ConfirmEdit => {
+                    let filename = state.filename.as_deref().unwrap_or("the file");
+                    content = format!("Write changes to {}? (y/n)", filename);
+                }
                 _ => {}
             }
         } else {
@@ -316,8 +855,13 @@ impl<'a> CsvTable<'a> {
             }
 
             // Row / Col
-            let total_str = if state.total_line_number.is_some() {
-                format!("{}", state.total_line_number.unwrap())
+            let total_str = if let Some(n) = state.total_line_number {
+                // Estimated until the whole file is parsed
+                if state.total_line_number_approx {
+                    format!("~{}", n)
+                } else {
+                    format!("{}", n)
+                }
             } else {
                 "?".to_owned()
             };
@@ -327,22 +871,85 @@ impl<'a> CsvTable<'a> {
                 self.rows.first()
             };
             let row_num = match current_row {
-                Some(row) => row.record_num.to_string(),
+                Some(row) => state.row_number(row.record_num).to_string(),
                 _ => "-".to_owned(),
             };
+            // The full name of the current column if it doesn't fit
+            let current_header = match self.header.get(state.current_column() as usize) {
+                Some(header) if state.current_header_shortened => format!(": {}", header),
+                _ => "".to_owned(),
+            };
             content += format!(
-                " [Row {}/{}, Col {}/{}]",
+                " [Row {}/{}, Col {}/{}{}]",
                 row_num,
                 total_str,
-                state.cols_offset + 1,
+                state.current_column() + 1,
                 state.total_cols,
+                current_header,
             )
             .as_str();
 
+            // Position among the rows shown, e.g. once filtered or sorted
+            if let (true, Some(i)) = (state.show_cell_position, state.selected) {
+                let column = state.current_column();
+                content += format!(
+                    " [R{} C{} ({})]",
+                    state.rows_offset + i + 1,
+                    column + 1,
+                    self.header.get(column as usize).map_or("", |h| h.as_str()),
+                )
+                .as_str();
+            }
+
+            if state.indexing {
+                content += format!(" [Indexing{}]", state.glyphs().ellipsis).as_str();
+            }
+
+            // Make it obvious that not all rows are shown
+            if let Some(n) = state.num_filtered_rows {
+                content += format!(" [{}/{} rows (filtered)]", n, total_str).as_str();
+            }
+
+            if state.raw_fields {
+                content += " [raw fields]";
+            }
+
+            if let Some(sort_state) = &state.sort_state {
+                content += sort_state.status_line(state.glyphs()).as_str();
+            }
+
+            if let Some((first, last)) = state.selection_range {
+                content += format!(" [{} selected", last - first + 1).as_str();
+                if let Some((sum, n)) = state.selection_sum {
+                    content += format!(
+                        ", sum {}, avg {}",
+                        format_number(sum),
+                        format_number(sum / n as f64)
+                    )
+                    .as_str();
+                }
+                content += "]";
+            }
+
+            if state.num_marked > 0 {
+                content += format!(" [{} marked]", state.num_marked).as_str();
+            }
+
+            if state.num_skipped > 0 {
+                content += format!(" [{} stale rows skipped]", state.num_skipped).as_str();
+            }
+
             // Finder
             if let FinderState::FinderActive(s) = &state.finder_state {
                 content += format!(" {}", s.status_line()).as_str();
             }
+            if let Some(header_find) = &state.header_find {
+                content += format!(" {}", header_find.status_line()).as_str();
+            }
+
+            if let Some(count_state) = &state.count_state {
+                content += format!(" {}", count_state.status_line()).as_str();
+            }
 
             if let Some(stats_line) = &state.debug_stats.status_line() {
                 content += format!(" {}", stats_line).as_str();
@@ -353,6 +960,15 @@ impl<'a> CsvTable<'a> {
                 content += format!(" {}", info.status_line()).as_str();
             }
 
+            // Length of the current cell, e.g. for spotting padded values
+            let current_value = match (state.selected, current_row) {
+                (Some(_), Some(row)) => row.fields.get(state.current_column() as usize),
+                _ => None,
+            };
+            if let (true, Some(value)) = (state.show_cell_length, current_value) {
+                content += format!(" [{}]", cell_length(value)).as_str();
+            }
+
             // Debug
             if !state.debug.is_empty() {
                 content += format!(" (debug: {})", state.debug).as_str();
@@ -363,6 +979,116 @@ impl<'a> CsvTable<'a> {
     }
 }
 
+impl<'a> CsvTable<'a> {
+    fn render_detail(&self, area: Rect, buf: &mut Buffer, state: &mut CsvTableState) {
+        let row = match state.selected.and_then(|i| self.rows.get(i as usize)) {
+            Some(row) => row,
+            None => return,
+        };
+
+        let width = area.width.saturating_mul(4) / 5;
+        let height = area.height.saturating_mul(4) / 5;
+        let popup_area = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        let inner_width = width.saturating_sub(2).max(1) as usize;
+        let inner_height = height.saturating_sub(2) as usize;
+
+        let header_style = Style::default().add_modifier(Modifier::BOLD);
+        let highlight_style = Style::default().fg(Color::Rgb(200, 0, 0));
+        let values: Vec<String> = row
+            .fields
+            .iter()
+            .map(|value| {
+                if state.detail_raw {
+                    escape_control(value)
+                } else {
+                    sanitize(value, state.tab_width).into_owned()
+                }
+            })
+            .collect();
+        if let Some(find) = &mut state.detail_find {
+            // The number of matches changes when another row is selected
+            find.num_found = values
+                .iter()
+                .map(|value| find.regex.find_iter(value).count())
+                .sum();
+            if find.cursor >= find.num_found {
+                find.cursor = 0;
+            }
+        }
+        let mut lines = vec![];
+        // Wrapped line of the current match, estimated from the widths since
+        // the paragraph wraps by words
+        let mut num_wrapped_lines = 0;
+        let mut current_line = None;
+        let mut num_found = 0;
+        for (i, value) in values.into_iter().enumerate() {
+            let header = self.header.get(i).map(|h| h.as_str()).unwrap_or("");
+            let header = format!("{}: ", sanitize(header, state.tab_width));
+            let header_width = header.graphemes(true).count();
+            let field_width = header_width + value.graphemes(true).count();
+            let mut spans = vec![Span::styled(header, header_style)];
+            match &state.detail_find {
+                Some(find) => {
+                    let mut last = 0;
+                    for m in find.regex.find_iter(&value) {
+                        let style = if num_found == find.cursor {
+                            let offset = header_width + value[..m.start()].graphemes(true).count();
+                            current_line = Some(num_wrapped_lines + offset / inner_width);
+                            highlight_style.bg(Color::LightYellow)
+                        } else {
+                            highlight_style
+                        };
+                        spans.push(Span::raw(value[last..m.start()].to_owned()));
+                        spans.push(Span::styled(m.as_str().to_owned(), style));
+                        last = m.end();
+                        num_found += 1;
+                    }
+                    spans.push(Span::raw(value[last..].to_owned()));
+                }
+                None => spans.push(Span::raw(value)),
+            }
+            num_wrapped_lines += max(1, field_width.div_ceil(inner_width));
+            lines.push(Spans::from(spans));
+        }
+
+        // Scroll only as needed, keeping the current match around the middle
+        let scroll = match current_line {
+            Some(line) if line >= inner_height => min(
+                line - inner_height / 2,
+                num_wrapped_lines.saturating_sub(inner_height),
+            ),
+            _ => 0,
+        };
+
+        let raw_marker = if state.detail_raw { " (raw)" } else { "" };
+        let find_marker = match &state.detail_find {
+            Some(find) if find.num_found == 0 => " [Not found]".to_owned(),
+            Some(find) => format!(" [Found {}/{}]", find.cursor + 1, find.num_found),
+            None => "".to_owned(),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Rgb(128, 128, 128)))
+            .title(format!(
+                " Row {}{}{} ",
+                row.record_num, raw_marker, find_marker
+            ));
+        let paragraph = Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false })
+            .scroll((scroll as u16, 0));
+
+        Clear.render(popup_area, buf);
+        paragraph.render(popup_area, buf);
+        state.glyphs().replace_outline(popup_area, buf);
+    }
+}
+
 impl<'a> StatefulWidget for CsvTable<'a> {
     type State = CsvTableState;
 
@@ -372,12 +1098,38 @@ impl<'a> StatefulWidget for CsvTable<'a> {
         if area.area() == 0 {
             return;
         }
+        // The borders, the header and the status bar don't fit, let alone a row
+        if area.width < MIN_WIDTH || area.height < state.header_lines() + 4 {
+            render_too_small(area, buf);
+            return;
+        }
+        let start = Instant::now();
 
-        let status_height = 2;
-        let column_widths = self.get_column_widths(area.width);
+        let status_height = state.status_height();
+        let column_types = if state.show_scientific || state.show_booleans {
+            column_types(self.rows, self.header.len(), &state.null_tokens)
+        } else {
+            vec![]
+        };
+        // Needed for the widths of the values as shown
+        state.scientific_columns = if state.show_scientific {
+            column_types.iter().map(|t| t.is_numeric()).collect()
+        } else {
+            vec![]
+        };
+        let column_widths = self.get_column_widths(area.width, state);
         state.column_widths = Some(column_widths.clone());
+        state.content_widths = self.get_content_widths(state);
+        state.boolean_columns = if state.show_booleans {
+            column_types
+                .iter()
+                .map(|t| matches!(t, ColumnType::Boolean(_)))
+                .collect()
+        } else {
+            vec![]
+        };
 
-        let (y_header, y_first_record) = self.render_header_borders(buf, area);
+        let (y_header, y_first_record) = self.render_header_borders(buf, area, state);
 
         // row area: including row numbers and row content
         let rows_area = Rect::new(
@@ -389,8 +1141,29 @@ impl<'a> StatefulWidget for CsvTable<'a> {
                 .saturating_sub(status_height),
         );
 
-        let row_num_section_width = self.render_row_numbers(buf, state, rows_area, self.rows);
-
+        // Values of the expanded row wrapped over as many lines as needed
+        let row_nums = self.row_numbers(state);
+        let x_first_column = first_column_x(&row_nums, state.compact);
+        let expanded = state
+            .selected
+            .filter(|_| state.selection_range.is_none())
+            .and_then(|i| {
+                let row = self.rows.get(i as usize)?;
+                let lines =
+                    state.wrapped_fields(row, &column_widths, area.width, x_first_column)?;
+                Some((i as usize, lines))
+            });
+        let expanded_extra_lines = expanded
+            .as_ref()
+            .map(|(i, lines)| (*i, lines.len() as u16 - 1));
+        state.expanded_extra_lines = expanded_extra_lines.map_or(0, |(_, n)| n);
+        let row_num_section_width =
+            self.render_row_numbers(buf, state, rows_area, row_nums, expanded_extra_lines);
+
+        state.header_columns.clear();
+        state.column_separators.clear();
+        state.y_header = y_header;
+        state.current_header_shortened = false;
         self.render_row(
             buf,
             state,
@@ -401,34 +1174,81 @@ impl<'a> StatefulWidget for CsvTable<'a> {
             true,
             &self.header,
             None,
+            None,
+            false,
             false,
         );
+        if state.show_ruler {
+            self.render_ruler(buf, state);
+        }
 
         let mut y_offset = y_first_record;
         for (i, row) in self.rows.iter().enumerate() {
-            let is_selected = if let Some(selected_row) = state.selected {
+            let is_selected = if let Some((first, last)) = state.selection_range {
+                let offset = state.rows_offset + i as u64;
+                offset >= first && offset <= last
+            } else if let Some(selected_row) = state.selected {
                 i as u64 == selected_row
             } else {
                 false
             };
-            self.render_row(
-                buf,
-                state,
-                &column_widths,
-                rows_area,
-                row_num_section_width,
-                y_offset,
-                false,
-                &row.fields,
-                Some(row.record_num - 1),
-                is_selected,
-            );
-            y_offset += 1;
+            match &expanded {
+                Some((j, lines)) if *j == i => {
+                    for (k, fields) in lines.iter().enumerate() {
+                        if k > 0 && y_offset >= rows_area.bottom() {
+                            break;
+                        }
+                        self.render_row(
+                            buf,
+                            state,
+                            &column_widths,
+                            rows_area,
+                            row_num_section_width,
+                            y_offset,
+                            false,
+                            fields,
+                            Some(row.record_num - 1),
+                            None,
+                            is_selected,
+                            k > 0,
+                        );
+                        y_offset += 1;
+                    }
+                }
+                _ => {
+                    self.render_row(
+                        buf,
+                        state,
+                        &column_widths,
+                        rows_area,
+                        row_num_section_width,
+                        y_offset,
+                        false,
+                        &row.fields,
+                        Some(row.record_num - 1),
+                        i.checked_sub(1).map(|j| self.rows[j].fields.as_slice()),
+                        is_selected,
+                        false,
+                    );
+                    y_offset += 1;
+                }
+            }
             if y_offset >= rows_area.bottom() {
                 break;
             }
         }
 
+        if let FinderState::FinderActive(s) = &state.finder_state {
+            if s.is_empty_filter() {
+                let data_area = Rect {
+                    x: rows_area.x + row_num_section_width,
+                    width: rows_area.width.saturating_sub(row_num_section_width),
+                    ..rows_area
+                };
+                render_no_rows(data_area, buf, &s.target);
+            }
+        }
+
         let status_area = Rect::new(
             area.x,
             area.bottom().saturating_sub(status_height),
@@ -438,6 +1258,433 @@ impl<'a> StatefulWidget for CsvTable<'a> {
         self.render_status(status_area, buf, state);
 
         self.render_other_borders(buf, rows_area, state);
+        self.render_scrollbars(buf, rows_area, state);
+
+        if state.show_detail {
+            self.render_detail(area, buf, state);
+        }
+        match &state.buffer_content {
+            BufferState::Enabled(InputMode::Palette, query) => {
+                render_palette(area, buf, query, state.glyphs())
+            }
+            BufferState::Enabled(InputMode::ConfirmQuit, _) => {
+                render_confirm_quit(area, buf, state.glyphs())
+            }
+            _ => {}
+        }
+
+        // Everything but the overlay itself
+        state
+            .debug_stats
+            .render_elapsed(start.elapsed().as_micros());
+        render_debug_overlay(rows_area, buf, &state.debug_stats, state.glyphs());
+    }
+}
+
+/// Breakdown of where the time went at the top right of the rows, when
+/// debugging
+fn render_debug_overlay(area: Rect, buf: &mut Buffer, debug_stats: &DebugStats, glyphs: &Glyphs) {
+    let lines = debug_stats.timings();
+    if lines.is_empty() {
+        return;
+    }
+    let label_width = lines
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<String> = lines
+        .iter()
+        .map(|(label, elapsed)| format!("{:<w$} {:>9.3}ms", label, elapsed, w = label_width))
+        .collect();
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) as u16 + 2;
+    let height = lines.len() as u16 + 2;
+    // Next to the scrollbar
+    if width + 1 > area.width || height > area.height {
+        return;
+    }
+    let popup_area = Rect::new(area.right() - width - 1, area.y, width, height);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Rgb(128, 128, 128)))
+        .title(" Timings ");
+    let lines: Vec<Spans> = lines.into_iter().map(Spans::from).collect();
+    Clear.render(popup_area, buf);
+    Paragraph::new(lines).block(block).render(popup_area, buf);
+    glyphs.replace_outline(popup_area, buf);
+}
+
+/// Actions matching the query of the command palette above the status line,
+/// with the first one highlighted since that is the one run by Enter
+fn render_palette(area: Rect, buf: &mut Buffer, query: &str, glyphs: &Glyphs) {
+    let actions = input::matching_actions(query);
+    let max_height = area.height.saturating_sub(4).min(12);
+    let height = min(max(actions.len(), 1) as u16 + 2, max_height);
+    let width = min(area.width, 50);
+    if height < 3 || width < 10 {
+        return;
+    }
+    let popup_area = Rect::new(area.x, area.bottom() - 2 - height, width, height);
+    let inner_width = width as usize - 2;
+
+    let lines: Vec<Spans> = actions
+        .iter()
+        .take(height as usize - 2)
+        .enumerate()
+        .map(|(i, action)| {
+            let padding = inner_width
+                .saturating_sub(action.name.len() + action.key.len())
+                .max(1);
+            let line = format!("{}{}{}", action.name, " ".repeat(padding), action.key);
+            let style = if i == 0 && !query.is_empty() {
+                Style::default()
+                    .fg(Color::Rgb(255, 200, 0))
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            Spans::from(Span::styled(line, style))
+        })
+        .collect();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Rgb(128, 128, 128)))
+        .title(if actions.is_empty() {
+            " No matching commands "
+        } else {
+            " Commands "
+        });
+    Clear.render(popup_area, buf);
+    Paragraph::new(lines).block(block).render(popup_area, buf);
+    glyphs.replace_outline(popup_area, buf);
+}
+
+/// Length of the value in graphemes, i.e. characters as they are shown,
+/// followed by the number of bytes if different, e.g. `4 chars, 5 bytes`
+fn cell_length(value: &str) -> String {
+    let num_chars = value.graphemes(true).count();
+    let unit = if num_chars == 1 { "char" } else { "chars" };
+    if value.len() == num_chars {
+        format!("{} {}", num_chars, unit)
+    } else {
+        format!("{} {}, {} bytes", num_chars, unit, value.len())
+    }
+}
+
+/// Question whether to quit in the middle of the screen
+/// Message in the middle of the rows when filtering is done without any
+/// matches, so that it doesn't look like the rows are still loading
+fn render_too_small(area: Rect, buf: &mut Buffer) {
+    let text_area = Rect {
+        y: area.y + area.height / 2,
+        height: 1,
+        ..area
+    };
+    Paragraph::new("Terminal too small")
+        .alignment(Alignment::Center)
+        .render(text_area, buf);
+}
+
+fn render_no_rows(area: Rect, buf: &mut Buffer, target: &find::Target) {
+    if area.height < 2 {
+        return;
+    }
+    let lines = vec![
+        Spans::from(format!("No rows match '{}'", target)),
+        Spans::from(Span::styled(
+            "Press & then Enter to clear the filter",
+            Style::default().fg(Color::Rgb(128, 128, 128)),
+        )),
+    ];
+    let text_area = Rect {
+        y: area.y + (area.height - 2) / 2,
+        height: 2,
+        ..area
+    };
+    Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .render(text_area, buf);
+}
+
+fn render_confirm_quit(area: Rect, buf: &mut Buffer, glyphs: &Glyphs) {
+    let text = "Quit csvlens? (y/n)";
+    let width = text.len() as u16 + 4;
+    let height = 3;
+    if width > area.width || height > area.height {
+        return;
+    }
+    let popup_area = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Rgb(128, 128, 128)));
+    Clear.render(popup_area, buf);
+    Paragraph::new(format!(" {}", text))
+        .block(block)
+        .render(popup_area, buf);
+    glyphs.replace_outline(popup_area, buf);
+}
+
+/// Start and length of a scrollbar thumb within a track of the given length,
+/// or None if everything is visible
+fn scrollbar_thumb(track: u16, offset: usize, visible: usize, total: usize) -> Option<(u16, u16)> {
+    if track == 0 || visible >= total {
+        return None;
+    }
+    let track = track as usize;
+    let len = (track * visible / total).clamp(1, track);
+    let max_offset = total - visible;
+    let start = (track - len) * min(offset, max_offset) / max_offset;
+    Some((start as u16, len as u16))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TruncateSide {
+    Left,
+    Right,
+}
+
+impl TruncateSide {
+    fn flipped(&self) -> Self {
+        match self {
+            TruncateSide::Left => TruncateSide::Right,
+            TruncateSide::Right => TruncateSide::Left,
+        }
+    }
+}
+
+/// How headers longer than their column are shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeaderMode {
+    /// Cut off at the end (or the start when truncating on the left)
+    Truncate,
+    /// Continued on a second line, so that columns only need to be about half
+    /// as wide for their headers
+    Wrap,
+    /// Shortened by dropping vowels, and then characters in the middle
+    Abbreviate,
+}
+
+impl HeaderMode {
+    pub fn num_lines(&self) -> u16 {
+        match self {
+            HeaderMode::Wrap => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl FromStr for HeaderMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "truncate" => Ok(HeaderMode::Truncate),
+            "wrap" => Ok(HeaderMode::Wrap),
+            "abbreviate" => Ok(HeaderMode::Abbreviate),
+            _ => anyhow::bail!(
+                "Unknown header mode: {} (should be one of truncate, wrap, abbreviate)",
+                s
+            ),
+        }
+    }
+}
+
+/// Graphemes of the header without vowels, except for the first letter of
+/// each word, e.g. `cstmr_Id` for `customer_Id`
+fn without_vowels(s: &str) -> Vec<&str> {
+    let mut prev_alphanumeric = false;
+    s.graphemes(true)
+        .filter(|g| {
+            let is_word_start = !prev_alphanumeric || g.chars().all(char::is_uppercase);
+            prev_alphanumeric = g.chars().all(char::is_alphanumeric);
+            let is_vowel = g.len() == 1 && "aeiouAEIOU".contains(*g);
+            is_word_start || !is_vowel
+        })
+        .collect()
+}
+
+/// Shorten the header to fit within the length if needed, first by dropping
+/// vowels and then by dropping characters in the middle
+fn abbreviate(s: &str, max_length: usize, ellipsis: &str) -> String {
+    if s.graphemes(true).count() <= max_length {
+        return s.to_owned();
+    }
+    let graphemes = without_vowels(s);
+    if graphemes.len() <= max_length {
+        return graphemes.concat();
+    }
+    if max_length == 0 {
+        return "".to_owned();
+    }
+    let num_head = max_length / 2;
+    let num_tail = max_length - 1 - num_head;
+    format!(
+        "{}{}{}",
+        graphemes[..num_head].concat(),
+        ellipsis,
+        graphemes[graphemes.len() - num_tail..].concat()
+    )
+}
+
+/// Split the header into two lines, after the last space, underscore or dash
+/// that fits on the first line if any. The second line may still be too long.
+fn wrap_header(s: &str, max_length: usize) -> (String, String) {
+    let graphemes: Vec<&str> = s.graphemes(true).collect();
+    if graphemes.len() <= max_length {
+        return (s.to_owned(), "".to_owned());
+    }
+    let split_at = graphemes[..max_length]
+        .iter()
+        .rposition(|g| is_word_separator(g))
+        .map_or(max_length, |i| i + 1);
+    let first = graphemes[..split_at].concat();
+    let second = graphemes[split_at..].concat();
+    (first.trim_end().to_owned(), second)
+}
+
+/// Narrowest width to fit the header in two lines wrapping between words,
+/// or else half the header
+fn wrapped_width(s: &str) -> usize {
+    let graphemes: Vec<&str> = s.graphemes(true).collect();
+    let len = graphemes.len();
+    (len.div_ceil(2)..len)
+        .find(|&width| {
+            graphemes[..width]
+                .iter()
+                .rposition(|g| is_word_separator(g))
+                .is_some_and(|i| len - (i + 1) <= width)
+        })
+        .unwrap_or(len.div_ceil(2))
+}
+
+fn is_word_separator(g: &str) -> bool {
+    matches!(g, " " | "_" | "-")
+}
+
+/// What the numbers next to the rows are
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GutterMode {
+    /// Row number in the file
+    #[default]
+    Original,
+    /// Position among the shown rows, which differs when filtering
+    Position,
+    /// Offset from the selected row
+    Relative,
+}
+
+impl GutterMode {
+    fn next(&self) -> Self {
+        match self {
+            GutterMode::Original => GutterMode::Position,
+            GutterMode::Position => GutterMode::Relative,
+            GutterMode::Relative => GutterMode::Original,
+        }
+    }
+}
+
+/// Symbols drawn by csvlens itself as opposed to the values shown, with an
+/// ASCII only set for terminals that can't show the others
+pub struct Glyphs {
+    pub vertical: &'static str,
+    pub horizontal: &'static str,
+    pub horizontal_down: &'static str,
+    pub horizontal_up: &'static str,
+    /// Where other lines meet, e.g. the corners of popups
+    pub corner: &'static str,
+    pub vertical_thumb: &'static str,
+    pub horizontal_thumb: &'static str,
+    /// End of truncated values
+    pub ellipsis: &'static str,
+    pub ascending: &'static str,
+    pub descending: &'static str,
+    pub cursor: &'static str,
+    /// Placeholder for empty values when showing empty cells
+    pub empty: &'static str,
+    /// Marker for each whitespace character around values
+    pub space: &'static str,
+    /// True and false values in boolean columns
+    pub check: &'static str,
+    pub cross: &'static str,
+}
+
+pub const UNICODE_GLYPHS: Glyphs = Glyphs {
+    vertical: line::VERTICAL,
+    horizontal: line::HORIZONTAL,
+    horizontal_down: line::HORIZONTAL_DOWN,
+    horizontal_up: line::HORIZONTAL_UP,
+    corner: line::CROSS,
+    vertical_thumb: "▐",
+    horizontal_thumb: "━",
+    ellipsis: "…",
+    ascending: "▲",
+    descending: "▼",
+    cursor: "█",
+    empty: "∅",
+    space: "·",
+    check: "✓",
+    cross: "✗",
+};
+
+pub const ASCII_GLYPHS: Glyphs = Glyphs {
+    vertical: "|",
+    horizontal: "-",
+    horizontal_down: "+",
+    horizontal_up: "+",
+    corner: "+",
+    vertical_thumb: "#",
+    horizontal_thumb: "=",
+    ellipsis: "~",
+    ascending: "^",
+    descending: "v",
+    cursor: "_",
+    empty: "-",
+    space: ".",
+    check: "Y",
+    cross: "N",
+};
+
+impl Glyphs {
+    fn arrow(&self, order: SortOrder) -> &'static str {
+        match order {
+            SortOrder::Ascending => self.ascending,
+            SortOrder::Descending => self.descending,
+        }
+    }
+
+    /// Redraw the lines of a block rendered in the area, since blocks always
+    /// draw them with box drawing characters
+    fn replace_outline(&self, area: Rect, buf: &mut Buffer) {
+        if self.vertical == line::VERTICAL || area.area() == 0 {
+            return;
+        }
+        let (right, bottom) = (area.right() - 1, area.bottom() - 1);
+        for y in area.top()..=bottom {
+            for x in area.left()..=right {
+                if x != area.left() && x != right && y != area.top() && y != bottom {
+                    continue;
+                }
+                let cell = buf.get_mut(x, y);
+                let symbol = match cell.symbol.as_str() {
+                    line::VERTICAL => self.vertical,
+                    line::HORIZONTAL => self.horizontal,
+                    line::TOP_LEFT
+                    | line::TOP_RIGHT
+                    | line::BOTTOM_LEFT
+                    | line::BOTTOM_RIGHT
+                    | line::CROSS => self.corner,
+                    line::HORIZONTAL_DOWN => self.horizontal_down,
+                    line::HORIZONTAL_UP => self.horizontal_up,
+                    _ => continue,
+                };
+                cell.set_symbol(symbol);
+            }
+        }
     }
 }
 
@@ -462,10 +1709,12 @@ pub struct FinderActiveState {
     find_complete: bool,
     total_found: u64,
     cursor_index: Option<u64>,
-    target: Regex,
+    target: find::Target,
     found_record: Option<find::FoundRecord>,
     selected_offset: Option<u64>,
     is_filter: bool,
+    // Index among the shown columns of the only column matched against
+    column: Option<usize>,
 }
 
 impl FinderActiveState {
@@ -475,9 +1724,32 @@ impl FinderActiveState {
             total_found: finder.count() as u64,
             cursor_index: finder.cursor().map(|x| x as u64),
             target: finder.target(),
-            found_record: finder.current(),
+            found_record: finder
+                .current()
+                .map(|r| r.map_column_indices(|i| rows_view.shown_column_index(i))),
             selected_offset: rows_view.selected_offset(),
             is_filter: rows_view.is_filter(),
+            column: finder
+                .target()
+                .column()
+                .and_then(|(i, _)| rows_view.shown_column_index(i)),
+        }
+    }
+
+    /// Whether filtering is done and nothing matched, leaving no rows
+    fn is_empty_filter(&self) -> bool {
+        self.is_filter && self.find_complete && self.total_found == 0
+    }
+
+    /// Whether the field in the shown column has matches to highlight, which
+    /// only applies when finding by regex
+    fn is_match(&self, column: usize, field: &str) -> bool {
+        if self.target.column().is_some() && self.column != Some(column) {
+            return false;
+        }
+        match self.target.regex() {
+            Some(target) => target.is_match(field),
+            None => false,
         }
     }
 
@@ -511,7 +1783,37 @@ impl FinderActiveState {
             line = format!("{}/{}{}", cursor_str, self.total_found, plus_marker,);
         }
         let action = if self.is_filter { "Filter" } else { "Find" };
-        format!("[{} \"{}\": {}]", action, self.target, line)
+        let target = match (self.target.column(), self.target.regex()) {
+            (Some((_, name)), Some(regex)) => format!("\"{}\" in {}", regex, name),
+            _ => format!("\"{}\"", self.target),
+        };
+        format!("[{} {}: {}]", action, target, line)
+    }
+}
+
+/// Number of rows matching, counted without finding or filtering
+pub struct CountState {
+    target: find::Target,
+    count: usize,
+    done: bool,
+}
+
+impl CountState {
+    pub fn from_finder(finder: &find::Finder) -> Self {
+        CountState {
+            target: finder.target(),
+            count: finder.count(),
+            done: finder.done(),
+        }
+    }
+
+    fn status_line(&self) -> String {
+        let plus_marker = if self.done { "" } else { "+" };
+        let rows = if self.count == 1 { "row" } else { "rows" };
+        format!(
+            "[Count \"{}\": {}{} {}]",
+            self.target, self.count, plus_marker, rows
+        )
     }
 }
 
@@ -521,10 +1823,13 @@ pub enum FilterColumnsState {
 }
 
 impl FilterColumnsState {
-    pub fn from_rows_view(rows_view: &view::RowsView) -> Self {
+    /// State of the columns filter, shown by the name of the column group if
+    /// given
+    pub fn from_rows_view(rows_view: &view::RowsView, group: Option<&str>) -> Self {
         if let Some(columns_filter) = rows_view.columns_filter() {
             Self::Enabled(FilterColumnsInfo {
                 pattern: columns_filter.pattern(),
+                group: group.map(|s| s.to_owned()),
                 shown: columns_filter.num_filtered(),
                 total: columns_filter.num_original(),
                 disabled_because_no_match: columns_filter.disabled_because_no_match(),
@@ -535,8 +1840,31 @@ impl FilterColumnsState {
     }
 }
 
+pub struct SortState {
+    /// Index of the sorted column among the shown columns, if shown
+    pub column: Option<usize>,
+    pub name: String,
+    pub order: SortOrder,
+    pub done: bool,
+}
+
+impl SortState {
+    fn status_line(&self, glyphs: &Glyphs) -> String {
+        if self.done {
+            format!(" [Sorted by {} {}]", self.name, glyphs.arrow(self.order))
+        } else {
+            format!(
+                " [Sorting by {} {}...]",
+                self.name,
+                glyphs.arrow(self.order)
+            )
+        }
+    }
+}
+
 pub struct FilterColumnsInfo {
-    pattern: Regex,
+    pattern: String,
+    group: Option<String>,
     shown: usize,
     total: usize,
     disabled_because_no_match: bool,
@@ -544,8 +1872,10 @@ pub struct FilterColumnsInfo {
 
 impl FilterColumnsInfo {
     fn status_line(&self) -> String {
-        let mut line;
-        line = format!("[Filter \"{}\": ", self.pattern);
+        let mut line = match &self.group {
+            Some(group) => format!("[Group {}: ", group),
+            None => format!("[Filter \"{}\": ", self.pattern),
+        };
         if self.disabled_because_no_match {
             line += "no match, showing all columns]";
         } else {
@@ -560,16 +1890,30 @@ struct BordersState {
     y_first_record: u16,
 }
 
+pub struct FileStats {
+    pub size: u64,
+    pub temp_size: Option<u64>,
+    pub line_ending: Option<csv::LineEnding>,
+}
+
 pub struct DebugStats {
     rows_view_elapsed: Option<f64>,
+    subset_elapsed: Option<f64>,
+    render_elapsed: Option<f64>,
     finder_elapsed: Option<f64>,
+    file_stats: Option<FileStats>,
+    rows_buffered: Option<(usize, usize)>,
 }
 
 impl DebugStats {
     pub fn new() -> Self {
         DebugStats {
             rows_view_elapsed: None,
+            subset_elapsed: None,
+            render_elapsed: None,
             finder_elapsed: None,
+            file_stats: None,
+            rows_buffered: None,
         }
     }
 
@@ -577,46 +1921,362 @@ impl DebugStats {
         self.rows_view_elapsed = elapsed.map(|e| e as f64 / 1000.0);
     }
 
+    pub fn subset_elapsed(&mut self, elapsed: Option<u128>) {
+        self.subset_elapsed = elapsed.map(|e| e as f64 / 1000.0);
+    }
+
+    fn render_elapsed(&mut self, elapsed: u128) {
+        self.render_elapsed = Some(elapsed as f64 / 1000.0);
+    }
+
     pub fn finder_elapsed(&mut self, elapsed: Option<u128>) {
         self.finder_elapsed = elapsed.map(|e| e as f64 / 1000.0);
     }
 
-    pub fn status_line(&self) -> Option<String> {
-        let mut line = "[".to_string();
-        if let Some(elapsed) = self.rows_view_elapsed {
-            line += format!("rows:{}ms", elapsed).as_str();
+    pub fn file_stats(&mut self, file_stats: FileStats) {
+        self.file_stats = Some(file_stats);
+    }
+
+    pub fn rows_buffered(&mut self, num_rows: usize, num_bytes: usize) {
+        self.rows_buffered = Some((num_rows, num_bytes));
+    }
+
+    /// Time taken by each step in milliseconds, only known when debugging
+    /// except for rendering
+    fn timings(&self) -> Vec<(&str, f64)> {
+        let rows_view_elapsed = match self.rows_view_elapsed {
+            Some(elapsed) => elapsed,
+            None => return vec![],
+        };
+        let mut timings = vec![("read rows", rows_view_elapsed)];
+        if let Some(elapsed) = self.subset_elapsed {
+            timings.push(("pick columns", elapsed));
+        }
+        if let Some(elapsed) = self.render_elapsed {
+            timings.push(("render", elapsed));
         }
         if let Some(elapsed) = self.finder_elapsed {
-            line += format!(" finder:{}ms", elapsed).as_str();
+            timings.push(("find/filter", elapsed));
+        }
+        timings
+    }
+
+    pub fn status_line(&self) -> Option<String> {
+        let mut parts = vec![];
+        if let Some(file_stats) = &self.file_stats {
+            parts.push(format!("file:{}", format_bytes(file_stats.size)));
+            if let Some(temp_size) = file_stats.temp_size {
+                parts.push(format!("tmp:{}", format_bytes(temp_size)));
+            }
+            if let Some(line_ending) = file_stats.line_ending {
+                parts.push(format!("eol:{}", line_ending));
+            }
+        }
+        if let Some((num_rows, num_bytes)) = self.rows_buffered {
+            parts.push(format!(
+                "mem:~{} ({} rows)",
+                format_bytes(num_bytes as u64),
+                num_rows
+            ));
         }
-        line += "]";
-        if line == "[]" {
+        if parts.is_empty() {
             None
         } else {
-            Some(line)
+            Some(format!("[{}]", parts.join(" ")))
+        }
+    }
+}
+
+/// Type of each column among the values shown, ignoring empty and null values
+fn column_types(rows: &[Row], num_columns: usize, null_tokens: &[String]) -> Vec<ColumnType> {
+    (0..num_columns)
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.fields.get(i))
+                .filter(|value| !null_tokens.contains(value))
+                .fold(ColumnType::Empty, |t, value| t.widen(ColumnType::of(value)))
+        })
+        .collect()
+}
+
+/// Numbers from this magnitude, or non-zero ones below the minimum, are shown
+/// in scientific notation if that is shorter
+const SCIENTIFIC_MAX: f64 = 1e10;
+const SCIENTIFIC_MIN: f64 = 1e-4;
+
+/// The number in scientific notation with up to 4 significant digits, e.g.
+/// 1.235e10 for 12345678901, if it's very large or small
+fn scientific(value: &str) -> Option<String> {
+    let value = value.trim();
+    let n: f64 = value.parse().ok()?;
+    let magnitude = n.abs();
+    if !n.is_finite() || (magnitude < SCIENTIFIC_MAX && (magnitude >= SCIENTIFIC_MIN || n == 0.0)) {
+        return None;
+    }
+    let formatted = format!("{:.3e}", n);
+    let (mantissa, exponent) = formatted.split_once('e')?;
+    let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+    let shown = format!("{}e{}", mantissa, exponent);
+    if shown.len() < value.len() {
+        Some(shown)
+    } else {
+        None
+    }
+}
+
+/// Spans of the value with leading and trailing whitespace shown as markers,
+/// so that e.g. values that look the same but don't match can be told apart
+fn whitespace_spans<'a>(value: &'a str, style: Style, marker: &str) -> Vec<Span<'a>> {
+    let marker_style = style
+        .fg(Color::Rgb(100, 100, 100))
+        .add_modifier(Modifier::DIM);
+    let content = value.trim_start();
+    let leading = value.len() - content.len();
+    let content = content.trim_end();
+    let trailing = value.len() - leading - content.len();
+    let marker = |s: &str| marker.repeat(s.chars().count());
+
+    let mut spans = vec![];
+    if leading > 0 {
+        spans.push(Span::styled(marker(&value[..leading]), marker_style));
+    }
+    spans.push(Span::styled(content, style));
+    if trailing > 0 {
+        spans.push(Span::styled(
+            marker(&value[value.len() - trailing..]),
+            marker_style,
+        ));
+    }
+    spans
+}
+
+fn format_bytes(n: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = n as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}{}", n, units[unit])
+    } else {
+        format!("{:.1}{}", value, units[unit])
+    }
+}
+
+/// Format with at most a few decimal places, without trailing zeros
+fn format_number(x: f64) -> String {
+    let s = format!("{:.4}", x);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_owned()
+    } else {
+        s.to_owned()
+    }
+}
+
+/// Find within the fields of the detail popup
+pub struct DetailFindState {
+    pub regex: Regex,
+    /// Index of the current match among all matches in the popup
+    pub cursor: usize,
+    num_found: usize,
+}
+
+impl DetailFindState {
+    pub fn new(regex: Regex) -> Self {
+        DetailFindState {
+            regex,
+            cursor: 0,
+            num_found: 0,
+        }
+    }
+
+    pub fn next(&mut self) {
+        if self.num_found > 0 {
+            self.cursor = (self.cursor + 1) % self.num_found;
+        }
+    }
+
+    pub fn prev(&mut self) {
+        if self.num_found > 0 {
+            self.cursor = (self.cursor + self.num_found - 1) % self.num_found;
+        }
+    }
+}
+
+/// Find among the names of the shown columns, scrolling to the matching
+/// columns instead of rows
+pub struct HeaderFindState {
+    pub regex: Regex,
+    /// Indices of the matching columns among the shown columns
+    pub columns: Vec<usize>,
+    /// Index of the current match in columns
+    pub cursor: Option<usize>,
+}
+
+impl HeaderFindState {
+    pub fn new(regex: Regex) -> Self {
+        HeaderFindState {
+            regex,
+            columns: vec![],
+            cursor: None,
         }
     }
+
+    /// Find again in the headers, e.g. after they changed, and move to the
+    /// next or previous match from the current column
+    pub fn step(
+        &mut self,
+        headers: &[String],
+        current_column: usize,
+        forward: bool,
+    ) -> Option<usize> {
+        self.columns = headers
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| self.regex.is_match(h))
+            .map(|(i, _)| i)
+            .collect();
+        let n = self.columns.len();
+        self.cursor = if n == 0 {
+            None
+        } else if forward {
+            // The first find starts from the current column
+            let is_after = |&c: &usize| match self.cursor {
+                Some(_) => c > current_column,
+                None => c >= current_column,
+            };
+            Some(self.columns.iter().position(is_after).unwrap_or(0))
+        } else {
+            let before = self.columns.iter().rposition(|&c| c < current_column);
+            Some(before.unwrap_or(n - 1))
+        };
+        self.cursor.map(|i| self.columns[i])
+    }
+
+    fn status_line(&self) -> String {
+        let line = match self.cursor {
+            Some(i) => format!("{}/{}", i + 1, self.columns.len()),
+            None => "Not found".to_owned(),
+        };
+        format!("[Find header \"{}\": {}]", self.regex, line)
+    }
 }
 
 pub struct CsvTableState {
     // TODO: types appropriate?
     pub rows_offset: u64,
     pub cols_offset: u64,
+    // Current column if not the leftmost one shown, e.g. once centered
+    centered_column: Option<u64>,
     pub num_cols_rendered: u64,
     pub more_cols_to_show: bool,
     filename: Option<String>,
     total_line_number: Option<usize>,
+    // Whether the total is estimated from the number of lines
+    total_line_number_approx: bool,
+    // Rows are still being read in the background, so there may be more
+    pub indexing: bool,
+    pub num_filtered_rows: Option<usize>,
     total_cols: usize,
     pub debug_stats: DebugStats,
     buffer_content: BufferState,
     pub finder_state: FinderState,
+    pub count_state: Option<CountState>,
     pub filter_columns_state: FilterColumnsState,
     borders_state: Option<BordersState>,
     // TODO: should probably be with BordersState
     col_ending_pos_x: u16,
     pub selected: Option<u64>,
+    pub selection_range: Option<(u64, u64)>,
+    pub selection_sum: Option<(f64, usize)>,
     pub user_error: Option<String>,
+    pub user_message: Option<String>,
+    pub tab_names: Vec<String>,
+    pub active_tab: usize,
+    pub marked_rows: HashSet<usize>,
+    pub num_marked: usize,
+    /// Rows shown that couldn't be read, e.g. after the file got shorter
+    pub num_skipped: u64,
+    /// Record numbers of the rows shown that are duplicates, when finding
+    /// duplicates
+    pub duplicate_rows: HashSet<usize>,
     pub column_widths: Option<Vec<u16>>,
+    /// Width of the longest value shown in each column, for autofit
+    content_widths: Vec<u16>,
+    /// Minimum width of the values in each column, to keep narrow columns
+    /// from looking cramped
+    pub min_column_width: u16,
+    pub show_empty_cells: bool,
+    /// Values that stand for a missing value, shown dimmed or as empty
+    pub null_tokens: Vec<String>,
+    // Mark leading and trailing whitespace in cells
+    pub show_whitespace: bool,
+    // Less space between columns and no line after the last one, to fit more
+    // columns
+    pub compact: bool,
+    // Lines between columns
+    pub grid: bool,
+    // Values that are the same as in the row above are dimmed and the ones
+    // that differ highlighted
+    pub show_changes: bool,
+    /// Column numbers above the headers
+    pub show_ruler: bool,
+    /// Leave out the status bar for another row, e.g. on short terminals
+    pub hide_status: bool,
+    /// Selected cell by its position among the rows shown in the status bar
+    pub show_cell_position: bool,
+    /// Length of the current cell in the status bar
+    pub show_cell_length: bool,
+    /// Check marks for the values of boolean columns
+    pub show_booleans: bool,
+    // Whether each column shown is boolean, as last rendered
+    boolean_columns: Vec<bool>,
+    /// Very large or small numbers in scientific notation
+    pub show_scientific: bool,
+    // Whether each column shown is numeric, as last rendered
+    scientific_columns: Vec<bool>,
+    // Only ASCII symbols are drawn, e.g. for terminals over serial lines
+    pub ascii: bool,
+    // Number of spaces shown for each tab in values, which are otherwise
+    // shown as a glyph
+    pub tab_width: Option<u16>,
+    pub show_detail: bool,
+    /// Record number of the row shown in full over as many lines as needed
+    /// while selected
+    pub expanded_row: Option<usize>,
+    expanded_extra_lines: u16,
+    pub detail_raw: bool,
+    /// Fields are shown as they are in the file, e.g. including quotes
+    pub raw_fields: bool,
+    pub sort_state: Option<SortState>,
+    pub header_mode: HeaderMode,
+    // Whether the header of the current column is not shown in full
+    current_header_shortened: bool,
+    /// Start and end x of the headers rendered and their column index, to
+    /// find the clicked column
+    header_columns: Vec<(u16, u16, usize)>,
+    // x of the lines between the columns rendered
+    column_separators: Vec<u16>,
+    y_header: u16,
+    pub detail_find: Option<DetailFindState>,
+    pub header_find: Option<HeaderFindState>,
+    /// Keep the first column in view besides the header when scrolling
+    pub freeze_panes: bool,
+    pub gutter_mode: GutterMode,
+    /// Number of the first row, e.g. 0 to count from 0
+    pub row_base: u64,
+    /// Count the header as the first row in the file, e.g. to match the line
+    /// numbers of an editor
+    pub count_header: bool,
+    pub truncate_side: TruncateSide,
+    truncate_side_overrides: HashMap<String, TruncateSide>,
+    /// Widths of the columns fitted to their values, by header
+    column_width_overrides: HashMap<String, u16>,
+    /// Columns aligned other than on the left, by header
+    column_alignments: HashMap<String, Alignment>,
     pub debug: String,
 }
 
@@ -625,30 +2285,285 @@ impl CsvTableState {
         Self {
             rows_offset: 0,
             cols_offset: 0,
+            centered_column: None,
             num_cols_rendered: 0,
             more_cols_to_show: true,
             filename,
             total_line_number: None,
+            total_line_number_approx: false,
+            indexing: false,
+            num_filtered_rows: None,
             total_cols,
             debug_stats: DebugStats::new(),
             buffer_content: BufferState::Disabled,
             finder_state: FinderState::FinderInactive,
+            count_state: None,
             filter_columns_state: FilterColumnsState::Disabled,
             borders_state: None,
             col_ending_pos_x: 0,
             selected: None,
+            selection_range: None,
+            selection_sum: None,
             user_error: None,
+            user_message: None,
+            tab_names: vec![],
+            active_tab: 0,
+            marked_rows: HashSet::new(),
+            duplicate_rows: HashSet::new(),
+            num_marked: 0,
+            num_skipped: 0,
             column_widths: None,
+            content_widths: vec![],
+            min_column_width: 0,
+            show_empty_cells: false,
+            null_tokens: vec![],
+            show_whitespace: false,
+            compact: false,
+            grid: false,
+            show_changes: false,
+            show_ruler: false,
+            hide_status: false,
+            show_cell_position: false,
+            show_cell_length: false,
+            show_booleans: false,
+            boolean_columns: vec![],
+            show_scientific: false,
+            scientific_columns: vec![],
+            ascii: false,
+            tab_width: None,
+            show_detail: false,
+            expanded_row: None,
+            expanded_extra_lines: 0,
+            detail_raw: false,
+            raw_fields: false,
+            sort_state: None,
+            header_mode: HeaderMode::Truncate,
+            current_header_shortened: false,
+            header_columns: vec![],
+            column_separators: vec![],
+            y_header: 0,
+            detail_find: None,
+            header_find: None,
+            freeze_panes: false,
+            gutter_mode: GutterMode::Original,
+            row_base: 1,
+            count_header: false,
+            truncate_side: TruncateSide::Right,
+            truncate_side_overrides: HashMap::new(),
+            column_width_overrides: HashMap::new(),
+            column_alignments: HashMap::new(),
             debug: "".into(),
         }
     }
 
+    /// Name of the file as given, or None for stdin
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    /// Number of the first row in the file as shown
+    pub fn first_row_number(&self) -> u64 {
+        self.row_base + self.count_header as u64
+    }
+
+    /// Number shown for the record, where the first record is 1
+    fn row_number(&self, record_num: usize) -> u64 {
+        record_num as u64 - 1 + self.first_row_number()
+    }
+
+    /// Values of the row wrapped to the widths the columns are shown with, as
+    /// the fields of each line, if it's the expanded row and takes more than
+    /// one line. Columns are laid out from x in the same way as render_row.
+    fn wrapped_fields(
+        &self,
+        row: &Row,
+        column_widths: &[u16],
+        area_width: u16,
+        x: u16,
+    ) -> Option<Vec<Vec<String>>> {
+        if self.expanded_row != Some(row.record_num) {
+            return None;
+        }
+        let cols_offset = self.cols_offset as usize;
+        let mut remaining_width = area_width.saturating_sub(x);
+        let mut columns: Vec<Vec<String>> = vec![];
+        for (i, (value, &width)) in row.fields.iter().zip(column_widths).enumerate() {
+            let is_shown =
+                remaining_width > 0 && (i >= cols_offset || (self.freeze_panes && i == 0));
+            if !is_shown {
+                columns.push(vec![value.clone()]);
+                continue;
+            }
+            let effective_width = min(remaining_width, width);
+            let max_length = effective_width.saturating_sub(self.column_padding()) as usize;
+            columns.push(wrap_value(value, max_length, self.tab_width));
+            remaining_width = remaining_width.saturating_sub(width);
+        }
+        let num_lines = columns.iter().map(|lines| lines.len()).max().unwrap_or(1);
+        if num_lines <= 1 {
+            return None;
+        }
+        let lines = (0..num_lines)
+            .map(|i| {
+                columns
+                    .iter()
+                    .map(|lines| lines.get(i).cloned().unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        Some(lines)
+    }
+
+    /// Lines taken by the expanded row after the first, as last rendered
+    pub fn expanded_extra_lines(&self) -> u16 {
+        if self.expanded_row.is_some() {
+            self.expanded_extra_lines
+        } else {
+            0
+        }
+    }
+
+    /// Lines taken by the status bar and the separator above it. Without the
+    /// status bar, errors, messages and prompts still show it until cleared.
+    pub fn status_height(&self) -> u16 {
+        let has_feedback = self.user_error.is_some()
+            || self.user_message.is_some()
+            || !matches!(self.buffer_content, BufferState::Disabled);
+        if self.hide_status && !has_feedback {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Lines taken by the header, including the column numbers if shown
+    pub fn header_lines(&self) -> u16 {
+        self.header_mode.num_lines() + self.show_ruler as u16
+    }
+
+    /// Index of the column whose header is at the position, as last rendered
+    pub fn header_column_at(&self, x: u16, y: u16) -> Option<usize> {
+        if y < self.y_header || y >= self.y_header + self.header_mode.num_lines() {
+            return None;
+        }
+        self.header_columns
+            .iter()
+            .find(|(start, end, _)| x >= *start && x < *end)
+            .map(|(_, _, col_index)| *col_index)
+    }
+
+    /// Order of the column if it is the sorted one and done sorting
+    fn sorted_column(&self, col_index: usize) -> Option<SortOrder> {
+        match &self.sort_state {
+            Some(sort_state) if sort_state.done && sort_state.column == Some(col_index) => {
+                Some(sort_state.order)
+            }
+            _ => None,
+        }
+    }
+
     pub fn set_rows_offset(&mut self, offset: u64) {
         self.rows_offset = offset;
     }
 
     pub fn set_cols_offset(&mut self, offset: u64) {
-        self.cols_offset = offset;
+        self.centered_column = None;
+        // The first column is pinned, so scrolling starts from the second,
+        // while the first can still be the current column
+        if self.freeze_panes && self.total_cols > 1 {
+            self.cols_offset = max(offset, 1);
+            if offset == 0 {
+                self.centered_column = Some(0);
+            }
+        } else {
+            self.cols_offset = offset;
+        }
+    }
+
+    /// Column acted on by e.g. sorting or copying its name, which is the
+    /// leftmost one shown unless centered
+    pub fn current_column(&self) -> u64 {
+        self.centered_column.unwrap_or(self.cols_offset)
+    }
+
+    /// Scroll so that the current column is in the middle of the given width,
+    /// keeping it the current column
+    pub fn center_current_column(&mut self, width: u16) {
+        let column = self.current_column();
+        let column_widths = match &self.column_widths {
+            Some(column_widths) => column_widths,
+            None => return,
+        };
+        let width_of = |i: u64| column_widths.get(i as usize).copied().unwrap_or(0);
+        let pinned = self.freeze_panes && self.total_cols > 1;
+        let mut half = width / 2;
+        if pinned {
+            half = half.saturating_sub(width_of(0));
+        }
+        let first = if pinned { 1 } else { 0 };
+        let mut offset = column;
+        let mut total = width_of(column) / 2;
+        while offset > first && total + width_of(offset - 1) <= half {
+            offset -= 1;
+            total += width_of(offset);
+        }
+        self.set_cols_offset(offset);
+        if offset != column {
+            self.centered_column = Some(column);
+        }
+    }
+
+    /// Whether the current column was shown in full the last time the table
+    /// was rendered
+    pub fn is_current_column_visible(&self) -> bool {
+        let column = self.current_column();
+        if self.freeze_panes && column == 0 {
+            return true;
+        }
+        // The first column is shown in addition when freezing panes
+        let mut num_shown = self.num_cols_rendered;
+        if self.freeze_panes && self.cols_offset > 0 {
+            num_shown = num_shown.saturating_sub(1);
+        }
+        // The last column shown is cut off if there are more
+        if self.more_cols_to_show {
+            num_shown = num_shown.saturating_sub(1);
+        }
+        column >= self.cols_offset && column < self.cols_offset + num_shown.max(1)
+    }
+
+    pub fn glyphs(&self) -> &'static Glyphs {
+        if self.ascii {
+            &ASCII_GLYPHS
+        } else {
+            &UNICODE_GLYPHS
+        }
+    }
+
+    /// Space reserved after the content of each column, including the line
+    /// between columns if shown
+    pub fn column_padding(&self) -> u16 {
+        match (self.compact, self.grid) {
+            (false, false) => 4,
+            (false, true) => 5,
+            (true, false) => 1,
+            (true, true) => 3,
+        }
+    }
+
+    pub fn cycle_gutter_mode(&mut self) {
+        self.gutter_mode = self.gutter_mode.next();
+    }
+
+    pub fn toggle_freeze_panes(&mut self) {
+        self.freeze_panes = !self.freeze_panes;
+        // Stay on the first column if it is the current one
+        let offset = if self.current_column() == 0 {
+            0
+        } else {
+            self.cols_offset
+        };
+        self.set_cols_offset(offset);
     }
 
     fn set_more_cols_to_show(&mut self, value: bool) {
@@ -663,8 +2578,9 @@ impl CsvTableState {
         self.num_cols_rendered = n;
     }
 
-    pub fn set_total_line_number(&mut self, n: usize) {
+    pub fn set_total_line_number(&mut self, n: usize, is_approx: bool) {
         self.total_line_number = Some(n);
+        self.total_line_number_approx = is_approx;
     }
 
     pub fn set_total_cols(&mut self, n: usize) {
@@ -678,4 +2594,62 @@ impl CsvTableState {
     pub fn reset_buffer(&mut self) {
         self.buffer_content = BufferState::Disabled;
     }
+
+    fn truncate_side_for(&self, header: &str) -> TruncateSide {
+        *self
+            .truncate_side_overrides
+            .get(header)
+            .unwrap_or(&self.truncate_side)
+    }
+
+    pub fn toggle_truncate_side(&mut self, header: &str) {
+        let flipped = self.truncate_side_for(header).flipped();
+        self.truncate_side_overrides
+            .insert(header.to_owned(), flipped);
+    }
+
+    /// Widen or narrow the column to the longest value on screen, keeping the
+    /// width when scrolling away
+    pub fn autofit_column(&mut self, column: usize, header: &str) {
+        if let Some(&width) = self.content_widths.get(column) {
+            self.column_width_overrides.insert(header.to_owned(), width);
+        }
+    }
+
+    pub fn autofit_columns(&mut self, headers: &[String]) {
+        for (i, header) in headers.iter().enumerate() {
+            self.autofit_column(i, header);
+        }
+    }
+
+    pub fn reset_column_widths(&mut self) {
+        self.column_width_overrides.clear();
+    }
+
+    fn alignment_for(&self, header: &str) -> Alignment {
+        *self
+            .column_alignments
+            .get(header)
+            .unwrap_or(&Alignment::Left)
+    }
+
+    /// Align the column on the left, right or center in turn, returning the
+    /// new alignment
+    pub fn cycle_alignment(&mut self, header: &str) -> Alignment {
+        let alignment = match self.alignment_for(header) {
+            Alignment::Left => Alignment::Right,
+            Alignment::Right => Alignment::Center,
+            Alignment::Center => Alignment::Left,
+        };
+        if alignment == Alignment::Left {
+            self.column_alignments.remove(header);
+        } else {
+            self.column_alignments.insert(header.to_owned(), alignment);
+        }
+        alignment
+    }
+
+    pub fn reset_column_alignments(&mut self) {
+        self.column_alignments.clear();
+    }
 }
@@ -6,10 +6,13 @@ use std::sync::{
 use std::thread;
 use std::time::Duration;
 
-use crossterm::event::{read, Event, KeyCode, KeyEvent};
+use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, MouseEvent};
+
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 pub enum CsvlensEvent<I> {
     Input(I),
+    Mouse(MouseEvent),
     Tick,
 }
 
@@ -19,6 +22,8 @@ pub struct CsvlensEvents {
     rx: mpsc::Receiver<CsvlensEvent<KeyEvent>>,
     input_handle: thread::JoinHandle<()>,
     ignore_exit_key: Arc<AtomicBool>,
+    // Stop reading input, e.g. while another program reads the terminal
+    paused: Arc<AtomicBool>,
     tick_handle: thread::JoinHandle<()>,
 }
 
@@ -45,16 +50,34 @@ impl CsvlensEvents {
     pub fn with_config(config: Config) -> CsvlensEvents {
         let (tx, rx) = mpsc::channel();
         let ignore_exit_key = Arc::new(AtomicBool::new(true));
+        let paused = Arc::new(AtomicBool::new(false));
         let input_handle = {
             let tx = tx.clone();
             // TODO: not used?
             let _ignore_exit_key = ignore_exit_key.clone();
+            let paused = paused.clone();
             thread::spawn(move || loop {
-                if let Event::Key(event) = read().unwrap() {
-                    if let Err(err) = tx.send(CsvlensEvent::Input(event)) {
-                        eprintln!("{}", err);
-                        return;
-                    }
+                // Polled with a timeout so that pausing doesn't wait for input
+                if paused.load(Ordering::Relaxed) {
+                    thread::sleep(PAUSE_POLL_INTERVAL);
+                    continue;
+                }
+                if !poll(PAUSE_POLL_INTERVAL).unwrap() {
+                    continue;
+                }
+                // Paused while polling, so the input is left to the process
+                // taking over the terminal
+                if paused.load(Ordering::Relaxed) {
+                    continue;
+                }
+                let event = match read().unwrap() {
+                    Event::Key(event) => CsvlensEvent::Input(event),
+                    Event::Mouse(event) => CsvlensEvent::Mouse(event),
+                    _ => continue,
+                };
+                if let Err(err) = tx.send(event) {
+                    eprintln!("{}", err);
+                    return;
                 }
             })
         };
@@ -69,6 +92,7 @@ impl CsvlensEvents {
         CsvlensEvents {
             rx,
             ignore_exit_key,
+            paused,
             input_handle,
             tick_handle,
         }
@@ -82,6 +106,10 @@ impl CsvlensEvents {
         self.ignore_exit_key.store(true, Ordering::Relaxed);
     }
 
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
     pub fn enable_exit_key(&mut self) {
         self.ignore_exit_key.store(false, Ordering::Relaxed);
     }
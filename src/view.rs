@@ -1,10 +1,15 @@
 use crate::csv::{CsvLensReader, Row};
 use crate::find;
 use crate::input::Control;
+use crate::sort::SortedRows;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use csv::Position;
 use regex::Regex;
-use std::cmp::min;
+use std::cmp::{max, min};
+use std::collections::{BTreeSet, HashMap};
+use std::mem::size_of;
+use std::sync::Arc;
 use std::time::Instant;
 
 struct RowsFilter {
@@ -23,6 +28,7 @@ impl RowsFilter {
 #[derive(Debug)]
 pub struct ColumnsFilter {
     pattern: Regex,
+    exclude: bool,
     indices: Vec<usize>,
     filtered_headers: Vec<String>,
     num_columns_before_filter: usize,
@@ -30,11 +36,22 @@ pub struct ColumnsFilter {
 }
 
 impl ColumnsFilter {
-    fn new(pattern: Regex, headers: &[String]) -> Self {
+    /// Show columns with headers matching the regex, or hide them instead if
+    /// the regex is prefixed with !
+    pub fn parse(s: &str, headers: &[String]) -> Result<Self> {
+        let (exclude, re) = match s.strip_prefix('!') {
+            Some(re) => (true, re),
+            None => (false, s),
+        };
+        let pattern = Regex::new(re).map_err(|_| anyhow!("Invalid regex: {}", s))?;
+        Ok(Self::new(pattern, exclude, headers))
+    }
+
+    fn new(pattern: Regex, exclude: bool, headers: &[String]) -> Self {
         let mut indices = vec![];
         let mut filtered_headers: Vec<String> = vec![];
         for (i, header) in headers.iter().enumerate() {
-            if pattern.is_match(header) {
+            if pattern.is_match(header) != exclude {
                 indices.push(i);
                 filtered_headers.push(header.clone());
             }
@@ -49,6 +66,7 @@ impl ColumnsFilter {
         }
         Self {
             pattern,
+            exclude,
             indices,
             filtered_headers,
             num_columns_before_filter: headers.len(),
@@ -60,12 +78,17 @@ impl ColumnsFilter {
         &self.filtered_headers
     }
 
-    fn indices(&self) -> &Vec<usize> {
+    pub fn indices(&self) -> &Vec<usize> {
         &self.indices
     }
 
-    pub fn pattern(&self) -> Regex {
-        self.pattern.to_owned()
+    /// Pattern that parses to the filter again
+    pub fn pattern(&self) -> String {
+        if self.exclude {
+            format!("!{}", self.pattern)
+        } else {
+            self.pattern.to_string()
+        }
     }
 
     pub fn num_filtered(&self) -> usize {
@@ -81,6 +104,65 @@ impl ColumnsFilter {
     }
 }
 
+/// Values of a column with the matches of a regex replaced, shown as another
+/// column after the shown ones without changing the file
+#[derive(Debug)]
+pub struct ReplacePreview {
+    column: usize,
+    header: String,
+    regex: Regex,
+    replacement: String,
+}
+
+impl ReplacePreview {
+    /// Replace in the column, given by its index among all columns, as typed
+    /// in `regex/replacement` with `\/` for a `/` in the regex. The
+    /// replacement can refer to groups, e.g. `$1`.
+    pub fn parse(s: &str, column: usize, headers: &[String]) -> Result<Self> {
+        let (re, replacement) = split_unescaped_slash(s)
+            .ok_or_else(|| anyhow!("Should be regex/replacement: {}", s))?;
+        let regex = Regex::new(&re).map_err(|_| anyhow!("Invalid regex: {}", re))?;
+        let header = headers
+            .get(column)
+            .ok_or_else(|| anyhow!("No column to replace in"))?;
+        Ok(ReplacePreview {
+            column,
+            header: format!("{} (replaced)", header),
+            regex,
+            replacement: replacement.to_owned(),
+        })
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    fn replace(&self, row: &Row) -> String {
+        let value = row.fields.get(self.column).map_or("", |s| s.as_str());
+        self.regex
+            .replace_all(value, self.replacement.as_str())
+            .into_owned()
+    }
+}
+
+/// Split at the first `/` not escaped by a backslash, unescaping `\/` before
+/// it
+fn split_unescaped_slash(s: &str) -> Option<(String, &str)> {
+    let mut before = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '/' => return Some((before, &s[i + 1..])),
+            '\\' if s[i + 1..].starts_with('/') => {
+                before.push('/');
+                chars.next();
+            }
+            c => before.push(c),
+        }
+    }
+    None
+}
+
 pub struct RowsView {
     reader: CsvLensReader,
     rows: Vec<Row>,
@@ -90,6 +172,29 @@ pub struct RowsView {
     columns_filter: Option<ColumnsFilter>,
     selected: Option<u64>,
     elapsed: Option<u128>,
+    subset_elapsed: Option<u128>,
+    scrolloff: u64,
+    page_overlap: u64,
+    marked: BTreeSet<u64>,
+    selection_anchor: Option<u64>,
+    // Percentage scrolled to using the approximate total, to be scrolled to
+    // again once the exact total is known
+    pending_percent: Option<u8>,
+    // Position restored from the last time, to be scrolled to once the total
+    // is known so that it can be kept within the rows
+    pending_position: Option<(u64, u64)>,
+    // Show fields as they are in the file instead of the parsed values
+    raw_fields: bool,
+    // Order of the rows when sorted by a column, only used when not filtered
+    sorted: Option<Arc<SortedRows>>,
+    // Pattern of the last filter, kept after clearing it to apply it again
+    last_filter: Option<String>,
+    replace_preview: Option<ReplacePreview>,
+    // Shown headers followed by that of the replace preview if any
+    headers_with_preview: Vec<String>,
+    // Indices of rows that couldn't be read when last getting the rows,
+    // e.g. stale ones after the file got shorter
+    num_skipped: u64,
 }
 
 impl RowsView {
@@ -105,18 +210,38 @@ impl RowsView {
             columns_filter: None,
             selected: Some(0),
             elapsed: None,
+            subset_elapsed: None,
+            scrolloff: 0,
+            page_overlap: 0,
+            marked: BTreeSet::new(),
+            selection_anchor: None,
+            pending_percent: None,
+            pending_position: None,
+            raw_fields: false,
+            sorted: None,
+            last_filter: None,
+            replace_preview: None,
+            headers_with_preview: vec![],
+            num_skipped: 0,
         };
         Ok(view)
     }
 
     pub fn headers(&self) -> &Vec<String> {
-        if let Some(columns_filter) = &self.columns_filter {
+        if self.replace_preview.is_some() {
+            &self.headers_with_preview
+        } else if let Some(columns_filter) = &self.columns_filter {
             columns_filter.filtered_headers()
         } else {
             &self.reader.headers
         }
     }
 
+    /// Headers of all columns, regardless of any columns filter
+    pub fn all_headers(&self) -> &Vec<String> {
+        &self.reader.headers
+    }
+
     pub fn rows(&self) -> &Vec<Row> {
         &self.rows
     }
@@ -125,15 +250,98 @@ impl RowsView {
         self.num_rows
     }
 
+    /// Change the number of rows shown, e.g. when the terminal is resized. The
+    /// selected row stays selected, scrolling down if it would no longer be
+    /// shown.
     pub fn set_num_rows(&mut self, num_rows: u64) -> Result<()> {
         if num_rows == self.num_rows {
             return Ok(());
         }
+        let selected_offset = self.selected_offset();
         self.num_rows = num_rows;
+        if let Some(offset) = selected_offset {
+            if num_rows > 0 && offset >= self.rows_from + num_rows {
+                self.rows_from = offset + 1 - num_rows;
+            }
+        }
         self.do_get_rows()?;
+        if let Some(offset) = selected_offset {
+            self.set_selected(offset.saturating_sub(self.rows_from));
+        }
         Ok(())
     }
 
+    pub fn set_scrolloff(&mut self, scrolloff: u64) {
+        self.scrolloff = scrolloff;
+    }
+
+    pub fn set_page_overlap(&mut self, page_overlap: u64) {
+        self.page_overlap = page_overlap;
+    }
+
+    /// Read the rows again with the reader of the changed file, which has the
+    /// same rows as before
+    pub fn reload(&mut self, reader: CsvLensReader) -> Result<()> {
+        self.reader = reader;
+        self.do_get_rows()
+    }
+
+    /// Show the rows of another reader of the file, e.g. with another
+    /// delimiter, from the top. Anything depending on the columns or rows
+    /// read before, like filters and marks, is reset.
+    pub fn reopen(&mut self, reader: CsvLensReader) -> Result<()> {
+        self.reader = reader;
+        self.filter = None;
+        self.columns_filter = None;
+        self.sorted = None;
+        self.replace_preview = None;
+        self.update_headers_with_preview();
+        self.marked.clear();
+        self.selection_anchor = None;
+        self.pending_percent = None;
+        self.pending_position = None;
+        self.rows_from = 0;
+        self.selected = Some(0);
+        self.do_get_rows()
+    }
+
+    pub fn raw_fields(&self) -> bool {
+        self.raw_fields
+    }
+
+    pub fn set_raw_fields(&mut self, raw_fields: bool) -> Result<()> {
+        self.raw_fields = raw_fields;
+        self.do_get_rows()
+    }
+
+    pub fn sorted(&self) -> Option<&Arc<SortedRows>> {
+        self.sorted.as_ref()
+    }
+
+    /// Show the rows in the sorted order, or in file order if None
+    pub fn set_sorted(&mut self, sorted: Option<Arc<SortedRows>>) -> Result<()> {
+        self.sorted = sorted;
+        self.clear_selection_range();
+        self.do_get_rows()
+    }
+
+    /// Position of the row index among the rows when not filtered, which is
+    /// the row index itself unless sorted
+    pub fn position_of(&self, row_index: u64) -> u64 {
+        match &self.sorted {
+            Some(sorted) => sorted.position(row_index),
+            None => row_index,
+        }
+    }
+
+    /// Row indices at the offsets (inclusive) when not filtered
+    pub fn row_indices_between(&self, first: u64, last: u64) -> Vec<u64> {
+        match &self.sorted {
+            Some(sorted) => sorted.row_indices(first, last - first + 1).to_vec(),
+            None => (first..=last).collect(),
+        }
+    }
+
     pub fn set_filter(&mut self, finder: &find::Finder) -> Result<()> {
         let filter = RowsFilter::new(finder, self.rows_from, self.num_rows);
         // only need to reload rows if the currently shown indices changed
@@ -153,15 +361,50 @@ impl RowsView {
         }
     }
 
+    pub fn last_filter(&self) -> Option<&str> {
+        self.last_filter.as_deref()
+    }
+
+    pub fn set_last_filter(&mut self, pattern: &str) {
+        self.last_filter = Some(pattern.to_owned());
+    }
+
     pub fn is_filter(&self) -> bool {
         self.filter.is_some()
     }
 
+    /// Number of rows that couldn't be read when last getting the rows
+    pub fn num_skipped(&self) -> u64 {
+        self.num_skipped
+    }
+
+    /// Number of rows matching the filter if any
+    pub fn num_filtered_rows(&self) -> Option<usize> {
+        self.filter.as_ref().map(|f| f.total)
+    }
+
     pub fn reset_filter(&mut self) -> Result<()> {
         if !self.is_filter() {
             return Ok(());
         }
+        // Keep the selected record selected and in view, in the same position
+        // within the window if possible
+        let selected_record = self
+            .selected
+            .and_then(|i| self.rows.get(i as usize))
+            .map(|row| row.record_num.saturating_sub(1) as u64);
         self.filter = None;
+        self.clear_selection_range();
+        if let (Some(record), Some(i)) = (selected_record, self.selected) {
+            // rows_from is an offset into the sorted rows when sorted
+            let position = self.position_of(record);
+            let mut rows_from = position.saturating_sub(i);
+            if let Some(n) = self.bottom_rows_from() {
+                rows_from = min(rows_from, n);
+            }
+            self.rows_from = rows_from;
+            self.selected = Some(position - rows_from);
+        }
         self.do_get_rows()
     }
 
@@ -169,16 +412,78 @@ impl RowsView {
         self.columns_filter.as_ref()
     }
 
-    pub fn set_columns_filter(&mut self, target: Regex) -> Result<()> {
-        self.columns_filter = Some(ColumnsFilter::new(target, &self.reader.headers));
+    /// Index of the shown column among all columns
+    pub fn original_column_index(&self, i: usize) -> Option<usize> {
+        match &self.columns_filter {
+            Some(columns_filter) => columns_filter.indices().get(i).copied(),
+            None => Some(i).filter(|&i| i < self.reader.headers.len()),
+        }
+    }
+
+    /// Index among the shown columns of the given column, if it is shown
+    pub fn shown_column_index(&self, original: usize) -> Option<usize> {
+        match &self.columns_filter {
+            Some(columns_filter) => columns_filter.indices().iter().position(|&i| i == original),
+            None => Some(original),
+        }
+    }
+
+    /// Index of the shown column that is, or else follows, the given one among
+    /// all columns, or the last shown column if none follows
+    pub fn column_index_from_original(&self, original: usize) -> usize {
+        match &self.columns_filter {
+            Some(columns_filter) => {
+                let indices = columns_filter.indices();
+                let i = indices.partition_point(|&x| x < original);
+                min(i, indices.len().saturating_sub(1))
+            }
+            None => min(original, self.reader.headers.len().saturating_sub(1)),
+        }
+    }
+
+    pub fn set_columns_filter(&mut self, columns_filter: ColumnsFilter) -> Result<()> {
+        self.columns_filter = Some(columns_filter);
+        self.update_headers_with_preview();
         self.do_get_rows()
     }
 
     pub fn reset_columns_filter(&mut self) -> Result<()> {
         self.columns_filter = None;
+        self.update_headers_with_preview();
+        self.do_get_rows()
+    }
+
+    pub fn replace_preview(&self) -> Option<&ReplacePreview> {
+        self.replace_preview.as_ref()
+    }
+
+    /// Whether the shown column is that of the replace preview
+    pub fn is_replace_preview_column(&self, i: usize) -> bool {
+        self.replace_preview.is_some() && i + 1 == self.headers_with_preview.len()
+    }
+
+    /// Show the values with the replacement as the last column, or no longer
+    /// if None
+    pub fn set_replace_preview(&mut self, replace_preview: Option<ReplacePreview>) -> Result<()> {
+        self.replace_preview = replace_preview;
+        self.update_headers_with_preview();
         self.do_get_rows()
     }
 
+    fn update_headers_with_preview(&mut self) {
+        self.headers_with_preview = match &self.replace_preview {
+            Some(preview) => {
+                let mut headers = match &self.columns_filter {
+                    Some(columns_filter) => columns_filter.filtered_headers().clone(),
+                    None => self.reader.headers.clone(),
+                };
+                headers.push(preview.header.clone());
+                headers
+            }
+            None => vec![],
+        };
+    }
+
     pub fn rows_from(&self) -> u64 {
         self.rows_from
     }
@@ -202,7 +507,6 @@ impl RowsView {
         self.selected = Some(selected);
     }
 
-    #[allow(dead_code)]
     pub fn reset_selected(&mut self) {
         self.selected = None;
     }
@@ -219,8 +523,15 @@ impl RowsView {
         }
     }
 
+    /// Select the first row shown, or the first one after the scrolloff
+    /// margin if there are rows above
     pub fn select_top(&mut self) {
-        self.set_selected(0);
+        let margin = if self.rows_from > 0 {
+            self.effective_scrolloff()
+        } else {
+            0
+        };
+        self.set_selected(margin);
     }
 
     pub fn select_bottom(&mut self) {
@@ -231,14 +542,117 @@ impl RowsView {
         self.selected
     }
 
+    /// Index of the first row shown and the selected row within the shown
+    /// rows, as if not filtered, i.e. keeping the selected row selected, in
+    /// the sorted order if sorted
+    pub fn position(&self) -> (u64, u64) {
+        let selected = self.selected.unwrap_or(0);
+        match self.rows.get(selected as usize) {
+            Some(row) => {
+                let position = self.position_of(row.record_num.saturating_sub(1) as u64);
+                let selected = min(selected, position);
+                (position - selected, selected)
+            }
+            None => (self.rows_from, selected),
+        }
+    }
+
+    /// Scroll to the position once the total number of rows is known
+    pub fn restore_position(&mut self, rows_from: u64, selected: u64) {
+        self.pending_position = Some((rows_from, selected));
+    }
+
     pub fn selected_offset(&self) -> Option<u64> {
         self.selected.map(|x| x.saturating_add(self.rows_from))
     }
 
+    /// Mark or unmark the selected row. Marks are kept by record index so they
+    /// survive scrolling and filtering.
+    pub fn toggle_mark_selected(&mut self) {
+        if let Some(row) = self.selected.and_then(|i| self.rows.get(i as usize)) {
+            let row_index = row.record_num.saturating_sub(1) as u64;
+            if !self.marked.remove(&row_index) {
+                self.marked.insert(row_index);
+            }
+        }
+    }
+
+    pub fn marked(&self) -> &BTreeSet<u64> {
+        &self.marked
+    }
+
+    pub fn clear_marked(&mut self) {
+        self.marked.clear();
+    }
+
+    pub fn mark_all(&mut self, row_indices: &[u64]) {
+        self.marked.extend(row_indices);
+    }
+
+    /// Mark the given rows that aren't marked and unmark those that are.
+    /// Marks of other rows, e.g. those not matching the filter, are kept.
+    pub fn invert_marks(&mut self, row_indices: &[u64]) {
+        for i in row_indices {
+            if !self.marked.remove(i) {
+                self.marked.insert(*i);
+            }
+        }
+    }
+
+    pub fn get_marked_rows(&mut self) -> Result<Vec<Row>> {
+        let indices: Vec<u64> = self.marked.iter().cloned().collect();
+        self.get_rows_for_indices(&indices)
+    }
+
+    /// Get rows by record index in the given order, with the columns as shown,
+    /// i.e. with the columns filter and the replace preview applied if any
+    pub fn get_rows_for_indices(&mut self, indices: &[u64]) -> Result<Vec<Row>> {
+        let rows = self.reader.get_rows_for_indices(indices)?;
+        let rows = Self::in_order_of(rows, indices);
+        Ok(self.shown_columns(rows))
+    }
+
+    /// Range of selected offsets (inclusive) when extending the selection
+    /// beyond a single row. Note that offsets are positions within the
+    /// filtered rows if a filter is active.
+    pub fn selection_range(&self) -> Option<(u64, u64)> {
+        match (self.selection_anchor, self.selected_offset()) {
+            (Some(anchor), Some(offset)) => Some((min(anchor, offset), max(anchor, offset))),
+            _ => None,
+        }
+    }
+
+    pub fn clear_selection_range(&mut self) {
+        self.selection_anchor = None;
+    }
+
+    /// Time taken to read the rows the last time, in microseconds
     pub fn elapsed(&self) -> Option<u128> {
         self.elapsed
     }
 
+    /// Time taken to pick the columns shown from the rows the last time, in
+    /// microseconds
+    pub fn subset_elapsed(&self) -> Option<u128> {
+        self.subset_elapsed
+    }
+
+    /// Rough estimate of the memory held for the currently buffered rows and
+    /// the reader's position index
+    pub fn memory_footprint(&self) -> usize {
+        let rows_bytes: usize = self
+            .rows
+            .iter()
+            .map(|row| {
+                row.fields
+                    .iter()
+                    .map(|f| f.capacity() + size_of::<String>())
+                    .sum::<usize>()
+            })
+            .sum();
+        rows_bytes + self.reader.get_pos_table_len() * size_of::<Position>()
+    }
+
     pub fn get_total_line_numbers(&self) -> Option<usize> {
         self.reader.get_total_line_numbers()
     }
@@ -247,19 +661,92 @@ impl RowsView {
         self.reader.get_total_line_numbers_approx()
     }
 
+    pub fn done_indexing(&self) -> bool {
+        self.reader.done()
+    }
+
     pub fn in_view(&self, row_index: u64) -> bool {
+        let position = self.position_of(row_index);
         let last_row = self.rows_from().saturating_add(self.num_rows());
-        if row_index >= self.rows_from() && row_index < last_row {
+        if position >= self.rows_from() && position < last_row {
             return true;
         }
         false
     }
 
     pub fn handle_control(&mut self, control: &Control) -> Result<()> {
+        // Extending the selection moves the same way as scrolling, while any
+        // other movement goes back to selecting a single row
+        let control = match control {
+            Control::SelectUp | Control::SelectDown => {
+                if self.selection_anchor.is_none() {
+                    self.selection_anchor = self.selected_offset();
+                }
+                if matches!(control, Control::SelectUp) {
+                    &Control::ScrollUp
+                } else {
+                    &Control::ScrollDown
+                }
+            }
+            Control::ScrollUp
+            | Control::ScrollDown
+            | Control::ScrollTop
+            | Control::ScrollBottom
+            | Control::ScrollPageUp
+            | Control::ScrollPageDown
+            | Control::ScrollTo(_)
+            | Control::ScrollToPercent(_)
+            | Control::ClearSelection => {
+                self.clear_selection_range();
+                control
+            }
+            _ => control,
+        };
+        match control {
+            Control::Nothing => {
+                if let Some(percent) = self.pending_percent {
+                    if self.reader.get_total_line_numbers().is_some() {
+                        self.scroll_to_percent(percent)?;
+                    }
+                }
+            }
+            // Stop refining once moved elsewhere
+            _ => self.pending_percent = None,
+        }
+        match control {
+            Control::Nothing if self.filter.is_some() => self.pending_position = None,
+            Control::Nothing => {
+                let total = self.reader.get_total_line_numbers();
+                if let (Some((rows_from, selected)), Some(total)) = (self.pending_position, total) {
+                    self.pending_position = None;
+                    let mut rows_from = min(rows_from, (total as u64).saturating_sub(1));
+                    if let Some(n) = self.bottom_rows_from() {
+                        rows_from = min(rows_from, n);
+                    }
+                    self.set_rows_from(rows_from)?;
+                    if self.selected.is_some() {
+                        self.set_selected(selected);
+                    }
+                }
+            }
+            Control::ScrollUp
+            | Control::ScrollDown
+            | Control::ScrollTop
+            | Control::ScrollBottom
+            | Control::ScrollPageUp
+            | Control::ScrollPageDown
+            | Control::ScrollTo(_)
+            | Control::ScrollToPercent(_) => self.pending_position = None,
+            _ => {}
+        }
         match control {
             Control::ScrollDown => {
                 if let Some(i) = self.selected {
-                    if i == self.num_rows - 1 {
+                    let last_row_before_margin = self
+                        .num_rows
+                        .saturating_sub(1)
+                        .saturating_sub(self.effective_scrolloff());
+                    if i >= last_row_before_margin && self.can_scroll_down() {
                         self.increase_rows_from(1)?;
                     } else {
                         self.increase_selected();
@@ -269,14 +756,14 @@ impl RowsView {
                 }
             }
             Control::ScrollPageDown => {
-                self.increase_rows_from(self.num_rows)?;
+                self.increase_rows_from(self.page_size())?;
                 if self.selected.is_some() {
                     self.select_top()
                 }
             }
             Control::ScrollUp => {
                 if let Some(i) = self.selected {
-                    if i == 0 {
+                    if i <= self.effective_scrolloff() && self.rows_from > 0 {
                         self.decrease_rows_from(1)?;
                     } else {
                         self.decrease_selected();
@@ -286,7 +773,7 @@ impl RowsView {
                 }
             }
             Control::ScrollPageUp => {
-                self.decrease_rows_from(self.num_rows)?;
+                self.decrease_rows_from(self.page_size())?;
                 if self.selected.is_some() {
                     self.select_top()
                 }
@@ -298,22 +785,34 @@ impl RowsView {
                 }
             }
             Control::ScrollBottom => {
-                if let Some(total) = self.get_total() {
-                    let rows_from = total.saturating_sub(self.num_rows as usize) as u64;
+                if let Some(rows_from) = self.bottom_rows_from() {
                     self.set_rows_from(rows_from)?;
                 }
                 if self.selected.is_some() {
                     self.select_bottom()
                 }
             }
+            Control::ToggleMark => {
+                self.toggle_mark_selected();
+            }
+            Control::ClearMarks => {
+                self.clear_marked();
+            }
+            Control::ScrollToPercent(percent) => {
+                self.scroll_to_percent(*percent)?;
+            }
             Control::ScrollTo(n) => {
-                let mut rows_from = n.saturating_sub(1) as u64;
+                // Keep the margin above the row gone to as well
+                let mut rows_from =
+                    (n.saturating_sub(1) as u64).saturating_sub(self.effective_scrolloff());
                 if let Some(n) = self.bottom_rows_from() {
                     rows_from = min(rows_from, n);
                 }
                 self.set_rows_from(rows_from)?;
                 if self.selected.is_some() {
-                    self.select_top()
+                    // Not at the top if near the bottom
+                    let index = n.saturating_sub(1) as u64;
+                    self.set_selected(index.saturating_sub(self.rows_from));
                 }
             }
             _ => {}
@@ -321,6 +820,29 @@ impl RowsView {
         Ok(())
     }
 
+    fn scroll_to_percent(&mut self, percent: u8) -> Result<()> {
+        let total = match self.get_total() {
+            Some(n) if n > 0 => n as u64,
+            _ => return Ok(()),
+        };
+        let index = min(total * min(percent, 100) as u64 / 100, total - 1);
+        let mut rows_from = index;
+        if let Some(n) = self.bottom_rows_from() {
+            rows_from = min(rows_from, n);
+        }
+        self.set_rows_from(rows_from)?;
+        if self.selected.is_some() {
+            self.set_selected(index - rows_from);
+        }
+        self.pending_percent =
+            if self.filter.is_none() && self.reader.get_total_line_numbers().is_none() {
+                Some(percent)
+            } else {
+                None
+            };
+        Ok(())
+    }
+
     fn get_total(&self) -> Option<usize> {
         if let Some(filter) = &self.filter {
             return Some(filter.total);
@@ -349,11 +871,32 @@ impl RowsView {
     fn bottom_rows_from(&self) -> Option<u64> {
         // fix type conversion craziness
         if let Some(n) = self.get_total() {
+            // allow scrolling past the last row to leave some blank lines as margin
+            let n = n.saturating_add(self.effective_scrolloff() as usize);
             return Some(n.saturating_sub(self.num_rows as usize) as u64);
         }
         None
     }
 
+    fn can_scroll_down(&self) -> bool {
+        match self.bottom_rows_from() {
+            Some(n) => self.rows_from < n,
+            None => true,
+        }
+    }
+
+    /// Number of rows to scroll by a page, keeping as many rows as the
+    /// overlap in view
+    fn page_size(&self) -> u64 {
+        // must scroll by at least one row
+        max(self.num_rows.saturating_sub(self.page_overlap), 1)
+    }
+
+    fn effective_scrolloff(&self) -> u64 {
+        // must leave room for at least one row
+        min(self.scrolloff, self.num_rows.saturating_sub(1))
+    }
+
     fn subset_columns(rows: &Vec<Row>, indices: &[usize]) -> Vec<Row> {
         let mut out = vec![];
         for row in rows {
@@ -362,18 +905,76 @@ impl RowsView {
         out
     }
 
+    fn shown_columns(&self, rows: Vec<Row>) -> Vec<Row> {
+        // Replaced first since the column may not be shown itself
+        let replaced: Option<Vec<String>> = self
+            .replace_preview
+            .as_ref()
+            .map(|preview| rows.iter().map(|row| preview.replace(row)).collect());
+        let mut rows = if let Some(columns_filter) = &self.columns_filter {
+            Self::subset_columns(&rows, columns_filter.indices())
+        } else {
+            rows
+        };
+        if let Some(replaced) = replaced {
+            let num_columns = self.headers_with_preview.len() - 1;
+            for (row, value) in rows.iter_mut().zip(replaced) {
+                row.fields.resize(num_columns, "".to_owned());
+                row.fields.push(value);
+            }
+        }
+        rows
+    }
+
+    /// Rows read in file order, put in the order of the indices
+    fn in_order_of(rows: Vec<Row>, indices: &[u64]) -> Vec<Row> {
+        if indices.windows(2).all(|w| w[0] < w[1]) {
+            return rows;
+        }
+        let mut rows: HashMap<u64, Row> = rows
+            .into_iter()
+            .map(|row| (row.record_num.saturating_sub(1) as u64, row))
+            .collect();
+        indices.iter().filter_map(|i| rows.remove(i)).collect()
+    }
+
     fn do_get_rows(&mut self) -> Result<()> {
         let start = Instant::now();
-        let mut rows = if let Some(filter) = &self.filter {
+        let sorted_indices = match (&self.filter, &self.sorted) {
+            (None, Some(sorted)) => {
+                Some(sorted.row_indices(self.rows_from, self.num_rows).to_vec())
+            }
+            _ => None,
+        };
+        let mut rows = if let Some(indices) = sorted_indices {
+            let rows = if self.raw_fields {
+                self.reader.get_raw_rows_for_indices(&indices)?
+            } else {
+                self.reader.get_rows_for_indices(&indices)?
+            };
+            Self::in_order_of(rows, &indices)
+        } else if self.raw_fields {
+            let indices: Vec<u64> = match &self.filter {
+                Some(filter) => filter.indices.clone(),
+                None => (self.rows_from..self.rows_from + self.num_rows).collect(),
+            };
+            self.reader.get_raw_rows_for_indices(&indices)?
+        } else if let Some(filter) = &self.filter {
             let indices = &filter.indices;
             self.reader.get_rows_for_indices(indices)?
         } else {
             self.reader.get_rows(self.rows_from, self.num_rows)?
         };
+        // Only getting rows for indices skips any
+        self.num_skipped = if self.sorted.is_some() || self.raw_fields || self.filter.is_some() {
+            self.reader.num_skipped()
+        } else {
+            0
+        };
         let elapsed = start.elapsed().as_micros();
-        if let Some(columns_filter) = &self.columns_filter {
-            rows = Self::subset_columns(&rows, columns_filter.indices());
-        }
+        let start = Instant::now();
+        rows = self.shown_columns(rows);
+        self.subset_elapsed = Some(start.elapsed().as_micros());
         self.rows = rows;
         self.elapsed = Some(elapsed);
         // current selected might be out of range, reset it
@@ -383,3 +984,237 @@ impl RowsView {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv::CsvConfig;
+    use crate::sort;
+    use std::io::Write;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::NamedTempFile;
+
+    fn rows_view(num_rows: u64) -> RowsView {
+        let config = Arc::new(CsvConfig::new("tests/data/cities.csv"));
+        RowsView::new(CsvLensReader::new(config).unwrap(), num_rows).unwrap()
+    }
+
+    fn wait_until(done: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if done() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("Timed out waiting for the background thread");
+    }
+
+    /// Rows view over CSV content, driven by controls the same way as the
+    /// app does, for testing how the view moves end to end
+    struct Harness {
+        view: RowsView,
+        config: Arc<CsvConfig>,
+        finder: Option<find::Finder>,
+        // The content is read from the file, which is removed when dropped
+        _file: NamedTempFile,
+    }
+
+    impl Harness {
+        /// View showing `num_rows` rows at a time, once the whole content has
+        /// been read so that e.g. the total number of rows is known
+        fn new(content: &str, num_rows: u64) -> Harness {
+            let mut file = NamedTempFile::new().unwrap();
+            file.write_all(content.as_bytes()).unwrap();
+            let config = Arc::new(CsvConfig::new(file.path().to_str().unwrap()));
+            let view =
+                RowsView::new(CsvLensReader::new(config.clone()).unwrap(), num_rows).unwrap();
+            wait_until(|| view.done_indexing());
+            Harness {
+                view,
+                config,
+                finder: None,
+                _file: file,
+            }
+        }
+
+        /// Content with a header and the given number of rows, where the
+        /// first field of each row is its number
+        fn numbered(num_records: usize, num_rows: u64) -> Harness {
+            let mut content = "n,square\n".to_owned();
+            for i in 1..=num_records {
+                content += &format!("{},{}\n", i, i * i);
+            }
+            Harness::new(&content, num_rows)
+        }
+
+        /// Handle the controls, updating the filter after each one like the
+        /// app since the filter only holds the rows shown
+        fn step(&mut self, controls: &[Control]) {
+            for control in controls {
+                self.view.handle_control(control).unwrap();
+                if let Some(finder) = &self.finder {
+                    self.view.set_filter(finder).unwrap();
+                }
+            }
+        }
+
+        /// Filter by the pattern once all matches have been found
+        fn filter(&mut self, pattern: &str) {
+            let target = find::Target::parse(pattern, self.view.all_headers()).unwrap();
+            let finder = find::Finder::new(self.config.clone(), target, None).unwrap();
+            wait_until(|| finder.done());
+            self.view.set_rows_from(0).unwrap();
+            self.view.set_filter(&finder).unwrap();
+            self.finder = Some(finder);
+        }
+
+        /// First field of each row shown
+        fn shown(&self) -> Vec<&str> {
+            self.view
+                .rows()
+                .iter()
+                .map(|row| row.fields[0].as_str())
+                .collect()
+        }
+
+        fn selected(&self) -> Option<&str> {
+            let row = self.view.rows().get(self.view.selected()? as usize)?;
+            Some(row.fields[0].as_str())
+        }
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut harness = Harness::numbered(20, 5);
+        assert_eq!(harness.shown(), ["1", "2", "3", "4", "5"]);
+        assert_eq!(harness.selected(), Some("1"));
+
+        // The selection moves down until the last row shown, then the rows
+        for _ in 0..5 {
+            harness.step(&[Control::ScrollDown]);
+        }
+        assert_eq!(harness.shown(), ["2", "3", "4", "5", "6"]);
+        assert_eq!(harness.selected(), Some("6"));
+
+        harness.step(&[Control::ScrollPageDown]);
+        assert_eq!(harness.shown()[0], "7");
+        assert_eq!(harness.selected(), Some("7"));
+
+        harness.step(&[Control::ScrollBottom]);
+        assert_eq!(harness.shown(), ["16", "17", "18", "19", "20"]);
+        assert_eq!(harness.selected(), Some("20"));
+
+        harness.step(&[Control::ScrollTop, Control::ScrollUp]);
+        assert_eq!(harness.shown()[0], "1");
+        assert_eq!(harness.selected(), Some("1"));
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut harness = Harness::numbered(20, 3);
+        harness.filter(":n ~ ^1");
+        assert_eq!(harness.view.num_filtered_rows(), Some(11));
+        assert_eq!(harness.shown(), ["1", "10", "11"]);
+
+        // Scrolling only goes through the matching rows
+        for _ in 0..3 {
+            harness.step(&[Control::ScrollDown]);
+        }
+        assert_eq!(harness.shown(), ["10", "11", "12"]);
+        assert_eq!(harness.selected(), Some("12"));
+
+        harness.step(&[Control::ScrollBottom]);
+        assert_eq!(harness.shown(), ["17", "18", "19"]);
+
+        harness.finder = None;
+        harness.view.reset_filter().unwrap();
+        assert_eq!(harness.view.num_filtered_rows(), None);
+    }
+
+    #[test]
+    fn test_reset_filter_while_sorted() {
+        let mut harness = Harness::numbered(20, 3);
+        let sorter = sort::Sorter::new(harness.config.clone(), 0, sort::SortOrder::Descending);
+        wait_until(|| sorter.sorted_rows().is_some());
+        harness.view.set_sorted(sorter.sorted_rows()).unwrap();
+        assert_eq!(harness.shown(), ["20", "19", "18"]);
+
+        harness.filter(":n ~ ^1");
+        harness.step(&[Control::ScrollDown, Control::ScrollDown]);
+        assert_eq!(harness.selected(), Some("11"));
+
+        // The selected row stays selected among the sorted rows
+        harness.finder = None;
+        harness.view.reset_filter().unwrap();
+        assert_eq!(harness.shown(), ["13", "12", "11"]);
+        assert_eq!(harness.selected(), Some("11"));
+        assert_eq!(harness.view.position(), (7, 2));
+    }
+
+    #[test]
+    fn test_set_num_rows_keeps_selected() {
+        let mut view = rows_view(10);
+        view.set_rows_from(20).unwrap();
+        view.set_selected(7);
+        assert_eq!(view.selected_offset(), Some(27));
+
+        // Shrinking scrolls so that the selected row is the last one shown
+        view.set_num_rows(5).unwrap();
+        assert_eq!(view.selected_offset(), Some(27));
+        assert_eq!(view.rows_from(), 23);
+        assert_eq!(view.selected(), Some(4));
+        assert_eq!(view.rows()[4].record_num, 28);
+
+        // Growing doesn't scroll
+        view.set_num_rows(20).unwrap();
+        assert_eq!(view.selected_offset(), Some(27));
+        assert_eq!(view.rows_from(), 23);
+        assert_eq!(view.rows().len(), 20);
+
+        // The selected row is kept if still shown
+        view.set_selected(2);
+        view.set_num_rows(3).unwrap();
+        assert_eq!(view.selected_offset(), Some(25));
+        assert_eq!(view.rows_from(), 23);
+
+        view.reset_selected();
+        view.set_num_rows(10).unwrap();
+        assert_eq!(view.selected_offset(), None);
+    }
+
+    #[test]
+    fn test_replace_preview() {
+        let mut harness = Harness::new("id,date\n1,2024/01/15\n2\n3,n/a\n", 3);
+        let headers = harness.view.all_headers().clone();
+        let preview = ReplacePreview::parse(r"(\d+)\/(\d+)\/(\d+)/$3.$2.$1", 1, &headers).unwrap();
+        harness.view.set_replace_preview(Some(preview)).unwrap();
+        assert_eq!(
+            harness.view.headers(),
+            &vec!["id", "date", "date (replaced)"]
+        );
+        let fields: Vec<&Vec<String>> = harness.view.rows().iter().map(|r| &r.fields).collect();
+        assert_eq!(
+            fields,
+            vec![
+                &vec!["1", "2024/01/15", "15.01.2024"],
+                &vec!["2", "", ""],
+                &vec!["3", "n/a", "n/a"],
+            ]
+        );
+
+        // Still shown when the column itself isn't
+        let columns_filter = ColumnsFilter::parse("id", &headers).unwrap();
+        harness.view.set_columns_filter(columns_filter).unwrap();
+        assert_eq!(harness.view.headers(), &vec!["id", "date (replaced)"]);
+        assert_eq!(harness.view.rows()[0].fields, vec!["1", "15.01.2024"]);
+
+        harness.view.set_replace_preview(None).unwrap();
+        assert_eq!(harness.view.headers(), &vec!["id"]);
+        assert_eq!(harness.view.rows()[0].fields, vec!["1"]);
+
+        assert!(ReplacePreview::parse("abc", 1, &headers).is_err());
+        assert!(ReplacePreview::parse("(/x", 1, &headers).is_err());
+        assert!(ReplacePreview::parse("a/b", 2, &headers).is_err());
+    }
+}